@@ -0,0 +1,77 @@
+/// 电平包络的时间常数（秒）：按约 300ms 的均方值估计电平，避免跟随单个音符或音节起伏
+const LEVEL_SECS: f32 = 0.3;
+/// 电平高于目标、需要降低增益时的时间常数（秒）
+const ATTACK_SECS: f32 = 0.2;
+/// 电平低于目标、需要提升增益时的时间常数（秒），远慢于 attack，避免音乐的强弱段之间产生“抽吸”
+const RELEASE_SECS: f32 = 3.0;
+/// 电平低于该值（dBFS）时视为静音或停顿，保持当前增益不变，不去放大底噪
+const GATE_DBFS: f32 = -50.0;
+/// 增益上下限（dB）
+const MAX_GAIN_DB: f32 = 20.0;
+const MIN_GAIN_DB: f32 = -20.0;
+/// 输出允许的最大峰值（线性），超过时立即压低增益而不是削波
+const PEAK_LIMIT: f32 = 0.97;
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// 一阶平滑系数：每个采样向目标值靠近的比例
+fn smoothing(secs: f32, sample_rate: u32) -> f32 {
+    1.0 - (-1.0 / (secs * sample_rate as f32)).exp()
+}
+
+/// 录制时的自动增益控制，把 RMS 电平平滑地拉向目标值。
+///
+/// 增益随采样逐渐变化：电平偏高时较快降低（attack），偏低时缓慢提升（release），
+/// 静音段保持增益不变；放大后的峰值接近满量程时立即压低增益，因此不会引入削波。
+pub struct Agc {
+    target: f32,
+    gate: f32,
+    min_gain: f32,
+    max_gain: f32,
+    level_coeff: f32,
+    attack: f32,
+    release: f32,
+    /// 平滑后的均方值
+    mean_square: f32,
+    gain: f32,
+}
+
+impl Agc {
+    /// 以目标 RMS 电平（dBFS）创建 AGC，`sample_rate` 为处理的采样率
+    pub fn new(target_dbfs: f32, sample_rate: u32) -> Self {
+        Self {
+            target: db_to_linear(target_dbfs),
+            gate: db_to_linear(GATE_DBFS),
+            min_gain: db_to_linear(MIN_GAIN_DB),
+            max_gain: db_to_linear(MAX_GAIN_DB),
+            level_coeff: smoothing(LEVEL_SECS, sample_rate),
+            attack: smoothing(ATTACK_SECS, sample_rate),
+            release: smoothing(RELEASE_SECS, sample_rate),
+            mean_square: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// 原地处理一段 16-bit PCM 单声道采样，状态在多次调用之间延续
+    pub fn process(&mut self, samples: &mut [i16]) {
+        for sample in samples {
+            let input = *sample as f32 / 32768.0;
+            self.mean_square += (input * input - self.mean_square) * self.level_coeff;
+
+            let rms = self.mean_square.sqrt();
+            if rms > self.gate {
+                let desired = (self.target / rms).clamp(self.min_gain, self.max_gain);
+                let coeff = if desired < self.gain { self.attack } else { self.release };
+                self.gain += (desired - self.gain) * coeff;
+            }
+            // 突发的峰值直接把增益压到刚好不超限，之后再按 release 缓慢恢复
+            if (input * self.gain).abs() > PEAK_LIMIT {
+                self.gain = PEAK_LIMIT / input.abs();
+            }
+
+            *sample = (input * self.gain * 32768.0).clamp(-32768.0, 32767.0) as i16;
+        }
+    }
+}
@@ -1,15 +1,273 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SampleFormat, Stream, StreamConfig};
+use cpal::{BufferSize, SampleFormat, SampleRate, Stream, StreamConfig, SupportedBufferSize, SupportedStreamConfig};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::fs::File;
-use std::io::{Write, Seek, SeekFrom};
+use std::io::{self, Write, Seek, SeekFrom};
 use num_traits::ToPrimitive;
+use crate::agc::Agc;
+use crate::audio_transcribe::{self, ResampleQuality, StreamResampler};
 use std::time::Duration;
 use std::thread;
+/// 作为输出路径时表示把 WAV 流式写到标准输出
+pub const STDOUT_PATH: &str = "-";
+
+/// 录音文件的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CaptureFormat {
+    /// 16-bit PCM 单声道 WAV
+    #[default]
+    Wav,
+    /// 不带文件头的 16-bit 小端 PCM 单声道采样
+    Raw,
+}
+
+impl CaptureFormat {
+    /// 该格式对应的文件扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            CaptureFormat::Wav => "wav",
+            CaptureFormat::Raw => "raw",
+        }
+    }
+}
+
+/// 多声道录音混合为单声道的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Downmix {
+    /// 所有通道取平均
+    #[default]
+    Average,
+    /// 只取左声道
+    Left,
+    /// 只取右声道
+    Right,
+    /// 中置信号：设备提供 3 个以上通道时取前中置通道（WAVE 通道顺序中的第 3 个），
+    /// 否则为 M = (L + R) / 2，立体声下与 `average` 相同
+    Mid,
+}
+
+impl Downmix {
+    /// 把一帧混合为 [-1.0, 1.0] 的单声道采样
+    fn mix<T>(self, frame: &[T]) -> f32
+    where
+        T: cpal::Sample + cpal::SizedSample + ToPrimitive,
+    {
+        let last = frame.len() - 1;
+        match self {
+            Downmix::Average => frame.iter().map(|&s| to_unit_f32(s)).sum::<f32>() / frame.len() as f32,
+            Downmix::Left => to_unit_f32(frame[0]),
+            Downmix::Right => to_unit_f32(frame[1.min(last)]),
+            Downmix::Mid if frame.len() >= 3 => to_unit_f32(frame[2]),
+            Downmix::Mid => (to_unit_f32(frame[0]) + to_unit_f32(frame[1.min(last)])) / 2.0,
+        }
+    }
+}
+
+/// 录音文件中每一帧的存储方式
+///
+/// 采集、重采样和自动增益始终在 16-bit 单声道上进行，写入前才按这里的布局编码：
+/// 单声道信号复制到每个通道，再转换为目标位深。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleLayout {
+    pub channels: u16,
+    /// 8、16、24 或 32（整数），浮点时为 32
+    pub bits_per_sample: u16,
+    pub float: bool,
+}
+
+impl Default for SampleLayout {
+    fn default() -> Self {
+        Self { channels: 1, bits_per_sample: 16, float: false }
+    }
+}
+
+impl SampleLayout {
+    /// 由 WAV 文件的 `Spec` 得到对应的布局，不支持的位深返回错误
+    pub fn from_wav_spec(spec: hound::WavSpec) -> Result<Self, String> {
+        let float = spec.sample_format == hound::SampleFormat::Float;
+        match (float, spec.bits_per_sample) {
+            (false, 8 | 16 | 24 | 32) | (true, 32) if spec.channels > 0 => {
+                Ok(Self { channels: spec.channels, bits_per_sample: spec.bits_per_sample, float })
+            }
+            _ => Err(format!(
+                "unsupported WAV format: {} channel(s), {}-bit {}",
+                spec.channels,
+                spec.bits_per_sample,
+                if float { "float" } else { "integer" }
+            )),
+        }
+    }
+
+    /// 每帧的字节数
+    fn block_align(self) -> u16 {
+        self.channels * (self.bits_per_sample / 8)
+    }
+
+    /// 把 16-bit 单声道采样编码为该布局的小端字节
+    fn encode(self, pcm: &[i16]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(pcm.len() * self.block_align() as usize);
+        for &sample in pcm {
+            for _ in 0..self.channels {
+                match (self.float, self.bits_per_sample) {
+                    (true, _) => bytes.extend_from_slice(&(sample as f32 / 32768.0).to_le_bytes()),
+                    // 8-bit WAV 为无符号采样，静音为 128
+                    (false, 8) => bytes.push(((sample >> 8) + 128) as u8),
+                    (false, 24) => bytes.extend_from_slice(&((sample as i32) << 8).to_le_bytes()[..3]),
+                    (false, 32) => bytes.extend_from_slice(&((sample as i32) << 16).to_le_bytes()),
+                    _ => bytes.extend_from_slice(&sample.to_le_bytes()),
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// 每个采集缓冲区（已转换为 16-bit PCM 单声道）的回调，例如用于绘制音量表
+pub type BufferCallback = Box<dyn FnMut(&[i16]) + Send>;
+
+/// 一次录制结束后的摘要信息，由 [`AudioCapture::stop`] 返回
+#[derive(Debug, Clone)]
+pub struct CaptureSummary {
+    /// 录音文件路径
+    pub path: String,
+    /// 按实际写入的采样数计算的时长（秒）
+    pub duration_secs: f64,
+    /// 文件的采样率
+    pub sample_rate: u32,
+    /// 文件的通道数（默认混合为单声道，按参考文件录制时与其一致）
+    pub channels: u16,
+    /// 峰值电平（dBFS），全程静音时为负无穷
+    pub peak_dbfs: f64,
+    /// 达到满量程、可能已经削波的采样数
+    pub clipped_samples: u64,
+}
+
+/// 音频线程中累计的录制统计
+#[derive(Default)]
+struct CaptureStats {
+    /// 实际成功写入文件的采样数
+    samples: AtomicU64,
+    /// 采样绝对值的最大值
+    peak: AtomicU32,
+    /// 达到满量程的采样数
+    clipped: AtomicU64,
+}
+
+impl CaptureStats {
+    fn reset(&self) {
+        self.samples.store(0, Ordering::SeqCst);
+        self.peak.store(0, Ordering::SeqCst);
+        self.clipped.store(0, Ordering::SeqCst);
+    }
+
+    fn record(&self, pcm: &[i16]) {
+        let peak = pcm.iter().map(|&s| (s as i32).unsigned_abs()).max().unwrap_or(0);
+        let clipped = pcm.iter().filter(|&&s| s == i16::MAX || s == i16::MIN).count();
+        self.samples.fetch_add(pcm.len() as u64, Ordering::SeqCst);
+        self.peak.fetch_max(peak, Ordering::SeqCst);
+        self.clipped.fetch_add(clipped as u64, Ordering::SeqCst);
+    }
+}
+
+/// 录音的写入目标
+enum CaptureSink {
+    File(File),
+    /// 管道无法回填大小字段，因此文件头中的大小固定为 0xFFFFFFFF；
+    /// 文件头先暂存，第一次写入数据时才输出，避免建流回退时写出两个文件头
+    Stdout { stdout: io::Stdout, header: Option<Vec<u8>> },
+    /// 不写出任何数据，采样只经缓冲区回调交给调用方，见 [`AudioCapture::in_memory`]
+    Discard,
+}
+
+impl CaptureSink {
+    fn create(path: &str) -> io::Result<Self> {
+        if path.is_empty() {
+            Ok(CaptureSink::Discard)
+        } else if path == STDOUT_PATH {
+            Ok(CaptureSink::Stdout { stdout: io::stdout(), header: None })
+        } else {
+            File::create(path).map(CaptureSink::File)
+        }
+    }
+
+    /// 写入（或重写）WAV 文件头
+    fn write_header(&mut self, sample_rate: u32, layout: SampleLayout) -> io::Result<()> {
+        match self {
+            CaptureSink::File(file) => {
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&wav_header(sample_rate, layout, 0))
+            }
+            CaptureSink::Stdout { header, .. } => {
+                *header = Some(wav_header(sample_rate, layout, u32::MAX));
+                Ok(())
+            }
+            CaptureSink::Discard => Ok(()),
+        }
+    }
+
+    fn write_data(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            CaptureSink::File(file) => file.write_all(bytes),
+            CaptureSink::Stdout { stdout, header } => {
+                let mut stdout = stdout.lock();
+                if let Some(header) = header.take() {
+                    stdout.write_all(&header)?;
+                }
+                stdout.write_all(bytes)?;
+                stdout.flush()
+            }
+            CaptureSink::Discard => Ok(()),
+        }
+    }
+
+    /// 结束写入：文件返回数据字节数（WAV 会先写回大小字段）；标准输出只做 flush，返回 `None`
+    fn finish(&mut self, format: CaptureFormat) -> io::Result<Option<u64>> {
+        match self {
+            CaptureSink::File(file) => {
+                file.flush()?;
+                match format {
+                    CaptureFormat::Wav => update_wav_header(file).map(Some),
+                    CaptureFormat::Raw => file.stream_position().map(Some),
+                }
+            }
+            CaptureSink::Stdout { .. } => self.write_data(&[]).map(|()| None),
+            CaptureSink::Discard => Ok(None),
+        }
+    }
+}
+
 pub struct AudioCapture {
     stream: Option<Stream>,
+    /// 输出文件路径，[`STDOUT_PATH`] 表示写到标准输出
     file_name: String,
-    file: Option<Arc<Mutex<File>>>,
+    file: Option<Arc<Mutex<CaptureSink>>>,
+    /// 要回环录制的输出设备名称，`None` 表示系统默认输出设备
+    device_name: Option<String>,
+    /// 期望的采样率，设备不支持时选择最接近的配置
+    preferred_sample_rate: Option<u32>,
+    /// 期望的通道数，设备不支持时选择最接近的配置
+    preferred_channels: Option<u16>,
+    /// 强制按该通道数建流并解读采集到的数据，不管设备报告的配置
+    forced_channels: Option<u16>,
+    /// 期望的缓冲区大小（帧），`None` 表示使用驱动默认值
+    buffer_size: Option<u32>,
+    /// 只录制这些通道（从 0 开始）并混合为单声道，`None` 表示混合全部通道
+    selected_channels: Option<Vec<usize>>,
+    /// 未指定通道时多声道混合为单声道的方式
+    downmix: Downmix,
+    /// 可选的缓冲区回调，默认为 `None`，不产生额外开销
+    on_buffer: Option<Arc<Mutex<BufferCallback>>>,
+    /// 录制统计，用于在停止时校验 WAV 头并生成 [`CaptureSummary`]
+    stats: Arc<CaptureStats>,
+    /// 写入 WAV 头的采样率
+    output_rate: u32,
+    /// 录音文件的格式
+    format: CaptureFormat,
+    /// 写入文件的通道数和位深
+    layout: SampleLayout,
+    /// 自动增益控制的目标电平（dBFS），`None` 表示不启用
+    agc_target_dbfs: Option<f32>,
 }
 
 impl AudioCapture {
@@ -18,97 +276,359 @@ impl AudioCapture {
             stream: None,
             file_name,
             file: None,
+            device_name: None,
+            preferred_sample_rate: None,
+            preferred_channels: None,
+            forced_channels: None,
+            buffer_size: None,
+            selected_channels: None,
+            downmix: Downmix::default(),
+            on_buffer: None,
+            stats: Arc::new(CaptureStats::default()),
+            output_rate: 0,
+            format: CaptureFormat::Wav,
+            layout: SampleLayout::default(),
+            agc_target_dbfs: None,
+        }
+    }
+
+    /// 不写出文件的采集：采样只通过 [`AudioCapture::set_on_buffer`] 交给调用方，例如滚动录音只在内存中保留最近的一段
+    pub fn in_memory() -> Self {
+        Self::new(String::new())
+    }
+
+    /// 按参考 WAV 文件的 `Spec` 录制：采样率、通道数和位深都与其一致，方便生成格式统一的数据集。
+    ///
+    /// 设备不支持该采样率时在写入路径中重采样；录音先混合为单声道，再复制到参考文件的每个通道
+    pub fn from_wav_spec(file_name: String, spec: hound::WavSpec) -> Result<Self, String> {
+        let layout = SampleLayout::from_wav_spec(spec)?;
+        let mut capture = Self::new(file_name);
+        capture.set_preferred_format(Some(spec.sample_rate), Some(spec.channels.min(2)));
+        capture.layout = layout;
+        Ok(capture)
+    }
+
+    /// 注册缓冲区回调，在音频线程中对每个写入文件的缓冲区调用一次。
+    ///
+    /// 回调运行在实时音频线程上，应尽快返回；需在 [`AudioCapture::start`] 之前设置。
+    pub fn set_on_buffer(&mut self, callback: impl FnMut(&[i16]) + Send + 'static) {
+        self.on_buffer = Some(Arc::new(Mutex::new(Box::new(callback))));
+    }
+
+    /// 输出文件路径
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// 是否把录音写到标准输出；此时调用方应开启 `--raw` 模式（[`crate::progress::set_raw`]），让状态信息改写到 stderr
+    pub fn is_stdout(&self) -> bool {
+        self.file_name == STDOUT_PATH
+    }
+
+    /// 按名称选择要回环录制的输出设备（播放端点），`None` 表示系统默认输出设备。
+    ///
+    /// 名称不区分大小写，先找完全相同的设备，没有时接受唯一一个包含该名称的设备；需在 [`AudioCapture::start`] 之前设置
+    pub fn set_device(&mut self, name: Option<String>) {
+        self.device_name = name;
+    }
+
+    /// 设置期望的采样率和通道数，`None` 表示沿用设备默认值
+    pub fn set_preferred_format(&mut self, sample_rate: Option<u32>, channels: Option<u16>) {
+        self.preferred_sample_rate = sample_rate;
+        self.preferred_channels = channels;
+    }
+
+    /// 强制以 `channels` 个通道建流，用于报告了错误通道配置的设备；`None` 表示以设备报告的配置为准（默认）。
+    ///
+    /// 采集到的数据按该通道数切分为帧再混合为单声道；需在 [`AudioCapture::start`] 之前设置
+    pub fn set_forced_channels(&mut self, channels: Option<u16>) {
+        self.forced_channels = channels;
+    }
+
+    /// 按 [`AudioCapture::set_forced_channels`] 替换配置中的通道数，其余参数保持不变
+    fn force_channels(&self, config: SupportedStreamConfig) -> SupportedStreamConfig {
+        match self.forced_channels.filter(|&c| c != config.channels()) {
+            Some(channels) => {
+                status!("Forcing {} channel(s) instead of the {} reported by the device", channels, config.channels());
+                SupportedStreamConfig::new(channels, config.sample_rate(), *config.buffer_size(), config.sample_format())
+            }
+            None => config,
+        }
+    }
+
+    /// 按期望的采样率（未指定时按 48kHz 估计）和文件布局估算录制 `duration` 需要的字节数，用于录制前检查磁盘空间
+    pub fn estimated_size(&self, duration: Duration) -> u64 {
+        let rate = self.preferred_sample_rate.unwrap_or(48000) as u64;
+        let bytes_per_frame = match self.format {
+            CaptureFormat::Wav => self.layout.block_align() as u64,
+            CaptureFormat::Raw => 2,
+        };
+        (duration.as_secs_f64() * (rate * bytes_per_frame) as f64) as u64
+    }
+
+    /// 设置采集缓冲区大小（帧），较小的值可以降低延迟；设备不支持时回退到驱动默认值
+    pub fn set_buffer_size(&mut self, frames: Option<u32>) {
+        self.buffer_size = frames;
+    }
+
+    /// 只录制指定的通道（从 1 开始编号），把它们混合为单声道写入；`None` 表示混合全部通道（默认）。
+    ///
+    /// 指定后会选用通道数足够的设备配置，不再局限于单声道或立体声；需在 [`AudioCapture::start`] 之前设置
+    pub fn set_channel_selection(&mut self, channels: Option<&[u16]>) {
+        self.selected_channels = channels.map(|channels| channels.iter().map(|&c| c as usize - 1).collect());
+    }
+
+    /// 设置多声道混合为单声道的方式，默认各通道取平均；指定了通道（[`AudioCapture::set_channel_selection`]）时不起作用
+    pub fn set_downmix(&mut self, downmix: Downmix) {
+        self.downmix = downmix;
+    }
+
+    /// 设备配置的通道数不足以覆盖所选通道时打印错误并退出
+    fn check_channel_selection(&self, device_channels: u16) {
+        if let Some(&highest) = self.selected_channels.as_ref().and_then(|selected| selected.iter().max()) {
+            if highest >= device_channels as usize {
+                eprintln!(
+                    "--channel {} is out of range: the device provides {} channel(s)",
+                    highest + 1,
+                    device_channels
+                );
+                std::process::exit(1);
+            }
         }
     }
 
+    /// 启用自动增益控制，把电平平滑地拉向目标 RMS（dBFS）；`None` 表示关闭（默认）
+    pub fn set_agc(&mut self, target_dbfs: Option<f32>) {
+        self.agc_target_dbfs = target_dbfs;
+    }
+
+    /// 设置录音文件的格式，默认为 WAV；需在 [`AudioCapture::start`] 之前设置
+    pub fn set_format(&mut self, format: CaptureFormat) {
+        self.format = format;
+    }
+
     pub fn start(&mut self) {
         let host = cpal::default_host();
-        // 这里依然使用输出设备来捕获系统输出（注意需操作系统支持 loopback 模式）
-        let device = host
-            .default_output_device()
-            .expect("Failed to get default output device");
-        println!(
+        // 这里依然使用输出设备来捕获系统输出（注意需操作系统支持 loopback 模式，
+        // Windows 上 cpal 的 WASAPI 后端会对输出设备建立回环输入流）
+        let device = match &self.device_name {
+            Some(name) => find_output_device(&host, name).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }),
+            None => host
+                .default_output_device()
+                .expect("Failed to get default output device"),
+        };
+        status!(
             "Using output device: {}",
             device.name().unwrap_or("Unknown".to_string())
         );
 
-        let config = device
-            .default_output_config()
-            .expect("Failed to get default output config");
-        println!("Default output config: {:?}", config);
+        let default_config = device.default_output_config().ok();
+        status!("Default output config: {:?}", default_config);
 
-        let sample_format = config.sample_format();
-        let config: StreamConfig = config.into();
+        let min_channels = self.selected_channels.as_ref().and_then(|s| s.iter().max()).map(|&c| c as u16 + 1);
+        let config = select_config(&device, self.preferred_sample_rate, self.forced_channels.or(self.preferred_channels), min_channels)
+            .or_else(|| default_config.clone())
+            .expect("No usable output config found on the device");
+        let config = self.force_channels(config);
+        status!("Selected capture config: {:?}", config);
+        self.check_channel_selection(config.channels());
+        if let Some(selected) = &self.selected_channels {
+            let names: Vec<String> = selected.iter().map(|c| (c + 1).to_string()).collect();
+            status!("Recording channel(s) {} of {}, mixed down to mono", names.join(","), config.channels());
+        }
 
         // 创建输出文件，并写入 WAV 文件头的占位数据
         let file = Arc::new(Mutex::new(
-            File::create(&self.file_name).expect("Failed to create output file"),
+            CaptureSink::create(&self.file_name).expect("Failed to create output file"),
         ));
-        {
-            let mut file_lock = file.lock().unwrap();
-            // 调用时去掉 sample_format 参数，因为我们固定输出为 16-bit PCM 单声道
-            write_wav_header(&mut *file_lock, &config);
-        }
         // 保存文件句柄，方便后续更新文件头
         self.file = Some(file.clone());
 
-        let err_fn = |err| eprintln!("An error occurred on the output audio stream: {}", err);
-
-        // 只处理 I16, F32, F64 格式，其他格式不支持
-        let stream = match sample_format {
-            SampleFormat::I16 => self.capture::<i16>(&device, &config, file.clone(), err_fn),
-            SampleFormat::F32 => self.capture::<f32>(&device, &config, file.clone(), err_fn),
-            SampleFormat::F64 => self.capture::<f64>(&device, &config, file.clone(), err_fn),
-            _ => panic!("Unsupported sample format"),
+        // 所选配置建流失败时，回退到设备默认配置再试一次
+        let stream = match self.build_stream(&device, &config, file.clone()) {
+            Ok(stream) => stream,
+            Err(e) => match default_config.map(|d| self.force_channels(d)).filter(|d| *d != config) {
+                Some(fallback) => {
+                    eprintln!("Failed to build stream with {:?}: {}. Falling back to {:?}", config, e, fallback);
+                    self.check_channel_selection(fallback.channels());
+                    self.build_stream(&device, &fallback, file.clone())
+                        .expect("Failed to build input stream")
+                }
+                None => panic!("Failed to build input stream: {}", e),
+            },
         };
 
-        let stream = stream.expect("Failed to build input stream");
+        // 裸 PCM 没有文件头，把采样参数打印到 stderr 供下游按此解码
+        if self.format == CaptureFormat::Raw {
+            eprintln!("Raw PCM output: signed 16-bit little-endian, {} Hz, 1 channel", self.output_rate);
+        }
         stream.play().expect("Failed to play the stream");
         self.stream = Some(stream);
     }
 
+    /// 按给定配置写入 WAV 文件头并创建输入流
+    fn build_stream(
+        &mut self,
+        device: &cpal::Device,
+        config: &SupportedStreamConfig,
+        file: Arc<Mutex<CaptureSink>>,
+    ) -> Result<Stream, cpal::BuildStreamError> {
+        let sample_format = config.sample_format();
+        let buffer_size = self.buffer_size.filter(|&frames| match config.buffer_size() {
+            SupportedBufferSize::Range { min, max } if !(*min..=*max).contains(&frames) => {
+                eprintln!(
+                    "Warning: buffer size of {} frames is outside the supported range {}..={}, using the driver default",
+                    frames, min, max
+                );
+                false
+            }
+            _ => true,
+        });
+        let mut config: StreamConfig = config.clone().into();
+
+        // 设备不支持期望的采样率时，在写入路径中重采样到期望值
+        let device_rate = config.sample_rate.0;
+        let output_rate = self.preferred_sample_rate.unwrap_or(device_rate);
+        if output_rate != device_rate {
+            status!("Device runs at {} Hz, resampling to the requested {} Hz", device_rate, output_rate);
+        }
+        // 每次建流都需要一个全新的重采样器
+        let new_resampler = || -> Result<Option<StreamResampler>, cpal::BuildStreamError> {
+            if output_rate == device_rate {
+                return Ok(None);
+            }
+            StreamResampler::new(device_rate, output_rate, ResampleQuality::Medium)
+                .map(Some)
+                .map_err(|_| cpal::BuildStreamError::StreamConfigNotSupported)
+        };
+        if let Some(channels) = self.preferred_channels.filter(|&c| c != config.channels) {
+            status!(
+                "Device provides {} channel(s) instead of the requested {}; the recording is mixed down to mono",
+                config.channels, channels
+            );
+        }
+
+        // 调用时去掉 sample_format 参数，文件格式由 self.layout 决定，与设备的采样格式无关
+        if self.format == CaptureFormat::Wav {
+            file.lock()
+                .unwrap()
+                .write_header(output_rate, self.layout)
+                .expect("Failed to write WAV header");
+        }
+        self.output_rate = output_rate;
+        self.stats.reset();
+
+        // 先按指定的缓冲区大小建流，设备拒绝时再以驱动默认值重试
+        if let Some(frames) = buffer_size {
+            config.buffer_size = BufferSize::Fixed(frames);
+            match self.capture_format(sample_format, device, &config, file.clone(), new_resampler()?) {
+                Ok(stream) => {
+                    status!("Using a capture buffer of {} frames", frames);
+                    return Ok(stream);
+                }
+                Err(e) => {
+                    eprintln!("Warning: device rejected a buffer size of {} frames ({}), using the driver default", frames, e);
+                    config.buffer_size = BufferSize::Default;
+                }
+            }
+        }
+        self.capture_format(sample_format, device, &config, file, new_resampler()?)
+    }
+
+    /// 按采样格式分派到对应的 [`AudioCapture::capture`]
+    fn capture_format(
+        &self,
+        sample_format: SampleFormat,
+        device: &cpal::Device,
+        config: &StreamConfig,
+        file: Arc<Mutex<CaptureSink>>,
+        resampler: Option<StreamResampler>,
+    ) -> Result<Stream, cpal::BuildStreamError> {
+        let err_fn = |err| eprintln!("An error occurred on the output audio stream: {}", err);
+
+        // 只处理 I16, F32, F64 格式，其他格式不支持
+        match sample_format {
+            SampleFormat::I16 => self.capture::<i16>(device, config, file, resampler, err_fn),
+            SampleFormat::F32 => self.capture::<f32>(device, config, file, resampler, err_fn),
+            SampleFormat::F64 => self.capture::<f64>(device, config, file, resampler, err_fn),
+            _ => Err(cpal::BuildStreamError::StreamConfigNotSupported),
+        }
+    }
+
     fn capture<T>(
         &self,
         device: &cpal::Device,
         config: &StreamConfig,
-        file: Arc<Mutex<File>>,
+        file: Arc<Mutex<CaptureSink>>,
+        mut resampler: Option<StreamResampler>,
         err_fn: fn(cpal::StreamError),
     ) -> Result<Stream, cpal::BuildStreamError>
     where
         T: cpal::Sample + cpal::SizedSample + ToPrimitive,
     {
         let channels = config.channels as usize;
+        let selected = self.selected_channels.clone();
+        let downmix = self.downmix;
+        let on_buffer = self.on_buffer.clone();
+        let stats = self.stats.clone();
+        let layout = self.layout;
+        // 设备报告的通道数不对时缓冲区长度可能不是整帧，多出的半帧直接丢弃，只警告一次
+        let mut warned_partial_frame = false;
+        // 写入路径中的采样已经是输出采样率
+        let mut agc = self.agc_target_dbfs.map(|target| Agc::new(target, self.output_rate));
 
         device.build_input_stream(
             config,
             move |data: &[T], _: &cpal::InputCallbackInfo| {
-                let mut file_lock = file.lock().unwrap();
-                println!("Captured {} frames", data.len() / channels);
-
-                // 判断捕获到的是单声道还是立体声
-                if channels == 1 {
-                    // 单声道：直接写入每个采样
-                    for &sample in data {
-                        Self::write_sample(&mut file_lock, sample);
+                status!("Captured {} frames", data.len() / channels);
+                if !data.len().is_multiple_of(channels) && !warned_partial_frame {
+                    warned_partial_frame = true;
+                    eprintln!(
+                        "Warning: a capture buffer of {} samples is not a whole number of {}-channel frames; the device may misreport its channels (try --force-mono or --force-stereo)",
+                        data.len(),
+                        channels
+                    );
+                }
+
+                // 需要重采样时：先混合为 f32 单声道，重采样后再转换为 16-bit PCM
+                if let Some(resampler) = resampler.as_mut() {
+                    let mono = mix_to_mono(data, channels, selected.as_deref(), downmix);
+                    let mut pcm: Vec<i16> = resampler
+                        .process(&mono)
+                        .iter()
+                        .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
+                        .collect();
+                    if let Some(agc) = agc.as_mut() {
+                        agc.process(&mut pcm);
                     }
-                } else if channels == 2 {
-                    // 立体声：混合左右通道（均值）转换为单声道后写入
-                    for frame in data.chunks(2) {
-                        let mut left_sample = frame[0].to_f32().unwrap();
-                        let mut right_sample = frame[1].to_f32().unwrap();
-                    
-                        // 对i16样本进行归一化
-                        if std::mem::size_of::<T>() == 2 {
-                            left_sample /= 32768.0;
-                            right_sample /= 32768.0;
-                        }
-                    
-                        let mixed_sample = (left_sample + right_sample) / 2.0;
-                        let pcm_value = (mixed_sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
-                        Self::write_sample(&mut file_lock, pcm_value);
+                    write_pcm(&file, &pcm, layout, &stats);
+                    if let Some(callback) = &on_buffer {
+                        (callback.lock().unwrap())(&pcm);
                     }
+                    return;
+                }
+
+                // 统一转换为 16-bit PCM 单声道：未指定通道的单声道直接转换每个采样，
+                // 其余按 --channel 或 --downmix 混合（默认取均值）
+                let mut pcm: Vec<i16> = if selected.is_none() && channels == 1 {
+                    data.iter().map(|&sample| to_pcm_i16(sample)).collect()
                 } else {
-                    panic!("Unsupported number of channels: {}", channels);
+                    mix_to_mono(data, channels, selected.as_deref(), downmix)
+                        .into_iter()
+                        .map(|s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
+                        .collect()
+                };
+
+                if let Some(agc) = agc.as_mut() {
+                    agc.process(&mut pcm);
+                }
+                write_pcm(&file, &pcm, layout, &stats);
+
+                if let Some(callback) = &on_buffer {
+                    (callback.lock().unwrap())(&pcm);
                 }
             },
             err_fn,
@@ -116,27 +636,10 @@ impl AudioCapture {
         )
     }
 
-    /// 将采样数据写入文件，转换为 16-bit PCM 格式
-    fn write_sample<T>(file_lock: &mut File, sample: T)
-    where
-        T: cpal::Sample + cpal::SizedSample + ToPrimitive,
-    {
-        if std::mem::size_of::<T>() == 4 {
-            // 对于 F32 和 F64，将浮点数转换为 16-bit PCM
-            let pcm_value = (sample.to_f32().unwrap() * 32767.0)
-                .clamp(-32768.0, 32767.0) as i16;
-            let bytes = pcm_value.to_le_bytes();
-            file_lock.write_all(&bytes).unwrap();
-        } else if std::mem::size_of::<T>() == 2 {
-            // 对于 I16，直接写入
-            let bytes = sample.to_i16().unwrap().to_le_bytes();
-            file_lock.write_all(&bytes).unwrap();
-        } else {
-            panic!("Unsupported sample type size");
-        }
-    }
-
-    pub fn stop(&mut self) {
+    /// 停止录制并写回 WAV 头，返回本次录制的摘要；从未开始录制时返回 `None`。
+    ///
+    /// 写到标准输出时无法回填大小字段，文件头中的大小保持为 0xFFFFFFFF，也不再校验数据长度。
+    pub fn stop(&mut self) -> Option<CaptureSummary> {
         // 取出流对象并暂停
         if let Some(stream) = self.stream.take() {
             stream.pause().expect("Failed to pause stream");
@@ -145,41 +648,216 @@ impl AudioCapture {
         }
 
         // 更新 WAV 文件头前先 flush 文件，确保所有数据已写入磁盘
-        if let Some(file_arc) = &self.file {
-            let mut file = file_arc.lock().unwrap();
-            file.flush().expect("Failed to flush file");
-            update_wav_header(&mut *file);
+        let file_arc = self.file.as_ref()?;
+        let data_size = file_arc.lock().unwrap().finish(self.format).expect("Failed to finalize the recording");
+        if data_size.is_some() && self.format == CaptureFormat::Wav {
             println!("WAV header updated.");
         }
+
+        // 用实际写入的采样数校验文件长度，二者不一致说明有缓冲区丢失或写了一半
+        let samples = self.stats.samples.load(Ordering::SeqCst);
+        let expected_size = samples * self.layout.block_align() as u64;
+        if let Some(data_size) = data_size.filter(|&size| size != expected_size) {
+            eprintln!(
+                "Warning: audio data is {} bytes but {} samples ({} bytes) were captured; some audio buffers were dropped or partially written, so the reported duration may be wrong",
+                data_size, samples, expected_size
+            );
+        }
+
+        let peak = self.stats.peak.load(Ordering::SeqCst) as f64 / 32768.0;
+        Some(CaptureSummary {
+            path: self.file_name.clone(),
+            duration_secs: if self.output_rate > 0 { samples as f64 / self.output_rate as f64 } else { 0.0 },
+            sample_rate: self.output_rate,
+            channels: self.layout.channels,
+            peak_dbfs: 20.0 * peak.log10(),
+            clipped_samples: self.stats.clipped.load(Ordering::SeqCst),
+        })
+    }
+}
+
+/// 将一个缓冲区的 16-bit PCM 按 `layout` 编码后写入文件，成功后累计统计信息。
+///
+/// 运行在音频线程上，写入失败时只打印错误而不 panic，缺失的部分会在 [`AudioCapture::stop`] 时被发现。
+fn write_pcm(file: &Mutex<CaptureSink>, pcm: &[i16], layout: SampleLayout, stats: &CaptureStats) {
+    match file.lock().unwrap().write_data(&layout.encode(pcm)) {
+        Ok(()) => stats.record(pcm),
+        Err(e) => eprintln!("Failed to write captured audio: {}", e),
     }
 }
 
-/// 写入 WAV 文件头  
-/// 固定输出为 16-bit PCM 格式，并且如果设备为立体声则混合为单声道输出，
-fn write_wav_header(file: &mut File, config: &StreamConfig) {
-    // 如果输入是立体声，则输出为单声道（1 通道）
-    let header_channels: u16 = if config.channels == 2 {
-        1
+/// 将采样归一化到 [-1.0, 1.0]：I16 按满量程缩放，浮点格式原样返回
+fn to_unit_f32<T>(sample: T) -> f32
+where
+    T: cpal::Sample + cpal::SizedSample + ToPrimitive,
+{
+    let value = sample.to_f32().unwrap();
+    if std::mem::size_of::<T>() == 2 {
+        value / 32768.0
     } else {
-        config.channels as u16
+        value
+    }
+}
+
+/// 把缓冲区中的整帧混合为 [-1.0, 1.0] 的单声道采样，不满一帧的剩余采样丢弃。
+///
+/// 指定了通道时只混合这些通道；否则按 `downmix` 混合，取平均时与读取多声道 WAV 文件共用
+/// [`audio_transcribe::downmix_average`]。
+fn mix_to_mono<T>(data: &[T], channels: usize, selected: Option<&[usize]>, downmix: Downmix) -> Vec<f32>
+where
+    T: cpal::Sample + cpal::SizedSample + ToPrimitive,
+{
+    let whole = &data[..data.len() - data.len() % channels];
+    match selected {
+        Some(selected) => whole.chunks_exact(channels).map(|frame| mix_selected(frame, selected)).collect(),
+        None if downmix == Downmix::Average => {
+            audio_transcribe::downmix_average(whole.iter().map(|&s| to_unit_f32(s)), channels as u16).collect()
+        }
+        None => whole.chunks_exact(channels).map(|frame| downmix.mix(frame)).collect(),
+    }
+}
+
+/// 把一帧中选中的通道（从 0 开始）取平均，归一化到 [-1.0, 1.0]
+fn mix_selected<T>(frame: &[T], selected: &[usize]) -> f32
+where
+    T: cpal::Sample + cpal::SizedSample + ToPrimitive,
+{
+    selected.iter().map(|&c| to_unit_f32(frame[c])).sum::<f32>() / selected.len() as f32
+}
+
+/// 将采样转换为 16-bit PCM：I16 直接使用，F32/F64 按满量程缩放
+fn to_pcm_i16<T>(sample: T) -> i16
+where
+    T: cpal::Sample + cpal::SizedSample + ToPrimitive,
+{
+    if std::mem::size_of::<T>() == 2 {
+        sample.to_i16().unwrap()
+    } else {
+        (sample.to_f32().unwrap() * 32767.0).clamp(-32768.0, 32767.0) as i16
+    }
+}
+
+/// 查询设备支持的配置，挑选一个可以采集的配置。
+///
+/// 只考虑 I16/F32/F64 且为单声道或立体声的配置；优先匹配期望的通道数，
+/// 采样率落在支持范围内时直接使用，否则取最接近的可用采样率。
+/// 期望值为 `None` 时以设备默认配置为准。
+fn select_config(
+    device: &cpal::Device,
+    sample_rate: Option<u32>,
+    channels: Option<u16>,
+    min_channels: Option<u16>,
+) -> Option<SupportedStreamConfig> {
+    let default_config = device.default_output_config().ok();
+    let ranges: Vec<_> = match device.supported_output_configs() {
+        Ok(configs) => configs
+            .filter(|c| matches!(c.sample_format(), SampleFormat::I16 | SampleFormat::F32 | SampleFormat::F64))
+            // 指定了要录制的通道时接受任意通道数足够的配置，否则只用单声道或立体声
+            .filter(|c| match min_channels {
+                Some(min) => c.channels() >= min,
+                None => c.channels() == 1 || c.channels() == 2,
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to query supported output configs: {}", e);
+            return default_config;
+        }
     };
-    let sample_rate = config.sample_rate.0;
-    let bits_per_sample = 16; // 固定为 16-bit PCM
-    let audio_format: u16 = 1; // PCM 格式
+
+    let target_channels = channels
+        .or(default_config.as_ref().map(|c| c.channels()))
+        .filter(|&c| min_channels.is_none_or(|min| c >= min));
+    let target_rate = sample_rate
+        .or(default_config.as_ref().map(|c| c.sample_rate().0))
+        .unwrap_or(48000);
+
+    // 优先使用通道数匹配的配置，没有则放宽到全部可用配置（多通道设备上选用通道最少的那些）
+    let matching: Vec<_> = ranges
+        .iter()
+        .filter(|c| Some(c.channels()) == target_channels)
+        .cloned()
+        .collect();
+    let candidates = if matching.is_empty() {
+        let fewest = ranges.iter().map(|c| c.channels()).min();
+        ranges.into_iter().filter(|c| Some(c.channels()) == fewest).collect()
+    } else {
+        matching
+    };
+
+    candidates
+        .into_iter()
+        .map(|range| {
+            let rate = target_rate.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+            // 与默认采样格式相同的配置排在前面
+            let same_format = default_config
+                .as_ref()
+                .map(|d| d.sample_format() == range.sample_format())
+                .unwrap_or(false);
+            (rate.abs_diff(target_rate), !same_format, range.with_sample_rate(SampleRate(rate)))
+        })
+        .min_by_key(|(distance, other_format, _)| (*distance, *other_format))
+        .map(|(_, _, config)| config)
+}
+
+/// 列出当前音频后端中所有输出设备（可回环录制的播放端点）的名称
+pub fn output_device_names() -> Vec<String> {
+    match cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(e) => {
+            eprintln!("Failed to enumerate output devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 按名称查找输出设备：优先完全相同（不区分大小写），否则接受唯一一个包含该名称的设备
+fn find_output_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, String> {
+    let devices: Vec<(String, cpal::Device)> = host
+        .output_devices()
+        .map_err(|e| format!("Failed to enumerate output devices: {}", e))?
+        .filter_map(|device| Some((device.name().ok()?, device)))
+        .collect();
+    let wanted = name.to_lowercase();
+    let available = || devices.iter().map(|(n, _)| format!("  {}", n)).collect::<Vec<_>>().join("\n");
+
+    if let Some(index) = devices.iter().position(|(n, _)| n.to_lowercase() == wanted) {
+        return Ok(devices.into_iter().nth(index).unwrap().1);
+    }
+    let matches: Vec<usize> = (0..devices.len()).filter(|&i| devices[i].0.to_lowercase().contains(&wanted)).collect();
+    match matches[..] {
+        [index] => Ok(devices.into_iter().nth(index).unwrap().1),
+        [] => Err(format!("No output device matches '{}'. Available output devices:\n{}", name, available())),
+        _ => Err(format!(
+            "'{}' matches several output devices, use a longer name:\n{}",
+            name,
+            matches.iter().map(|&i| format!("  {}", devices[i].0)).collect::<Vec<_>>().join("\n")
+        )),
+    }
+}
+
+/// 生成 WAV 文件头  
+/// 默认为 16-bit PCM 单声道，按参考文件录制时使用 `layout` 给出的通道数和位深，
+/// `size` 同时填入 RIFF 与 data 块的大小字段（文件用 0 占位，管道用 0xFFFFFFFF）
+fn wav_header(sample_rate: u32, layout: SampleLayout, size: u32) -> Vec<u8> {
+    let header_channels: u16 = layout.channels;
+    let bits_per_sample = layout.bits_per_sample;
+    let audio_format: u16 = if layout.float { 3 } else { 1 }; // 3 为 IEEE 浮点，1 为 PCM 格式
     let byte_rate = sample_rate * header_channels as u32 * (bits_per_sample / 8) as u32;
-    let block_align = header_channels * (bits_per_sample / 8) as u16;
+    let block_align = header_channels * (bits_per_sample / 8);
 
     let mut header = vec![
         b'R', b'I', b'F', b'F', // ChunkID
-        0, 0, 0, 0,             // ChunkSize (占位)
+    ];
+    header.extend_from_slice(&size.to_le_bytes()); // ChunkSize (占位)
+    header.extend_from_slice(&[
         b'W', b'A', b'V', b'E', // Format
         b'f', b'm', b't', b' ', // Subchunk1ID
         16, 0, 0, 0,            // Subchunk1Size (16 for PCM)
-    ];
+    ]);
 
     // 写入 AudioFormat（PCM 格式）
     header.extend_from_slice(&audio_format.to_le_bytes());
-    // 写入通道数（这里固定为 header_channels，即单声道时为 1）
+    // 写入通道数（默认为单声道，即 1）
     header.push(header_channels as u8);
     header.push((header_channels >> 8) as u8);
     // 写入采样率
@@ -199,19 +877,18 @@ fn write_wav_header(file: &mut File, config: &StreamConfig) {
     header.push(bits_per_sample as u8);
     header.push((bits_per_sample >> 8) as u8);
     // 写入 "data" chunk ID 及占位的 Subchunk2Size
-    header.extend_from_slice(&[b'd', b'a', b't', b'a']);
-    header.extend_from_slice(&[0, 0, 0, 0]); // 占位
-
-    file.seek(SeekFrom::Start(0)).unwrap();
-    file.write_all(&header).unwrap();
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&size.to_le_bytes()); // 占位
+    header
 }
 
-/// 更新 WAV 文件头中的文件大小和数据块大小字段
-fn update_wav_header(file: &mut File) {
-    let file_size = file.seek(SeekFrom::End(0)).unwrap();
+/// 更新 WAV 文件头中的文件大小和数据块大小字段，返回数据块字节数
+fn update_wav_header(file: &mut File) -> io::Result<u64> {
+    let file_size = file.seek(SeekFrom::End(0))?;
     let data_chunk_size = file_size - 44;
-    file.seek(SeekFrom::Start(4)).unwrap();
-    file.write_all(&(data_chunk_size as u32).to_le_bytes()).unwrap();
-    file.seek(SeekFrom::Start(40)).unwrap();
-    file.write_all(&(data_chunk_size as u32).to_le_bytes()).unwrap();
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&(data_chunk_size as u32).to_le_bytes())?;
+    file.seek(SeekFrom::Start(40))?;
+    file.write_all(&(data_chunk_size as u32).to_le_bytes())?;
+    Ok(data_chunk_size)
 }
@@ -1,141 +1,990 @@
-use hound;
-use samplerate::{convert, ConverterType};
+use crate::checkpoint::{self, Checkpoint, CheckpointKey};
+use crate::progress;
+use crate::segment_hook::SegmentSender;
+use crate::transcript;
+use crate::vad;
+use crate::wav64::{self, Float64WavReader};
+use anyhow::{anyhow, bail, Context};
+use samplerate::{ConverterType, Samplerate};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use whisper_rs::{
-    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+    FullParams, SamplingStrategy, SegmentCallbackData, WhisperContext, WhisperContextParameters, WhisperState,
 };
 
+/// 一段识别结果，时间单位为毫秒
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+    /// 词级时间戳，仅在启用 [`TranscribeOptions::word_timestamps`] 时填充
+    pub words: Vec<Word>,
+    /// 说话人编号（从 1 开始），仅在做过说话人分离时填充
+    pub speaker: Option<u32>,
+    /// 该段所在分块检测到的语言代码，仅在启用 [`TranscribeOptions::language_chunk_secs`] 时填充
+    pub language: Option<String>,
+    /// 段内各 token 的平均概率（0–1），可作为该段的置信度；无法计算时为 `None`
+    pub confidence: Option<f32>,
+}
+
+/// 一个词及其时间范围（毫秒）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Word {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+}
+
+/// 从 Whisper ggml 模型文件中读出的基本信息，见 [`inspect_model`]
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    /// 模型规模，例如 "base"
+    pub model_type: String,
+    pub n_vocab: i32,
+    pub multilingual: bool,
+    pub n_audio_layer: i32,
+    pub n_text_layer: i32,
+    pub n_mels: i32,
+    /// 权重的数据类型编号（0 = F32，1 = F16，其余为各种量化格式）
+    pub ftype: i32,
+}
+
+/// 在 CPU 上加载 Whisper 模型并读出其超参数，用于在长任务之前确认模型文件可用。
+///
+/// # Errors
+///
+/// 文件损坏、被截断或不是 ggml 格式时，whisper.cpp 无法创建上下文，返回错误。
+pub fn inspect_model(path: &str) -> anyhow::Result<ModelInfo> {
+    let params = WhisperContextParameters { use_gpu: false, ..Default::default() };
+    let ctx = WhisperContext::new_with_params(path, params).map_err(|e| anyhow!("failed to load whisper model: {:?}", e))?;
+    Ok(ModelInfo {
+        model_type: ctx.model_type_readable().unwrap_or_else(|_| "unknown".to_string()),
+        n_vocab: ctx.model_n_vocab(),
+        multilingual: ctx.is_multilingual(),
+        n_audio_layer: ctx.model_n_audio_layer(),
+        n_text_layer: ctx.model_n_text_layer(),
+        n_mels: ctx.model_n_mels(),
+        ftype: ctx.model_ftype(),
+    })
+}
+
+/// 词级时间戳的计算方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum WordTimestamps {
+    /// 使用 Whisper 自带的 token 时间戳，不占额外内存但精度一般
+    Token,
+    /// 使用基于交叉注意力的 DTW 对齐，时间更精确，需要在加载模型时启用
+    Dtw,
+}
+
+/// 重采样质量，越高越慢
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ResampleQuality {
+    /// 线性插值，速度最快
+    Fast,
+    /// 中等质量的 Sinc 插值
+    Medium,
+    /// 最高质量的 Sinc 插值
+    #[default]
+    Best,
+}
+
+impl ResampleQuality {
+    fn converter_type(self) -> ConverterType {
+        match self {
+            ResampleQuality::Fast => ConverterType::Linear,
+            ResampleQuality::Medium => ConverterType::SincMediumQuality,
+            ResampleQuality::Best => ConverterType::SincBestQuality,
+        }
+    }
+}
+
+/// Whisper 要求的输入采样率
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// 语言检测只看开头的一个 Whisper 窗口（秒）
+pub const LANGUAGE_DETECT_SECS: f32 = 30.0;
+
+/// whisper.cpp 默认的熵阈值：解码结果的平均熵低于该值时（文本高度重复）提高温度重新解码
+pub const DEFAULT_ENTROPY_THRESHOLD: f32 = 2.4;
+/// whisper.cpp 默认的对数概率阈值：解码结果的平均对数概率低于该值时提高温度重新解码
+pub const DEFAULT_LOGPROB_THRESHOLD: f32 = -1.0;
+/// 贪心解码时每次温度回退采样的候选数，与 `SamplingStrategy::default()` 相同
+pub const DEFAULT_BEST_OF: i32 = 1;
+/// whisper.cpp 默认的束搜索耐心系数，负数表示不启用
+pub const DEFAULT_PATIENCE: f32 = -1.0;
+
+/// 转录选项，默认转录整个文件
+#[derive(Debug, Clone)]
+pub struct TranscribeOptions {
+    /// 从第几秒开始转录
+    pub start_secs: Option<f32>,
+    /// 转录到第几秒为止
+    pub end_secs: Option<f32>,
+    /// 输入采样率不是 16kHz 时使用的重采样质量
+    pub resample_quality: ResampleQuality,
+    /// 抑制段落开头的空白输出（whisper.cpp 默认开启）
+    pub suppress_blank: bool,
+    /// 抑制非语音 token，例如音乐符号和纯标点（whisper.cpp 默认关闭）
+    pub suppress_non_speech_tokens: bool,
+    /// 不把前面已识别的文本作为后续窗口的提示（whisper.cpp 默认开启）
+    pub no_context: bool,
+    /// 每个窗口只输出一个段落，适合很短的独立片段（whisper.cpp 默认关闭）
+    pub single_segment: bool,
+    /// 触发温度回退的熵阈值，默认为 [`DEFAULT_ENTROPY_THRESHOLD`]
+    pub entropy_threshold: f32,
+    /// 触发温度回退的平均对数概率阈值，默认为 [`DEFAULT_LOGPROB_THRESHOLD`]
+    pub logprob_threshold: f32,
+    /// 贪心解码在温度回退时采样的候选数，取其中最好的一个，默认为 [`DEFAULT_BEST_OF`]
+    pub best_of: i32,
+    /// 设置后改用该束宽的束搜索解码，更准确但明显更慢
+    pub beam_size: Option<i32>,
+    /// 束搜索的耐心系数，默认为 [`DEFAULT_PATIENCE`]
+    pub patience: f32,
+    /// 需要时输出词级时间戳
+    pub word_timestamps: Option<WordTimestamps>,
+    /// 按该长度（秒）分块转录并逐块检测语言，用于中英混杂等语言切换的录音
+    pub language_chunk_secs: Option<f32>,
+    /// 分块转写时相邻块之间的重叠秒数，重叠区中重复识别的内容由 [`transcript::merge_overlapping`] 去重
+    pub chunk_overlap_secs: Option<f32>,
+    /// 分块转写时每完成一块就把已完成的段落和位置写入该检查点文件，转写成功后删除
+    pub checkpoint: Option<PathBuf>,
+    /// 检查点存在时跳过其中已完成的块，从中断处继续转写
+    pub resume: bool,
+    /// 设置后先做语音活动检测，只转录电平高于该值（dBFS）的语音区域
+    pub vad_threshold_dbfs: Option<f32>,
+    /// 指定转录语言（例如 "en"），`None` 表示自动检测
+    pub language: Option<String>,
+    /// 音频短于该时长（秒）时自动检测语言并不可靠，会给出警告
+    pub short_clip_secs: f32,
+    /// 未指定语言且音频过短时改用的语言
+    pub short_clip_language: Option<String>,
+    /// 该标志被置位后尽快中止转录，已完成的段落通过 [`PartialTranscript`] 返回
+    pub abort: Option<Arc<AtomicBool>>,
+    /// 转录（不含读取和重采样）超过该时长后同样中止，错误中附带已完成的段落
+    pub timeout: Option<Duration>,
+    /// 输出前丢弃置信度低于该值的段落，由调用方通过 [`transcript::filter_by_confidence`] 统一应用
+    pub min_segment_confidence: Option<f32>,
+    /// 输出前对段落文本应用的清理规则，为空时保持 Whisper 的原始输出
+    pub cleanup: Vec<transcript::Cleanup>,
+    /// 每解码出一段就送入这些队列（`--segment-callback-script`、`--overlap-translation`），段落为 Whisper 的原始输出
+    pub segment_hooks: Vec<SegmentSender>,
+}
+
+impl TranscribeOptions {
+    /// 按 `beam_size`、`best_of` 和 `patience` 选择解码策略
+    pub fn sampling_strategy(&self) -> SamplingStrategy {
+        match self.beam_size {
+            Some(beam_size) => SamplingStrategy::BeamSearch { beam_size, patience: self.patience },
+            None => SamplingStrategy::Greedy { best_of: self.best_of },
+        }
+    }
+}
+
+impl Default for TranscribeOptions {
+    fn default() -> Self {
+        Self {
+            start_secs: None,
+            end_secs: None,
+            resample_quality: ResampleQuality::default(),
+            suppress_blank: true,
+            suppress_non_speech_tokens: false,
+            no_context: true,
+            single_segment: false,
+            entropy_threshold: DEFAULT_ENTROPY_THRESHOLD,
+            logprob_threshold: DEFAULT_LOGPROB_THRESHOLD,
+            best_of: DEFAULT_BEST_OF,
+            beam_size: None,
+            patience: DEFAULT_PATIENCE,
+            word_timestamps: None,
+            language_chunk_secs: None,
+            chunk_overlap_secs: None,
+            checkpoint: None,
+            resume: false,
+            vad_threshold_dbfs: None,
+            language: None,
+            short_clip_secs: 3.0,
+            short_clip_language: None,
+            abort: None,
+            timeout: None,
+            min_segment_confidence: None,
+            cleanup: Vec::new(),
+            segment_hooks: Vec::new(),
+        }
+    }
+}
+
+/// 转录被中止或中途失败时已经解码出的段落，作为错误的来源附在 [`Whisper::transcribe_segments`] 的错误中，
+/// 可通过 `anyhow::Error::downcast_ref` 取出。
+#[derive(Debug)]
+pub struct PartialTranscript {
+    pub segments: Vec<Segment>,
+}
+
+impl fmt::Display for PartialTranscript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "transcription stopped after {} segment(s)", self.segments.len())
+    }
+}
+
+impl std::error::Error for PartialTranscript {}
+
 /// Whisper 结构体封装了 Whisper 状态，
 /// 并提供从 WAV 文件转录文本的接口。
 pub struct Whisper {
     /// Whisper 内部状态，用于执行转录操作
     whisper_state: WhisperState,
-    /// 固定的目标采样率为 16000Hz
+    /// 固定的目标采样率，即 [`WHISPER_SAMPLE_RATE`]
     sample_rate_target: u32,
 }
 
 impl Whisper {
-    /// 根据指定的模型文件路径创建一个新的 Whisper 转录器。
+    /// 根据指定的模型文件路径创建一个新的 Whisper 转录器，并指定是否使用 GPU。
     ///
     /// # 参数
     ///
     /// * `whisper_model_path` - Whisper 模型文件路径（例如 "models/ggml-whisper.bin"）
+    /// * `use_gpu` - 是否使用 GPU
     ///
     /// # Panics
     ///
     /// 如果创建 WhisperContext 或状态失败，则会直接 panic。
-    pub fn new(whisper_model_path: &str) -> Self {
-        let ctx = WhisperContext::new_with_params(
+    pub fn with_gpu(whisper_model_path: &str, use_gpu: bool) -> Self {
+        Self::with_params(
             whisper_model_path,
             WhisperContextParameters {
-                use_gpu: true,
+                use_gpu,
                 flash_attn: false,
                 ..Default::default()
             },
         )
-        .expect("failed to create WhisperContext");
-        let state = ctx.create_state().expect("failed to create Whisper state");
-        Self {
+        .expect("failed to create WhisperContext")
+    }
+
+    /// 使用完整的上下文参数创建转写器，例如指定 GPU 设备序号。
+    ///
+    /// # Errors
+    ///
+    /// 模型无法加载或 GPU 设备不可用（例如序号超出范围）时返回错误。
+    pub fn with_params(whisper_model_path: &str, params: WhisperContextParameters) -> anyhow::Result<Self> {
+        let use_gpu = params.use_gpu;
+        let gpu_device = params.gpu_device;
+        let ctx = WhisperContext::new_with_params(whisper_model_path, params).map_err(|e| {
+            if use_gpu && gpu_device > 0 {
+                anyhow!("failed to create WhisperContext on GPU device {} (is the index out of range?): {:?}", gpu_device, e)
+            } else {
+                anyhow!("failed to create WhisperContext: {:?}", e)
+            }
+        })?;
+        let state = ctx
+            .create_state()
+            .map_err(|e| anyhow!("failed to create Whisper state: {:?}", e))?;
+        Ok(Self {
             whisper_state: state,
-            sample_rate_target: 16000,
-        }
+            sample_rate_target: WHISPER_SAMPLE_RATE,
+        })
     }
 
-    /// 对指定的 WAV 文件进行转录，并返回识别的文本。
+    /// 对指定的 WAV 文件进行转录，并返回带时间戳的段落列表。
     ///
-    /// 该函数会使用 [hound] 读取 WAV 文件数据，如果输入文件的采样率不是 16000Hz，
-    /// 则会自动进行重采样。注意：仅支持单声道 WAV 文件。
+    /// 音频先经 [`Whisper::load_mono_16k`] 解码，再交给 [`Whisper::transcribe_samples_with`]，
+    /// 返回的段落时间戳已加上起始偏移，对应原文件中的位置。
     ///
     /// # 参数
     ///
     /// * `wav_file_path` - WAV 文件路径
+    /// * `options` - 转录选项
     ///
     /// # 返回值
     ///
-    /// 成功时返回 `Some(转录文本)`；如果转录过程中出现问题，则会 panic 或返回 None。
-    pub fn transcribe_file(&mut self, wav_file_path: &str) -> Option<String> {
-        // 打开 WAV 文件，如果失败则直接 panic
-        let reader = hound::WavReader::open(wav_file_path)
-            .expect("failed to open WAV file");
-        let spec = reader.spec();
-
-        // 只支持单声道 WAV 文件
-        if spec.channels != 1 {
-            panic!("只支持单声道 WAV 文件，当前通道数：{}", spec.channels);
-        }
-        let input_sample_rate = spec.sample_rate;
-
-        // 根据 WAV 文件格式读取采样数据
-        let samples: Vec<f32> = match spec.sample_format {
-            hound::SampleFormat::Int => {
-                reader
-                    .into_samples::<i16>()
-                    .map(|s| s.expect("failed to read sample") as f32 / i16::MAX as f32)
-                    .collect()
+    /// 成功时返回段落列表，未检测到语音时为空列表（并在 stderr 提示）；文件无法读取、没有音频数据（包括截取范围为空）或转录失败时返回错误。
+    /// 转录被 `options.abort` 中止或中途失败时，错误中附带 [`PartialTranscript`]，包含已完成的段落。
+    pub fn transcribe_segments(&mut self, wav_file_path: &str, options: &TranscribeOptions) -> anyhow::Result<Vec<Segment>> {
+        let samples = load_nonempty_mono_16k(wav_file_path, options.resample_quality)?;
+        self.transcribe_samples_with(&samples, self.sample_rate_target, options)
+    }
+
+    /// 使用默认选项转录 WAV 文件：解码为 16kHz 单声道后交给 [`Whisper::transcribe_samples`]。
+    ///
+    /// 错误与 [`Whisper::transcribe_segments`] 相同。
+    pub fn transcribe_file(&mut self, wav_file_path: &str) -> anyhow::Result<Vec<Segment>> {
+        let samples = load_nonempty_mono_16k(wav_file_path, ResampleQuality::default())?;
+        self.transcribe_samples(&samples, self.sample_rate_target)
+    }
+
+    /// 直接转录内存中的单声道采样，使用默认选项，不经过任何文件读写。
+    ///
+    /// 采样率不是 16kHz 时会先重采样，适合把实时采集到的音频直接送入 Whisper。
+    pub fn transcribe_samples(&mut self, samples: &[f32], sample_rate: u32) -> anyhow::Result<Vec<Segment>> {
+        self.transcribe_samples_with(samples, sample_rate, &TranscribeOptions::default())
+    }
+
+    /// 与 [`Whisper::transcribe_samples`] 相同，但使用给定的转录选项。
+    ///
+    /// 先按 `options` 中的时间范围截取，再按需重采样到 16kHz，返回的时间戳对应截取前的位置。
+    /// 错误与 [`Whisper::transcribe_segments`] 相同。
+    pub fn transcribe_samples_with(
+        &mut self,
+        samples: &[f32],
+        sample_rate: u32,
+        options: &TranscribeOptions,
+    ) -> anyhow::Result<Vec<Segment>> {
+        // 按时间范围截取采样
+        let rate = sample_rate as f32;
+        let start = options.start_secs.map(|s| (s * rate) as usize).unwrap_or(0).min(samples.len());
+        let end = options.end_secs.map(|s| (s * rate) as usize).unwrap_or(samples.len()).clamp(start, samples.len());
+        let offset_ms = (start as i64) * 1000 / sample_rate as i64;
+        if start == end {
+            bail!("no audio data to transcribe");
+        }
+
+        // 只对截取后的部分重采样
+        let resampled;
+        let samples = if sample_rate != self.sample_rate_target {
+            resampled = audio_resample(&samples[start..end], sample_rate, self.sample_rate_target, options.resample_quality);
+            &resampled[..]
+        } else {
+            &samples[start..end]
+        };
+
+        // 自动检测语言在很短的音频上经常出错，此时给出警告并按需改用指定的语言
+        let duration_secs = samples.len() as f32 / self.sample_rate_target as f32;
+        let language = match (&options.language, &options.short_clip_language) {
+            (Some(language), _) => language.as_str(),
+            (None, fallback) if duration_secs < options.short_clip_secs => match fallback {
+                Some(fallback) => {
+                    eprintln!(
+                        "Warning: audio is only {:.1}s long, automatic language detection is unreliable; using '{}'",
+                        duration_secs, fallback
+                    );
+                    fallback.as_str()
+                }
+                None => {
+                    eprintln!(
+                        "Warning: audio is only {:.1}s long, the language may be misdetected; pass --language or --force-language-on-short",
+                        duration_secs
+                    );
+                    "auto"
+                }
+            },
+            (None, _) => "auto",
+        };
+
+        // 决定送入 Whisper 的片段：VAD 检测出的语音区域、按语言检测分的固定长度块，或者整段
+        let target_rate = self.sample_rate_target as usize;
+        let chunk_len = options
+            .language_chunk_secs
+            .map(|secs| (secs * target_rate as f32) as usize)
+            .filter(|&len| len > 0);
+        let pieces = match (options.vad_threshold_dbfs, chunk_len) {
+            (Some(threshold), _) => {
+                let regions = vad::speech_regions(samples, self.sample_rate_target, threshold);
+                let speech: usize = regions.iter().map(|r| r.len()).sum();
+                status!(
+                    "VAD: {} speech region(s), transcribing {:.1}s of {:.1}s",
+                    regions.len(),
+                    speech as f64 / target_rate as f64,
+                    samples.len() as f64 / target_rate as f64
+                );
+                regions
             }
-            hound::SampleFormat::Float => {
-                reader
-                    .into_samples::<f32>()
-                    .map(|s| s.expect("failed to read sample"))
+            (None, Some(len)) => {
+                // 每块向后多取重叠部分，避免块边界切断的词在两块中都识别不全
+                let overlap = options.chunk_overlap_secs.map_or(0, |secs| (secs * target_rate as f32) as usize);
+                // 上一块（连同重叠部分）已经到达结尾时不再多出一块
+                (0..samples.len())
+                    .step_by(len)
+                    .take_while(|&start| start == 0 || start + overlap < samples.len())
+                    .map(|start| start..(start + len + overlap).min(samples.len()))
                     .collect()
             }
+            (None, None) => std::iter::once(0..samples.len()).collect(),
         };
 
-        // 如果采样率不匹配，则进行重采样
-        let samples = if input_sample_rate != self.sample_rate_target {
-            println!("need audio_resample, since input_sample_rate is  {} and self.sample_rate_target {}", input_sample_rate, self.sample_rate_target);
-            audio_resample(&samples, input_sample_rate, self.sample_rate_target)
-        } else {
-            samples
+        // 逐片转录，时间戳换算回原始位置；分块检测语言时把检测结果标注到该块的段落上。
+        // 进度按各片的采样数加权，汇总为整个文件的百分比
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let total_len: usize = pieces.iter().map(|p| p.len()).sum::<usize>().max(1);
+        let mut done_len = 0;
+        let mut segments: Vec<Segment> = Vec::new();
+        // 上一块的结束位置（毫秒），与本块开头之间即为重叠区
+        let mut previous_end_ms = offset_ms;
+        // 只有分块转写才写检查点；续转时从检查点恢复已完成的段落，跳过在 next_sample 之前开始的块
+        let checkpoint_path = options.checkpoint.as_deref().filter(|_| chunk_len.is_some() && options.vad_threshold_dbfs.is_none());
+        let checkpoint_key = CheckpointKey {
+            samples: samples.len(),
+            offset_ms,
+            chunk_len: chunk_len.unwrap_or(0),
+            overlap: options.chunk_overlap_secs.map_or(0, |secs| (secs * target_rate as f32) as usize),
+            language: language.to_string(),
         };
+        let mut next_sample = 0;
+        if let Some(path) = checkpoint_path.filter(|path| path.exists()) {
+            if !options.resume {
+                status!("Overwriting the checkpoint {}; pass --resume to continue from it instead", path.display());
+            } else if let Some(saved) = checkpoint::load(path, &checkpoint_key) {
+                status!(
+                    "Resuming from {} at {:.1}s ({} segment(s) already transcribed)",
+                    path.display(),
+                    offset_ms as f64 / 1000.0 + saved.next_sample as f64 / target_rate as f64,
+                    saved.segments.len()
+                );
+                segments = saved.segments;
+                previous_end_ms = saved.previous_end_ms;
+                next_sample = saved.next_sample;
+            }
+        }
+        for piece in pieces {
+            if piece.start < next_sample {
+                done_len += piece.len();
+                continue;
+            }
+            let piece_start = piece.start;
+            let piece_offset_ms = offset_ms + (piece.start as i64) * 1000 / target_rate as i64;
+            let piece_end_ms = offset_ms + (piece.end as i64) * 1000 / target_rate as i64;
+            let progress_range = (done_len as f64 * 100.0 / total_len as f64, piece.len() as f64 / total_len as f64);
+            done_len += piece.len();
+            match self.run_full(&samples[piece], piece_offset_ms, language, options, deadline, progress_range) {
+                Ok(mut piece_segments) => {
+                    if chunk_len.is_some() {
+                        let language = self.detected_language();
+                        for segment in &mut piece_segments {
+                            segment.language = language.clone();
+                        }
+                    }
+                    if piece_offset_ms < previous_end_ms {
+                        segments = transcript::merge_overlapping(std::mem::take(&mut segments), piece_segments, piece_offset_ms, previous_end_ms);
+                    } else {
+                        segments.extend(piece_segments);
+                    }
+                    previous_end_ms = piece_end_ms;
+                    if let Some(path) = checkpoint_path {
+                        next_sample = piece_start + chunk_len.unwrap_or(0);
+                        let saved = Checkpoint { key: checkpoint_key.clone(), next_sample, previous_end_ms, segments };
+                        if let Err(e) = checkpoint::save(path, &saved) {
+                            eprintln!("Warning: {:#}", e);
+                        }
+                        segments = saved.segments;
+                    }
+                }
+                Err(mut e) => {
+                    if let Some(path) = checkpoint_path.filter(|path| path.exists()) {
+                        eprintln!("Progress saved to {}; run again with --resume to continue", path.display());
+                    }
+                    // 把之前各块的结果并入部分结果，一起返回给调用方
+                    if let Some(partial) = e.downcast_mut::<PartialTranscript>() {
+                        segments.append(&mut partial.segments);
+                        partial.segments = segments;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        if let Some(path) = checkpoint_path {
+            checkpoint::remove(path);
+        }
+        if segments.is_empty() {
+            // 纯静音或纯音乐时 Whisper 会成功返回但没有任何段落
+            eprintln!("No speech detected");
+        }
+        Ok(segments)
+    }
 
+    /// 对一段 16kHz 采样执行一次 Whisper 转录，返回的时间戳加上 `offset_ms`。
+    ///
+    /// `progress_range` 为 `(起始百分比, 占整体的比例)`，用于把这一片的进度换算为整个文件的进度。
+    /// 失败、被中止或超过 `deadline` 时，错误中附带 [`PartialTranscript`]。
+    fn run_full(
+        &mut self,
+        samples: &[f32],
+        offset_ms: i64,
+        language: &str,
+        options: &TranscribeOptions,
+        deadline: Option<Instant>,
+        progress_range: (f64, f64),
+    ) -> anyhow::Result<Vec<Segment>> {
         // 配置转录参数
-        let mut params = FullParams::new(SamplingStrategy::default());
+        let mut params = FullParams::new(options.sampling_strategy());
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_special(false);
         params.set_print_timestamps(false);
         params.set_debug_mode(false);
-        // 这里设置语言为英文，如有需要可改为其他语言（例如 "zh"）
-        params.set_language(Some("auto"));
+        // "auto" 表示由 Whisper 自动检测语言
+        params.set_language(Some(language));
+        params.set_suppress_blank(options.suppress_blank);
+        params.set_suppress_non_speech_tokens(options.suppress_non_speech_tokens);
+        params.set_no_context(options.no_context);
+        params.set_single_segment(options.single_segment);
+        params.set_entropy_thold(options.entropy_threshold);
+        params.set_logprob_thold(options.logprob_threshold);
+        params.set_token_timestamps(options.word_timestamps.is_some());
+
+        // 每解码出一段就记录下来，中途失败或被中止时仍可返回已完成的部分
+        let partial = Arc::new(Mutex::new(Vec::new()));
+        let sink = partial.clone();
+        let hooks = options.segment_hooks.clone();
+        params.set_segment_callback_safe_lossy(move |data: SegmentCallbackData| {
+            let segment = Segment {
+                start_ms: data.start_timestamp * 10 + offset_ms,
+                end_ms: data.end_timestamp * 10 + offset_ms,
+                text: data.text,
+                words: Vec::new(),
+                speaker: None,
+                language: None,
+                confidence: None,
+            };
+            for hook in &hooks {
+                hook.send(segment.clone());
+            }
+            sink.lock().unwrap().push(segment);
+        });
+        if progress::json_enabled() {
+            let (base, share) = progress_range;
+            params.set_progress_callback_safe(move |percent: i32| {
+                progress::report("transcribe", Some(base + share * percent as f64));
+            });
+        }
+        let abort = options.abort.clone();
+        if abort.is_some() || deadline.is_some() {
+            params.set_abort_callback_safe(move || {
+                abort.as_ref().is_some_and(|abort| abort.load(Ordering::SeqCst))
+                    || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            });
+        }
+
+        // 执行转录
+        if let Err(e) = self.whisper_state.full(params, samples) {
+            let segments = std::mem::take(&mut *partial.lock().unwrap());
+            let error = anyhow::Error::new(PartialTranscript { segments });
+            return Err(match (options.timeout, deadline) {
+                (Some(timeout), Some(deadline)) if Instant::now() >= deadline => {
+                    error.context(format!("transcription timed out after {:.0}s", timeout.as_secs_f64()))
+                }
+                _ => error.context(format!("transcription failed: {:?}", e)),
+            });
+        }
+
+        let mut segments = self.segments();
+        for (index, segment) in segments.iter_mut().enumerate() {
+            if let Some(mode) = options.word_timestamps {
+                segment.words = self.words(index as i32, segment.end_ms, mode);
+            }
+            segment.start_ms += offset_ms;
+            segment.end_ms += offset_ms;
+            for word in &mut segment.words {
+                word.start_ms += offset_ms;
+                word.end_ms += offset_ms;
+            }
+        }
+        Ok(segments)
+    }
 
-        // 执行转录，失败时直接 panic
+    /// 读取 WAV 文件并转换为 16kHz 单声道 f32 采样，见 [`load_mono_16k`]。
+    pub fn load_mono_16k(&self, wav_file_path: &str, quality: ResampleQuality) -> anyhow::Result<Vec<f32>> {
+        load_mono_16k(wav_file_path, quality)
+    }
+
+    /// 只运行 Whisper 的语言识别而不解码：对 16kHz 单声道采样的前 [`LANGUAGE_DETECT_SECS`] 秒计算 mel 频谱，
+    /// 返回按概率从高到低排列的 `(语言代码, 概率)`，比完整转录快得多。
+    ///
+    /// # Errors
+    ///
+    /// 没有采样或 Whisper 无法完成语言识别（例如只支持英语的模型）时返回错误。
+    pub fn detect_language(&mut self, samples: &[f32]) -> anyhow::Result<Vec<(String, f32)>> {
+        let len = samples.len().min((LANGUAGE_DETECT_SECS * self.sample_rate_target as f32) as usize);
+        if len == 0 {
+            bail!("no audio data to detect the language of");
+        }
+        let threads = std::thread::available_parallelism().map(|n| n.get().min(4)).unwrap_or(1);
         self.whisper_state
-            .full(params, &samples)
-            .expect("transcription failed");
+            .pcm_to_mel(&samples[..len], threads)
+            .map_err(|e| anyhow!("failed to compute the mel spectrogram: {:?}", e))?;
+        let (_, probs) = self
+            .whisper_state
+            .lang_detect(0, threads)
+            .map_err(|e| anyhow!("language detection failed: {:?}", e))?;
+        let mut languages: Vec<(String, f32)> = probs
+            .iter()
+            .enumerate()
+            .filter_map(|(id, &p)| whisper_rs::get_lang_str(id as i32).map(|code| (code.to_string(), p)))
+            .collect();
+        languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(languages)
+    }
+
+    /// 返回最近一次转录中 Whisper 自动检测到的语言代码（例如 "en"）
+    pub fn detected_language(&self) -> Option<String> {
+        let lang_id = self.whisper_state.full_lang_id_from_state().ok()?;
+        whisper_rs::get_lang_str(lang_id).map(|s| s.to_string())
+    }
 
-        // 获取所有识别段落的文本
-        let mut result = String::new();
+    /// 读取最近一次转录的所有段落。
+    ///
+    /// Whisper 返回的时间戳以 10ms 为单位，这里统一换算为毫秒。
+    pub fn segments(&self) -> Vec<Segment> {
         let num_segments = self.whisper_state.full_n_segments().expect("Failed to get number of segments");
+        let mut segments = Vec::with_capacity(num_segments.max(0) as usize);
         for i in 0..num_segments {
-            if let Ok(segment_text) = self.whisper_state.full_get_segment_text_lossy(i) {
-                result.push_str(&segment_text);
-                result.push('\n');
+            let text = match self.segment_text(i) {
+                Some(text) => text,
+                None => continue,
+            };
+            let start_ms = self.whisper_state.full_get_segment_t0(i).unwrap_or(0) * 10;
+            let end_ms = self.whisper_state.full_get_segment_t1(i).unwrap_or(0) * 10;
+            let confidence = self.segment_confidence(i);
+            segments.push(Segment { start_ms, end_ms, text, words: Vec::new(), speaker: None, language: None, confidence });
+        }
+        segments
+    }
+
+    /// 读取第 `i` 段的文本。
+    ///
+    /// 优先按严格 UTF-8 解码，避免中文、日文等多字节文本被替换为 `\u{FFFD}`；
+    /// 只有段落边界截断了多字节字符、严格解码失败时，才退回有损解码并给出警告。
+    fn segment_text(&self, i: i32) -> Option<String> {
+        match self.whisper_state.full_get_segment_text(i) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                let text = self.whisper_state.full_get_segment_text_lossy(i).ok()?;
+                eprintln!("Warning: segment {} is not valid UTF-8 ({:?}), some characters were replaced", i, e);
+                Some(text)
             }
         }
-        Some(result)
+    }
+
+    /// 第 `segment` 段中普通 token 的平均概率，跳过 `[_BEG_]` 等特殊 token；没有普通 token 时返回 `None`
+    fn segment_confidence(&self, segment: i32) -> Option<f32> {
+        let num_tokens = self.whisper_state.full_n_tokens(segment).ok()?;
+        let probs: Vec<f32> = (0..num_tokens)
+            .filter(|&i| {
+                self.whisper_state
+                    .full_get_token_text_lossy(segment, i)
+                    .map(|text| !text.starts_with("[_"))
+                    .unwrap_or(false)
+            })
+            .filter_map(|i| self.whisper_state.full_get_token_prob(segment, i).ok())
+            .collect();
+        (!probs.is_empty()).then(|| probs.iter().sum::<f32>() / probs.len() as f32)
+    }
+
+    /// 将第 `segment` 段的 token 合并为词，并计算每个词的时间范围。
+    ///
+    /// 以空格开头的 token 开始一个新词，特殊 token（如 `[_BEG_]`）会被跳过。
+    /// DTW 只为每个 token 给出一个时间点，因此每个词持续到下一个词开始（最后一个词到段落结束）；
+    /// 模型未启用 DTW 时退回 token 时间戳。
+    fn words(&self, segment: i32, segment_end_ms: i64, mode: WordTimestamps) -> Vec<Word> {
+        let num_tokens = self.whisper_state.full_n_tokens(segment).unwrap_or(0);
+        let mut words: Vec<Word> = Vec::new();
+        for i in 0..num_tokens {
+            let text = match self.whisper_state.full_get_token_text_lossy(segment, i) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            if text.starts_with("[_") {
+                continue;
+            }
+            let data = match self.whisper_state.full_get_token_data(segment, i) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+            let (start_ms, end_ms) = match mode {
+                WordTimestamps::Dtw if data.t_dtw >= 0 => (data.t_dtw * 10, data.t_dtw * 10),
+                _ => (data.t0 * 10, data.t1 * 10),
+            };
+            match words.last_mut() {
+                Some(word) if !text.starts_with(' ') => {
+                    word.text.push_str(&text);
+                    word.end_ms = word.end_ms.max(end_ms);
+                }
+                _ => words.push(Word { start_ms, end_ms, text: text.trim_start().to_string() }),
+            }
+        }
+
+        if mode == WordTimestamps::Dtw {
+            let starts: Vec<i64> = words.iter().skip(1).map(|w| w.start_ms).collect();
+            for (word, next_start) in words.iter_mut().zip(starts.into_iter().chain(Some(segment_end_ms))) {
+                word.end_ms = next_start.max(word.start_ms);
+            }
+        }
+        words.retain(|w| !w.text.trim().is_empty());
+        words
     }
 }
 
+/// 流式重采样时每块的采样数
+const RESAMPLE_BLOCK: usize = 64 * 1024;
+
 /// 对音频数据进行重采样，从原始采样率转换到目标采样率。
 ///
-/// 使用 SincBestQuality 算法进行转换，且仅支持单声道音频数据。
+/// 按 `quality` 选择转换算法，且仅支持单声道音频数据。
 ///
 /// # 参数
 ///
 /// * `data` - 输入音频数据（f32 数组）
 /// * `sample_rate0` - 原始采样率
 /// * `sample_rate` - 目标采样率
+/// * `quality` - 重采样质量
 ///
 /// # Panics
 ///
 /// 如果重采样失败，则会 panic。
-pub fn audio_resample(data: &[f32], sample_rate0: u32, sample_rate: u32) -> Vec<f32> {
-    convert(
-        sample_rate0,
-        sample_rate,
-        1, // 单声道
-        ConverterType::SincBestQuality,
-        data,
-    )
-    .expect("failed to resample")
-}
\ No newline at end of file
+pub fn audio_resample(data: &[f32], sample_rate0: u32, sample_rate: u32, quality: ResampleQuality) -> Vec<f32> {
+    audio_resample_chunked(data.iter().copied(), sample_rate0, sample_rate, quality)
+}
+
+/// 读取 WAV 文件并转换为 16kHz 单声道 f32 采样。
+///
+/// 该函数会使用 [hound] 读取 WAV 文件数据（64-bit 浮点 WAV 由 [`Float64WavReader`] 读取），
+/// 如果输入文件的采样率不是 16000Hz，则会自动进行重采样。多声道文件经 [`to_mono_16k`] 把各通道取平均混合为单声道。
+///
+/// # Errors
+///
+/// 文件无法打开、格式不受支持或数据块比文件头声明的短（截断的录音）时返回错误。
+pub fn load_mono_16k(wav_file_path: &str, quality: ResampleQuality) -> anyhow::Result<Vec<f32>> {
+    // ADPCM 等压缩编码 hound 读不了，先给出明确的错误
+    wav64::ensure_supported_codec(wav_file_path)?;
+    // hound 不支持 64-bit 浮点采样，这类文件单独解析后再转换为 f32
+    if let Some(reader) = Float64WavReader::open(wav_file_path)? {
+        let channels = reader.channels();
+        let input_sample_rate = reader.sample_rate();
+        let samples = reader
+            .into_samples()
+            .collect::<std::io::Result<Vec<f32>>>()
+            .with_context(|| format!("truncated WAV data in {}", wav_file_path))?;
+        return Ok(to_mono_16k(samples.into_iter(), channels, input_sample_rate, quality));
+    }
+
+    // 打开 WAV 文件
+    let reader = hound::WavReader::open(wav_file_path)
+        .with_context(|| format!("failed to open WAV file {}", wav_file_path))?;
+    let spec = reader.spec();
+    let input_sample_rate = spec.sample_rate;
+
+    // 根据 WAV 文件格式逐个读取采样数据，转换为 f32；数据块比文件头声明的短时 hound 在读到末尾时返回错误
+    let samples: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            // 按实际位深读取整数采样（hound 会把 8-bit 无符号采样转换为有符号），
+            // 再按满量程归一化到 [-1.0, 1.0)
+            let bits = spec.bits_per_sample;
+            if !matches!(bits, 8 | 16 | 24 | 32) {
+                bail!("不支持的整数位深：{}", bits);
+            }
+            let full_scale = (1i64 << (bits - 1)) as f32;
+            reader.into_samples::<i32>().map(|s| s.map(|s| s as f32 / full_scale)).collect()
+        }
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect(),
+    };
+    let samples = samples.with_context(|| format!("truncated WAV data in {}", wav_file_path))?;
+
+    Ok(to_mono_16k(samples.into_iter(), spec.channels, input_sample_rate, quality))
+}
+
+/// 与 [`load_mono_16k`] 相同，但空文件或只有文件头的截断录音直接报错，而不是把空输入交给 Whisper
+fn load_nonempty_mono_16k(wav_file_path: &str, quality: ResampleQuality) -> anyhow::Result<Vec<f32>> {
+    let samples = load_mono_16k(wav_file_path, quality)?;
+    if samples.is_empty() {
+        bail!("no audio data to transcribe in {}", wav_file_path);
+    }
+    Ok(samples)
+}
+
+/// 把交错采样转换为 Whisper 需要的 16kHz 单声道：每帧各通道取平均，采样率不是 16kHz 时再按 `quality` 重采样。
+///
+/// [`load_mono_16k`] 读取 WAV 文件后也经过这里，自行解码音频的调用方得到的结果同样可以直接交给
+/// [`Whisper::transcribe_samples`]。输入边读边按块处理，不需要先收集完整的交错采样。
+pub fn to_mono_16k(samples: impl Iterator<Item = f32>, channels: u16, rate: u32, quality: ResampleQuality) -> Vec<f32> {
+    let mono = downmix_average(samples, channels);
+    // 如果采样率不匹配，则边读边按块重采样，避免同时持有完整的输入和输出
+    if rate != WHISPER_SAMPLE_RATE {
+        status!("need audio_resample, since input_sample_rate is  {} and sample_rate_target {}", rate, WHISPER_SAMPLE_RATE);
+        audio_resample_chunked(mono, rate, WHISPER_SAMPLE_RATE, quality)
+    } else {
+        mono.collect()
+    }
+}
+
+/// 把交错采样的每一帧各通道取平均，得到单声道采样。
+///
+/// `channels` 为 0 时按单声道处理；结尾不满一帧的采样按实际的通道数取平均。
+pub fn downmix_average(samples: impl Iterator<Item = f32>, channels: u16) -> impl Iterator<Item = f32> {
+    let channels = channels.max(1) as usize;
+    let mut samples = samples.fuse();
+    std::iter::from_fn(move || {
+        let (sum, count) = samples.by_ref().take(channels).fold((0.0f32, 0usize), |(sum, count), s| (sum + s, count + 1));
+        (count > 0).then(|| sum / count as f32)
+    })
+}
+
+/// 按块对单声道音频流进行重采样。
+///
+/// 所有块共用同一个转换器，滤波器状态在块之间延续，最后一块以 `process_last`
+/// 冲刷尾部，因此结果与一次性转换完全一致，而输入只需按块读取。
+///
+/// # Panics
+///
+/// 如果重采样失败，则会 panic。
+pub fn audio_resample_chunked(
+    samples: impl Iterator<Item = f32>,
+    sample_rate0: u32,
+    sample_rate: u32,
+    quality: ResampleQuality,
+) -> Vec<f32> {
+    warn_if_upsampling(sample_rate0, sample_rate);
+    let converter = Samplerate::new(quality.converter_type(), sample_rate0, sample_rate, 1)
+        .expect("failed to create resampler");
+    let mut samples = samples.peekable();
+    let mut block = Vec::with_capacity(RESAMPLE_BLOCK);
+    let mut output = Vec::new();
+    loop {
+        block.clear();
+        block.extend(samples.by_ref().take(RESAMPLE_BLOCK));
+        if samples.peek().is_none() {
+            output.extend(converter.process_last(&block).expect("failed to resample"));
+            return output;
+        }
+        output.extend(converter.process(&block).expect("failed to resample"));
+    }
+}
+
+/// 目标采样率高于源采样率时提示：升采样只是插值，不会带来源信号中没有的细节
+fn warn_if_upsampling(sample_rate0: u32, sample_rate: u32) {
+    if sample_rate > sample_rate0 {
+        eprintln!(
+            "Note: upsampling from {} Hz to {} Hz; this adds no detail beyond what the {} Hz source contains",
+            sample_rate0, sample_rate, sample_rate0
+        );
+    }
+}
+
+/// 逐块处理实时音频流的重采样器，转换器状态在多次调用之间延续。
+///
+/// 与 [`audio_resample_chunked`] 不同，它不知道流何时结束，适合在采集回调中使用。
+pub struct StreamResampler {
+    converter: Samplerate,
+}
+
+// libsamplerate 的转换器状态只会在持有者所在的线程中被顺序访问，
+// 将其整体移动到音频回调线程是安全的。
+unsafe impl Send for StreamResampler {}
+
+impl StreamResampler {
+    /// 创建单声道重采样器
+    pub fn new(sample_rate0: u32, sample_rate: u32, quality: ResampleQuality) -> anyhow::Result<Self> {
+        warn_if_upsampling(sample_rate0, sample_rate);
+        let converter = Samplerate::new(quality.converter_type(), sample_rate0, sample_rate, 1)
+            .map_err(|e| anyhow!("failed to create resampler: {:?}", e))?;
+        Ok(Self { converter })
+    }
+
+    /// 处理一块输入，返回目前可以输出的采样；失败时丢弃这一块并返回空结果
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        self.converter.process(input).unwrap_or_else(|e| {
+            eprintln!("Resampling failed: {:?}", e);
+            Vec::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, secs: f32, freq: f32) -> Vec<f32> {
+        (0..(rate as f32 * secs) as usize)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn header_only_wav_is_an_error() {
+        for rate in [WHISPER_SAMPLE_RATE, 44100] {
+            let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_header_only_{}.wav", std::process::id(), rate));
+            let spec = hound::WavSpec { channels: 1, sample_rate: rate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+            hound::WavWriter::create(&path, spec).unwrap().finalize().unwrap();
+            let result = load_nonempty_mono_16k(path.to_str().unwrap(), ResampleQuality::default());
+            std::fs::remove_file(&path).unwrap();
+            let error = result.expect_err("header-only WAV was accepted");
+            assert!(error.to_string().starts_with("no audio data to transcribe"), "{:#}", error);
+        }
+    }
+
+    #[test]
+    fn truncated_wav_data_is_an_error() {
+        let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_truncated.wav", std::process::id()));
+        let spec = hound::WavSpec { channels: 1, sample_rate: WHISPER_SAMPLE_RATE, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..100 {
+            writer.write_sample(i as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        // 文件头仍声明 200 字节的数据，实际只剩 150 字节
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(file.metadata().unwrap().len() - 50).unwrap();
+        drop(file);
+
+        let result = load_mono_16k(path.to_str().unwrap(), ResampleQuality::default());
+        std::fs::remove_file(&path).unwrap();
+        let error = result.expect_err("truncated WAV was accepted");
+        assert!(error.to_string().starts_with("truncated WAV data in"), "{:#}", error);
+    }
+
+    #[test]
+    fn chunked_resampling_matches_one_shot() {
+        let samples = sine(44100, 3.0, 440.0);
+        assert!(samples.len() > 2 * RESAMPLE_BLOCK);
+        for quality in [ResampleQuality::Fast, ResampleQuality::Medium, ResampleQuality::Best] {
+            let converter = Samplerate::new(quality.converter_type(), 44100, WHISPER_SAMPLE_RATE, 1).unwrap();
+            let one_shot = converter.process_last(&samples).unwrap();
+            let chunked = audio_resample(&samples, 44100, WHISPER_SAMPLE_RATE, quality);
+            assert_eq!(chunked, one_shot, "{:?}", quality);
+        }
+    }
+
+    #[test]
+    fn to_mono_16k_keeps_mono_16k_input() {
+        let samples = sine(WHISPER_SAMPLE_RATE, 0.5, 440.0);
+        let mono = to_mono_16k(samples.iter().copied(), 1, WHISPER_SAMPLE_RATE, ResampleQuality::default());
+        assert_eq!(mono, samples);
+    }
+
+    #[test]
+    fn to_mono_16k_averages_channels() {
+        let stereo = [0.2, 0.4, -1.0, 1.0, 0.5, 0.5, 0.3];
+        let mono = to_mono_16k(stereo.into_iter(), 2, WHISPER_SAMPLE_RATE, ResampleQuality::default());
+        // 结尾不满一帧的采样按实际的通道数取平均
+        assert_eq!(mono, vec![0.3, 0.0, 0.5, 0.3]);
+    }
+
+    #[test]
+    fn to_mono_16k_treats_zero_channels_as_mono() {
+        let mono = to_mono_16k([0.1, 0.2].into_iter(), 0, WHISPER_SAMPLE_RATE, ResampleQuality::default());
+        assert_eq!(mono, vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn to_mono_16k_upsamples() {
+        let samples = sine(8000, 1.0, 440.0);
+        let mono = to_mono_16k(samples.into_iter(), 1, 8000, ResampleQuality::default());
+        assert!(mono.len().abs_diff(16000) <= 16, "got {} samples", mono.len());
+        assert!((peak(&mono) - 0.5).abs() < 0.05, "peak {}", peak(&mono));
+    }
+
+    #[test]
+    fn to_mono_16k_downsamples_stereo() {
+        let left = sine(48000, 1.0, 440.0);
+        let stereo: Vec<f32> = left.iter().flat_map(|&s| [s, s]).collect();
+        let mono = to_mono_16k(stereo.into_iter(), 2, 48000, ResampleQuality::default());
+        assert!(mono.len().abs_diff(16000) <= 16, "got {} samples", mono.len());
+        assert!((peak(&mono) - 0.5).abs() < 0.05, "peak {}", peak(&mono));
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 批量转写默认接受的音频扩展名：内置解码只支持 WAV，启用 `ffmpeg` feature 时再加上 ffmpeg 可解码的常见格式
+pub const AUDIO_EXTENSIONS: &[&str] = if cfg!(feature = "ffmpeg") { &["wav", "mp3", "flac", "ogg", "m4a"] } else { &["wav"] };
+
+/// 批处理时的统计结果：成功的文件数以及失败的文件和原因
+#[derive(Default)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl BatchSummary {
+    /// 打印最终的处理汇总
+    pub fn print(&self, total: usize) {
+        status!(
+            "Batch finished: {} succeeded, {} failed, {} total.",
+            self.succeeded,
+            self.failed.len(),
+            total
+        );
+        for (path, err) in &self.failed {
+            status!("  failed: {} ({})", path.display(), err);
+        }
+    }
+}
+
+/// 收集目录下扩展名匹配的文件（不区分大小写，可带前导 `.`），并按路径排序。
+///
+/// `recursive` 为 true 时同时收集各级子目录中的文件（不跟随指向目录的符号链接，避免循环）。
+/// 返回 `(匹配的文件, 因扩展名不符被跳过的文件)`。
+pub fn collect_input_files<S: AsRef<str>>(dir: &Path, extensions: &[S], recursive: bool) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if recursive && entry.file_type()?.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if !path.is_file() {
+                continue;
+            }
+            let matched = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| extensions.iter().any(|e| e.as_ref().trim_start_matches('.').eq_ignore_ascii_case(ext)))
+                .unwrap_or(false);
+            if matched {
+                files.push(path);
+            } else {
+                skipped.push(path);
+            }
+        }
+    }
+    files.sort();
+    skipped.sort();
+    Ok((files, skipped))
+}
+
+/// 多个 `-i` 输入共同的上级目录，作为 [`batch_output_path`] 的 `input_dir`，让 `--output-dir` 下保留它们的相对位置；
+/// 没有共同部分时为空路径
+pub fn common_parent(files: &[PathBuf]) -> PathBuf {
+    let mut parent = files.first().and_then(|f| f.parent()).map(Path::to_path_buf).unwrap_or_default();
+    while !files.iter().all(|f| f.starts_with(&parent)) {
+        if !parent.pop() {
+            return PathBuf::new();
+        }
+    }
+    parent
+}
+
+/// 批处理输出文件名模板中可用的占位符
+pub const TEMPLATE_PLACEHOLDERS: &[&str] = &["stem", "ext", "lang", "date"];
+
+/// 批量转写默认的输出文件名，例如 `talk.wav` => `talk.srt`
+pub const TRANSCRIPT_TEMPLATE: &str = "{stem}.{ext}";
+
+/// 批量翻译默认的输出文件名，例如 `talk.txt` => `talk.zh.txt`
+pub const TRANSLATION_TEMPLATE: &str = "{stem}.{lang}.{ext}";
+
+/// 批处理的输出文件名模板，例如 `{stem}.{lang}.srt`，对每个输入文件展开：
+/// `{stem}` 为输入文件名（不含扩展名），`{ext}` 为输出格式的扩展名，`{lang}` 为语言代码，
+/// `{date}` 为开始处理的日期（YYYY-MM-DD），同一批次中的所有文件相同。
+#[derive(Debug, Clone)]
+pub struct OutputTemplate {
+    template: String,
+    date: String,
+}
+
+impl OutputTemplate {
+    /// 校验模板：花括号必须成对，只能使用 [`TEMPLATE_PLACEHOLDERS`] 中的占位符，且只能是文件名（不含路径分隔符）。
+    ///
+    /// 用作 `--output-template` 的 `value_parser`，在开始处理之前就拒绝写错的模板。
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value.trim().is_empty() {
+            return Err("the output template is empty".to_string());
+        }
+        if value.contains(['/', '\\']) {
+            return Err(format!("'{}' must be a file name without directories; use --output-dir to choose the directory", value));
+        }
+        let mut rest = value;
+        while let Some(open) = rest.find('{') {
+            if rest[..open].contains('}') {
+                return Err(format!("unmatched '}}' in '{}'", value));
+            }
+            let after = &rest[open + 1..];
+            let close = after.find('}').ok_or_else(|| format!("unclosed '{{' in '{}'", value))?;
+            let name = &after[..close];
+            if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+                let known: Vec<String> = TEMPLATE_PLACEHOLDERS.iter().map(|p| format!("{{{}}}", p)).collect();
+                return Err(format!("unknown placeholder '{{{}}}' in '{}'; expected {}", name, value, known.join(", ")));
+            }
+            rest = &after[close + 1..];
+        }
+        if rest.contains('}') {
+            return Err(format!("unmatched '}}' in '{}'", value));
+        }
+        Ok(Self { template: value.to_string(), date: chrono::Local::now().format("%Y-%m-%d").to_string() })
+    }
+
+    /// 按输入文件展开模板，得到输出文件名
+    pub fn render(&self, input: &Path, ext: &str, lang: &str) -> String {
+        let stem = input.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        self.template
+            .replace("{stem}", &stem)
+            .replace("{ext}", ext)
+            .replace("{lang}", lang)
+            .replace("{date}", &self.date)
+    }
+
+    /// 展开后输出文件名中 `{stem}` 之后的部分，例如 `{stem}.{lang}.{ext}` => `.zh.txt`，
+    /// 批量翻译据此认出上次运行写出的译文；模板不含 `{stem}` 或其后没有内容时为 `None`
+    pub fn suffix(&self, ext: &str, lang: &str) -> Option<String> {
+        let (_, after) = self.template.rsplit_once("{stem}")?;
+        let suffix = after.replace("{ext}", ext).replace("{lang}", lang).replace("{date}", &self.date);
+        (!suffix.is_empty()).then_some(suffix)
+    }
+}
+
+/// 计算批处理的输出路径：文件名为 `file_name`，未指定 `output_dir` 时与输入文件同目录，
+/// 否则把输入相对于 `input_dir` 的路径映射到 `output_dir` 下，并创建所需的目录。
+pub fn batch_output_path(input: &Path, input_dir: &Path, output_dir: Option<&Path>, file_name: &str) -> io::Result<PathBuf> {
+    let sibling = input.with_file_name(file_name);
+    let Some(output_dir) = output_dir else {
+        return Ok(sibling);
+    };
+    let relative = sibling.strip_prefix(input_dir).unwrap_or_else(|_| Path::new(sibling.file_name().unwrap_or_default()));
+    let output = output_dir.join(relative);
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_nested_files_only_when_recursive() {
+        let root = std::env::temp_dir().join(format!("audio_trans_vox_{}_collect", std::process::id()));
+        fs::create_dir_all(root.join("day1/morning")).unwrap();
+        for file in ["a.wav", "notes.txt", "day1/b.WAV", "day1/morning/c.wav"] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+
+        let flat = collect_input_files(&root, &["wav"], false);
+        let nested = collect_input_files(&root, &[".wav"], true);
+        fs::remove_dir_all(&root).unwrap();
+
+        let (files, skipped) = flat.unwrap();
+        assert_eq!(files, [root.join("a.wav")]);
+        assert_eq!(skipped, [root.join("notes.txt")]);
+        let (files, skipped) = nested.unwrap();
+        assert_eq!(files, [root.join("a.wav"), root.join("day1/b.WAV"), root.join("day1/morning/c.wav")]);
+        assert_eq!(skipped, [root.join("notes.txt")]);
+    }
+
+    #[test]
+    fn suffix_is_what_follows_the_stem() {
+        let template = OutputTemplate::parse(TRANSLATION_TEMPLATE).unwrap();
+        assert_eq!(template.suffix("txt", "zh").as_deref(), Some(".zh.txt"));
+        assert_eq!(OutputTemplate::parse("{lang}-{stem}").unwrap().suffix("txt", "zh"), None);
+        assert_eq!(OutputTemplate::parse("translated.{ext}").unwrap().suffix("txt", "zh"), None);
+    }
+
+    #[test]
+    fn recursive_outputs_mirror_the_input_tree() {
+        let root = std::env::temp_dir().join(format!("audio_trans_vox_{}_mirror", std::process::id()));
+        let output = batch_output_path(&root.join("in/day1/b.wav"), &root.join("in"), Some(&root.join("out")), "b.txt");
+        let created = root.join("out/day1").is_dir();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(output.unwrap(), root.join("out/day1/b.txt"));
+        assert!(created);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::audio_transcribe::{TranscribeOptions, Whisper};
+use crate::manifest::wav_duration_secs;
+use crate::models::WhisperModel;
+use std::time::Instant;
+
+/// 基准测试使用的计算设备
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BenchDevice {
+    Gpu,
+    Cpu,
+}
+
+/// 对每个模型和设备组合重复转写同一个文件，打印平均实时率（RTF）和峰值内存。
+///
+/// RTF = 转写耗时 / 音频时长，小于 1 表示比实时更快。
+pub fn run(input: &str, models: &[String], devices: &[BenchDevice], runs: usize) {
+    let audio_secs = wav_duration_secs(input).expect("Failed to read benchmark input");
+    println!("Benchmarking {} ({:.1}s of audio), {} run(s) per configuration", input, audio_secs, runs);
+    println!("{:<12} {:<6} {:>10} {:>8} {:>14}", "model", "device", "mean (s)", "RTF", "peak RSS (MB)");
+
+    for name in models {
+        let model = match WhisperModel::find(name) {
+            Some(model) => model,
+            None => {
+                eprintln!("Unknown model '{}', skipping", name);
+                continue;
+            }
+        };
+        crate::ensure_model_exists(&model.into(), true);
+
+        for &device in devices {
+            let mut whisper = Whisper::with_gpu(&model.path(), device == BenchDevice::Gpu);
+            let options = TranscribeOptions::default();
+            let mut total_secs = 0.0;
+            for _ in 0..runs {
+                let started = Instant::now();
+                whisper.transcribe_segments(input, &options).expect("Transcription failed");
+                total_secs += started.elapsed().as_secs_f64();
+            }
+            let mean_secs = total_secs / runs.max(1) as f64;
+            let peak = peak_rss_mb()
+                .map(|mb| format!("{:.0}", mb))
+                .unwrap_or_else(|| "n/a".to_string());
+            println!(
+                "{:<12} {:<6} {:>10.2} {:>8.3} {:>14}",
+                model.name,
+                format!("{:?}", device).to_lowercase(),
+                mean_secs,
+                mean_secs / audio_secs,
+                peak
+            );
+        }
+    }
+}
+
+/// 读取进程的峰值常驻内存（MB），目前只支持 Linux
+fn peak_rss_mb() -> Option<f64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: f64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024.0)
+}
@@ -0,0 +1,182 @@
+use crate::audio_transcribe::Segment;
+use crate::segment_hook::SegmentSender;
+use crate::transcript;
+use serde_json::json;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// 客户端超过该时长仍不接收数据时断开它，避免一个卡住的浏览器拖住所有字幕
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 连接后超过该时长仍未完成 WebSocket 握手时放弃该连接
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Clients = Arc<Mutex<Vec<WebSocket<TcpStream>>>>;
+
+/// `--websocket`：把每个解码出的段落以 JSON 文本消息推送给所有已连接的 WebSocket 客户端，作为网页的实时字幕源。
+///
+/// 本程序作为服务端监听指定地址，浏览器用 `new WebSocket("ws://…")` 连接即可，随时连接、断开都不影响转写；
+/// 客户端只能收到连接之后解码出的段落。消息格式：
+///
+/// - `{"type":"segment","start_ms":…,"end_ms":…,"text":"…","speaker":…,"language":…,"confidence":…}`，
+///   字段与 `--format json` 中的段落相同；
+/// - 转写结束时发送 `{"type":"end"}`，随后关闭所有连接。
+///
+/// 与 [`crate::segment_hook::SegmentHook`] 一样，段落经无界队列送到后台线程，解码不会被慢客户端阻塞；
+/// 丢弃时等队列中已有的段落发送完。
+pub struct CaptionServer {
+    sender: Sender<Option<Segment>>,
+    worker: Option<JoinHandle<()>>,
+    local_addr: SocketAddr,
+}
+
+impl CaptionServer {
+    /// 在 `addr`（例如 `127.0.0.1:9001`）上开始监听
+    pub fn bind(addr: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let clients = Clients::default();
+        // 接受连接的线程随进程退出，不需要等待
+        let accepted = clients.clone();
+        thread::spawn(move || accept_clients(listener, accepted));
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || broadcast_queue(receiver, clients));
+        Ok(Self { sender, worker: Some(worker), local_addr })
+    }
+
+    /// 实际监听的地址（端口为 0 时由系统分配）
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn sender(&self) -> SegmentSender {
+        SegmentSender::unbounded(self.sender.clone())
+    }
+}
+
+impl Drop for CaptionServer {
+    fn drop(&mut self) {
+        let _ = self.sender.send(None);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn accept_clients(listener: TcpListener, clients: Clients) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        // 每个连接在自己的线程中握手，连上后迟迟不发送请求的客户端不会挡住其他连接
+        let clients = clients.clone();
+        thread::spawn(move || handshake(stream, clients));
+    }
+}
+
+fn handshake(stream: TcpStream, clients: Clients) {
+    let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+    match tungstenite::accept(stream) {
+        Ok(socket) => clients.lock().expect("caption clients poisoned").push(socket),
+        Err(e) => eprintln!("Warning: WebSocket handshake failed: {}", e),
+    }
+}
+
+fn broadcast_queue(receiver: Receiver<Option<Segment>>, clients: Clients) {
+    while let Ok(Some(segment)) = receiver.recv() {
+        let mut message = transcript::segment_json(&segment);
+        message["type"] = json!("segment");
+        broadcast(&clients, &message.to_string());
+    }
+    broadcast(&clients, &json!({ "type": "end" }).to_string());
+    for mut client in clients.lock().expect("caption clients poisoned").drain(..) {
+        let _ = client.close(None);
+        let _ = client.flush();
+    }
+}
+
+/// 向所有客户端发送一条消息，发送失败（已断开或超时）的客户端被移除
+fn broadcast(clients: &Clients, text: &str) {
+    let mut clients = clients.lock().expect("caption clients poisoned");
+    clients.retain_mut(|client| client.send(Message::text(text)).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn wait_for_clients(clients: &Clients, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while clients.lock().unwrap().len() < count {
+            assert!(Instant::now() < deadline, "client was not accepted in time");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn stalled_connection_does_not_block_handshakes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let clients = Clients::default();
+        let accepted = clients.clone();
+        thread::spawn(move || accept_clients(listener, accepted));
+
+        // 只建立 TCP 连接、不发送握手请求
+        let _stalled = TcpStream::connect(addr).unwrap();
+        let stream = TcpStream::connect(addr).unwrap();
+        let (_client, _) = tungstenite::client(format!("ws://{}", addr).as_str(), stream).unwrap();
+        wait_for_clients(&clients, 1);
+    }
+
+    #[test]
+    fn broadcasts_segments_then_end() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let clients = Clients::default();
+        let accepted = clients.clone();
+        thread::spawn(move || accept_clients(listener, accepted));
+
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (mut client, _) = tungstenite::client(format!("ws://{}", addr).as_str(), stream).unwrap();
+        wait_for_clients(&clients, 1);
+
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || broadcast_queue(receiver, clients));
+        let segment = Segment {
+            start_ms: 1000,
+            end_ms: 2500,
+            text: " hello".to_string(),
+            words: Vec::new(),
+            speaker: None,
+            language: Some("en".to_string()),
+            confidence: Some(0.5),
+        };
+        sender.send(Some(segment)).unwrap();
+        sender.send(None).unwrap();
+        worker.join().unwrap();
+
+        let mut next_json = || match client.read().unwrap() {
+            Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            other => panic!("unexpected message {:?}", other),
+        };
+        assert_eq!(
+            next_json(),
+            json!({
+                "type": "segment",
+                "start_ms": 1000,
+                "end_ms": 2500,
+                "text": "hello",
+                "speaker": null,
+                "language": "en",
+                "confidence": 0.5,
+            })
+        );
+        assert_eq!(next_json(), json!({ "type": "end" }));
+        assert!(matches!(client.read(), Ok(Message::Close(_)) | Err(_)));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::audio_transcribe::Segment;
+use crate::output::{write_output, WriteOptions};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 决定分块方式的转写参数；与检查点中记录的不一致时，已完成的块与本次的块对不上，检查点作废
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointKey {
+    /// 截取并重采样到 16kHz 后的采样数
+    pub samples: usize,
+    /// 截取范围的起点（毫秒）
+    pub offset_ms: i64,
+    /// 每块的采样数
+    pub chunk_len: usize,
+    /// 相邻块之间重叠的采样数
+    pub overlap: usize,
+    pub language: String,
+}
+
+/// 分块转写的检查点：已完成的段落和下一块的起始位置
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub key: CheckpointKey,
+    /// 下一块在截取后的 16kHz 采样中的起始位置，之前开始的块都已转写完成
+    pub next_sample: usize,
+    /// 最后一块（连同重叠部分）的结束位置（毫秒），续转时用于合并重叠区
+    pub previous_end_ms: i64,
+    pub segments: Vec<Segment>,
+}
+
+/// 输入文件旁的检查点文件路径：`<input>.checkpoint.json`
+pub fn sidecar_path(input: &str) -> PathBuf {
+    PathBuf::from(format!("{}.checkpoint.json", input))
+}
+
+/// 读取检查点；文件损坏或与本次的转写参数不一致时给出警告并返回 `None`，从头开始转写
+pub fn load(path: &Path, key: &CheckpointKey) -> Option<Checkpoint> {
+    let checkpoint: Checkpoint = match std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| Ok(serde_json::from_str(&text)?))
+    {
+        Ok(checkpoint) => checkpoint,
+        Err(e) => {
+            eprintln!("Warning: ignoring checkpoint {}: {:#}", path.display(), e);
+            return None;
+        }
+    };
+    if checkpoint.key != *key {
+        eprintln!(
+            "Warning: checkpoint {} was written for a different audio range or chunking, starting over",
+            path.display()
+        );
+        return None;
+    }
+    Some(checkpoint)
+}
+
+/// 写入检查点，整体替换旧文件，写到一半被中断也不会留下损坏的检查点
+pub fn save(path: &Path, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+    let json = serde_json::to_string(checkpoint)?;
+    write_output(path, &json, &WriteOptions::default())
+        .with_context(|| format!("failed to write checkpoint {}", path.display()))
+}
+
+/// 转写完成后删除检查点
+pub fn remove(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("Warning: failed to remove checkpoint {}: {}", path.display(), e);
+        }
+    }
+}
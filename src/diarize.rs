@@ -0,0 +1,164 @@
+use crate::audio_transcribe::Segment;
+
+/// 分析帧长（采样数），16kHz 下为 30ms
+const FRAME: usize = 480;
+/// 基频搜索范围（Hz），覆盖常见的成人与儿童说话声
+const MIN_F0_HZ: f32 = 60.0;
+const MAX_F0_HZ: f32 = 400.0;
+/// 有声帧的最低均方根能量，低于该值的帧视为静音
+const MIN_FRAME_RMS: f32 = 0.01;
+/// 自相关峰值与零延迟能量之比的下限，低于该值视为清音或噪声
+const MIN_VOICING: f32 = 0.3;
+/// k-means 的最大迭代次数
+const MAX_ITERATIONS: usize = 50;
+
+/// 按音色特征为段落分配说话人编号（从 1 开始，按首次出现的顺序编号）。
+///
+/// 这是一个粗略的启发式方法：对每个段落估计基频中位数与过零率，标准化后做 k-means 聚类。
+/// 适合两三位音高差异明显的说话人；没有可用语音特征的段落沿用前一段的说话人。
+///
+/// # 参数
+///
+/// * `samples` - 16kHz 单声道采样，段落时间戳以其开头为零点
+/// * `sample_rate` - 采样率
+/// * `segments` - 待标注的段落
+/// * `speakers` - 说话人数量
+pub fn assign_speakers(samples: &[f32], sample_rate: u32, segments: &mut [Segment], speakers: usize) {
+    let features: Vec<Option<[f32; 2]>> = segments
+        .iter()
+        .map(|segment| {
+            let start = (segment.start_ms.max(0) as usize * sample_rate as usize / 1000).min(samples.len());
+            let end = (segment.end_ms.max(0) as usize * sample_rate as usize / 1000).clamp(start, samples.len());
+            segment_features(&samples[start..end], sample_rate)
+        })
+        .collect();
+
+    let points: Vec<[f32; 2]> = features.iter().flatten().copied().collect();
+    let labels = cluster(&standardize(&points), speakers.max(1));
+
+    // 按首次出现的顺序重新编号，使第一个开口的人总是 Speaker 1
+    let mut order: Vec<usize> = Vec::new();
+    let mut labels = labels.into_iter();
+    let mut previous = None;
+    for (segment, feature) in segments.iter_mut().zip(&features) {
+        if feature.is_some() {
+            let label = labels.next().unwrap_or(0);
+            let id = match order.iter().position(|&l| l == label) {
+                Some(index) => index,
+                None => {
+                    order.push(label);
+                    order.len() - 1
+                }
+            };
+            previous = Some(id as u32 + 1);
+        }
+        segment.speaker = previous;
+    }
+}
+
+/// 计算一个段落的 `[ln(基频中位数), 平均过零率]`，没有有声帧时返回 `None`
+fn segment_features(samples: &[f32], sample_rate: u32) -> Option<[f32; 2]> {
+    let min_lag = (sample_rate as f32 / MAX_F0_HZ) as usize;
+    let max_lag = (sample_rate as f32 / MIN_F0_HZ) as usize;
+    let mut pitches = Vec::new();
+    let mut zcr_sum = 0.0;
+    let mut active_frames = 0;
+    for frame in samples.chunks_exact(FRAME) {
+        let energy: f32 = frame.iter().map(|s| s * s).sum();
+        if (energy / FRAME as f32).sqrt() < MIN_FRAME_RMS {
+            continue;
+        }
+        let crossings = frame.windows(2).filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0)).count();
+        zcr_sum += crossings as f32 / FRAME as f32;
+        active_frames += 1;
+
+        // 在基频范围内寻找自相关峰值
+        let best = (min_lag..max_lag.min(FRAME - 1))
+            .map(|lag| {
+                let correlation: f32 = frame[..FRAME - lag].iter().zip(&frame[lag..]).map(|(a, b)| a * b).sum();
+                (lag, correlation)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((lag, correlation)) = best {
+            if correlation / energy >= MIN_VOICING {
+                pitches.push(sample_rate as f32 / lag as f32);
+            }
+        }
+    }
+    if pitches.is_empty() {
+        return None;
+    }
+    pitches.sort_by(f32::total_cmp);
+    let median = pitches[pitches.len() / 2];
+    Some([median.ln(), zcr_sum / active_frames as f32])
+}
+
+/// 将每一维特征标准化为零均值、单位方差
+fn standardize(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let n = points.len() as f32;
+    let mut result = points.to_vec();
+    for dim in 0..2 {
+        let mean = points.iter().map(|p| p[dim]).sum::<f32>() / n;
+        let std = (points.iter().map(|p| (p[dim] - mean).powi(2)).sum::<f32>() / n).sqrt();
+        for point in &mut result {
+            point[dim] = if std > 0.0 { (point[dim] - mean) / std } else { 0.0 };
+        }
+    }
+    result
+}
+
+fn distance(a: &[f32; 2], b: &[f32; 2]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)
+}
+
+/// 确定性的 k-means：以最远点法选取初始中心，返回每个点的簇编号
+fn cluster(points: &[[f32; 2]], k: usize) -> Vec<usize> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let k = k.min(points.len());
+    let mut centers = vec![points[0]];
+    while centers.len() < k {
+        let farthest = points
+            .iter()
+            .max_by(|a, b| {
+                let da = centers.iter().map(|c| distance(a, c)).fold(f32::INFINITY, f32::min);
+                let db = centers.iter().map(|c| distance(b, c)).fold(f32::INFINITY, f32::min);
+                da.total_cmp(&db)
+            })
+            .copied()
+            .unwrap();
+        centers.push(farthest);
+    }
+
+    let mut labels = vec![0; points.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (point, label) in points.iter().zip(labels.iter_mut()) {
+            let nearest = (0..k)
+                .min_by(|&a, &b| distance(point, &centers[a]).total_cmp(&distance(point, &centers[b])))
+                .unwrap();
+            if nearest != *label {
+                *label = nearest;
+                changed = true;
+            }
+        }
+        for (index, center) in centers.iter_mut().enumerate() {
+            let members: Vec<_> = points.iter().zip(&labels).filter(|(_, &l)| l == index).map(|(p, _)| p).collect();
+            if !members.is_empty() {
+                let n = members.len() as f32;
+                *center = [
+                    members.iter().map(|p| p[0]).sum::<f32>() / n,
+                    members.iter().map(|p| p[1]).sum::<f32>() / n,
+                ];
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    labels
+}
@@ -0,0 +1,85 @@
+use anyhow::bail;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// `--min-free-space` 的默认值
+pub const DEFAULT_MIN_FREE: &str = "100M";
+
+static MIN_FREE: OnceLock<u64> = OnceLock::new();
+
+/// 设置录音和下载之后目标卷上至少要保留的空间（字节），只在启动时调用一次
+pub fn set_min_free(bytes: u64) {
+    let _ = MIN_FREE.set(bytes);
+}
+
+fn min_free() -> u64 {
+    *MIN_FREE.get_or_init(|| parse_size(DEFAULT_MIN_FREE).expect("default size is valid"))
+}
+
+/// 解析字节数，例如 `500M`、`2G`、`1.5GiB`、`0`；K/M/G/T 按 1024 进位，不区分大小写
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let number = lower.trim_end_matches("ib").trim_end_matches('b');
+    let (digits, exponent) = match number.chars().last() {
+        Some('k') => (&number[..number.len() - 1], 1),
+        Some('m') => (&number[..number.len() - 1], 2),
+        Some('g') => (&number[..number.len() - 1], 3),
+        Some('t') => (&number[..number.len() - 1], 4),
+        _ => (number, 0),
+    };
+    match digits.trim().parse::<f64>() {
+        Ok(n) if n >= 0.0 && n.is_finite() => Ok((n * 1024f64.powi(exponent)) as u64),
+        _ => Err(format!("expected a size such as 500M or 2G, got '{}'", value)),
+    }
+}
+
+/// 以 MB/GB 显示字节数
+pub fn format_size(bytes: u64) -> String {
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+    if mb >= 1024.0 {
+        format!("{:.1} GB", mb / 1024.0)
+    } else {
+        format!("{:.0} MB", mb)
+    }
+}
+
+/// 在写入 `path` 之前确认所在的卷上有足够的空间：预计写入 `needed` 字节后仍要剩下 `--min-free-space`。
+///
+/// 空间不足时返回错误，避免写到一半磁盘写满、留下损坏的文件；无法查询可用空间时只打印警告。
+pub fn ensure_free_space(path: &Path, needed: Option<u64>) -> anyhow::Result<()> {
+    // 文件和目录可能还不存在，查询最近一级已存在的上级目录
+    let volume = path
+        .ancestors()
+        .skip(1)
+        .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("."));
+    let available = match fs2::available_space(volume) {
+        Ok(available) => available,
+        Err(e) => {
+            eprintln!("Warning: could not check the free space on {}: {}", volume.display(), e);
+            return Ok(());
+        }
+    };
+    let reserve = min_free();
+    let required = needed.unwrap_or(0).saturating_add(reserve);
+    if available < required {
+        match needed {
+            Some(needed) => bail!(
+                "only {} free on the volume of {}, but {} are needed plus the {} kept free by --min-free-space",
+                format_size(available),
+                volume.display(),
+                format_size(needed),
+                format_size(reserve)
+            ),
+            None => bail!(
+                "only {} free on the volume of {}, below --min-free-space {}",
+                format_size(available),
+                volume.display(),
+                format_size(reserve)
+            ),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::audio_transcribe;
+use crate::download_model;
+use crate::models::{ModelEntry, MARIAN_MODELS};
+use crate::translate;
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::fmt;
+use std::path::Path;
+
+/// 检查下载服务器是否可达时请求的地址
+const DOWNLOAD_HOST: &str = "https://huggingface.co";
+
+/// whisper.cpp 系统信息中表示 GPU 后端的字段
+const GPU_BACKENDS: &[&str] = &["CUDA", "METAL", "VULKAN", "HIPBLAS", "SYCL", "CANN", "COREML"];
+
+/// 单项检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        })
+    }
+}
+
+/// 打印一行检查结果并返回其状态
+fn report(status: Status, check: &str, detail: impl fmt::Display) -> Status {
+    println!("[{}] {:<18} {}", status, check, detail);
+    status
+}
+
+/// 依次检查音频设备、GPU 后端、模型文件、模型目录写权限与下载服务器连通性，打印 PASS/WARN/FAIL 报告。
+///
+/// `whisper` 为按 `--model`/`--model-registry` 解析出的模型（解析失败时为错误），`name` 为其名称；
+/// `require_gpu` 时没有 GPU 后端算作失败。没有任何 FAIL 时返回 `true`。
+pub fn run(name: &str, whisper: anyhow::Result<ModelEntry>, require_gpu: bool) -> bool {
+    let mut results = vec![check_audio_device(), check_gpu_backend(require_gpu)];
+    // 有模型尚未下载时，下载服务器不可达就是失败而不只是警告
+    let mut needs_download = MARIAN_MODELS.iter().any(|model| !Path::new(&model.path()).exists());
+
+    match whisper {
+        Ok(entry) => {
+            needs_download |= !Path::new(&entry.path).exists();
+            results.push(check_file(&format!("Whisper '{}'", name), &entry.path, |path| {
+                audio_transcribe::inspect_model(path).map(|info| format!("ggml {}, {} vocab", info.model_type, info.n_vocab))
+            }));
+            results.push(check_writable(&entry.path));
+        }
+        Err(e) => results.push(report(Status::Fail, &format!("Whisper '{}'", name), format!("{:#}", e))),
+    }
+    for model in MARIAN_MODELS {
+        let check = format!("Marian {}->{}", model.source, model.target);
+        results.push(check_file(&check, &model.path(), |path| {
+            translate::inspect_safetensors(path).map(|(_, params, _)| format!("{:.1}M parameters", params as f64 / 1e6))
+        }));
+        for tokenizer in [model.tokenizer_source_path(), model.tokenizer_target_path()] {
+            if !Path::new(&tokenizer).exists() {
+                results.push(report(Status::Fail, &check, format!("tokenizer {} is missing", tokenizer)));
+            }
+        }
+    }
+    results.push(check_network(needs_download));
+
+    let worst = results.iter().copied().max().unwrap_or(Status::Pass);
+    println!();
+    match worst {
+        Status::Pass => println!("All checks passed."),
+        Status::Warn => println!("No failures, but some checks need attention."),
+        Status::Fail => println!("Some checks failed; fix them before transcribing or translating."),
+    }
+    worst != Status::Fail
+}
+
+/// 默认输出设备及其配置能否取得（录音通过回环捕获系统输出）
+fn check_audio_device() -> Status {
+    let host = cpal::default_host();
+    let Some(device) = host.default_output_device() else {
+        return report(Status::Fail, "Audio device", "no default output device; `capture` and `pipeline` will not work");
+    };
+    let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+    match device.default_output_config() {
+        Ok(config) => report(
+            Status::Pass,
+            "Audio device",
+            format!("{} ({} Hz, {} channel(s))", name, config.sample_rate().0, config.channels()),
+        ),
+        Err(e) => report(Status::Fail, "Audio device", format!("{}: {}", name, e)),
+    }
+}
+
+/// whisper.cpp 编译时是否启用了 GPU 后端；没有时只是较慢，除非要求必须使用 GPU，否则不算失败
+fn check_gpu_backend(require_gpu: bool) -> Status {
+    let info = whisper_rs::print_system_info();
+    let enabled: Vec<&str> = GPU_BACKENDS
+        .iter()
+        .copied()
+        .filter(|backend| info.contains(&format!("{} = 1", backend)))
+        .collect();
+    if enabled.is_empty() {
+        let status = if require_gpu { Status::Fail } else { Status::Warn };
+        report(status, "GPU backend", "none compiled in; whisper runs on the CPU")
+    } else {
+        report(Status::Pass, "GPU backend", enabled.join(", "))
+    }
+}
+
+/// 模型文件存在时用 `inspect` 确认其可以加载；尚未下载只是警告，首次使用时会自动下载
+fn check_file(check: &str, path: &str, inspect: impl FnOnce(&str) -> anyhow::Result<String>) -> Status {
+    if !Path::new(path).exists() {
+        return report(Status::Warn, check, format!("{} not downloaded yet; it is fetched on first use", path));
+    }
+    match inspect(path) {
+        Ok(detail) => report(Status::Pass, check, format!("{} ({})", path, detail)),
+        Err(e) => report(Status::Fail, check, format!("{} is not valid: {:#}", path, e)),
+    }
+}
+
+/// 模型所在目录能否创建并写入，下载模型时需要
+fn check_writable(model_path: &str) -> Status {
+    let dir = Path::new(model_path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let probe = dir.join(".doctor-write-test");
+    let result = std::fs::create_dir_all(dir).and_then(|()| std::fs::write(&probe, b""));
+    let _ = std::fs::remove_file(&probe);
+    match result {
+        Ok(()) => report(Status::Pass, "Model directory", format!("{} is writable", dir.display())),
+        Err(e) => report(Status::Fail, "Model directory", format!("cannot write to {}: {}", dir.display(), e)),
+    }
+}
+
+/// 下载服务器是否可达；模型都已下载时离线也能使用，只算警告，否则算失败
+fn check_network(needs_download: bool) -> Status {
+    let unreachable = if needs_download { Status::Fail } else { Status::Warn };
+    match download_model::check_reachable(DOWNLOAD_HOST) {
+        Ok(status) if status.is_success() || status.is_redirection() => {
+            report(Status::Pass, "Network", format!("{} is reachable", DOWNLOAD_HOST))
+        }
+        Ok(status) => report(unreachable, "Network", format!("{} answered HTTP {}", DOWNLOAD_HOST, status)),
+        Err(e) => report(unreachable, "Network", format!("cannot reach {}: {}", DOWNLOAD_HOST, e)),
+    }
+}
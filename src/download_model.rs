@@ -1,34 +1,415 @@
+use clap::Args;
+use serde::Serialize;
 use reqwest::blocking::Client;
 use std::fs::File;
-use std::io::Write;
-use std::path::Path;
-use std::time::Duration;
+use sha2::{Digest, Sha256};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use crate::disk_space;
+use crate::progress::ByteProgress;
 
-/// 下载文件，并保存到指定路径。如果下载失败则 panic。
+/// 下载相关的超时与重试设置，可通过命令行或环境变量配置
+#[derive(Args, Debug, Clone, Serialize)]
+pub struct DownloadConfig {
+    /// 建立连接的超时时间（秒）
+    #[arg(long = "connect-timeout", global = true, value_name = "SEC", env = "AUDIO_TRANS_VOX_CONNECT_TIMEOUT", default_value_t = 30, help = "Seconds to wait for a download connection to be established")]
+    pub connect_timeout_secs: u64,
+    /// 等待响应以及每次读取数据的超时时间（秒），连接停滞超过该时长即失败并重试，0 表示不限制
+    #[arg(long = "read-timeout", global = true, value_name = "SEC", env = "AUDIO_TRANS_VOX_READ_TIMEOUT", default_value_t = 60, help = "Seconds a download may stall without receiving data before it is retried (0 = no limit)")]
+    pub read_timeout_secs: u64,
+    /// 单次下载允许的最长时间（秒），0 表示不限制
+    #[arg(long = "download-timeout", global = true, value_name = "SEC", env = "AUDIO_TRANS_VOX_DOWNLOAD_TIMEOUT", default_value_t = 3600, help = "Maximum total seconds for a single download attempt (0 = no limit)")]
+    pub max_time_secs: u64,
+    /// 下载失败后的重试次数
+    #[arg(long = "download-retries", global = true, value_name = "N", env = "AUDIO_TRANS_VOX_DOWNLOAD_RETRIES", default_value_t = 3, help = "Number of times to retry a failed download")]
+    pub retries: u32,
+    /// 并行下载的连接数，服务器不支持 Range 请求时退回单连接
+    #[arg(long = "download-connections", global = true, value_name = "N", env = "AUDIO_TRANS_VOX_DOWNLOAD_CONNECTIONS", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=16), help = "Download large files over this many parallel ranged connections")]
+    pub connections: u32,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 30,
+            read_timeout_secs: 60,
+            max_time_secs: 3600,
+            retries: 3,
+            connections: 1,
+        }
+    }
+}
+
+static CONFIG: OnceLock<DownloadConfig> = OnceLock::new();
+
+/// 设置进程内所有下载使用的配置，只在启动时调用一次
+pub fn set_config(config: DownloadConfig) {
+    let _ = CONFIG.set(config);
+}
+
+fn config() -> &'static DownloadConfig {
+    CONFIG.get_or_init(DownloadConfig::default)
+}
+
+/// 下载文件，并保存到指定路径。
+///
+/// 按 [`DownloadConfig`] 设置连接、读取超时和每次尝试的总时长上限，失败时删除不完整的文件并以递增的间隔重试；
+/// 重试次数用尽或磁盘空间不足（见 [`disk_space::ensure_free_space`]）时 panic。
 pub fn download_file(url: &str, output_path: &str) {
-    println!("Downloading from {} ...", url);
+    status!("Downloading from {} ...", url);
+    let config = config();
 
-    // 使用 Client 构建器设置超时时间（例如 600 秒）
+    // 阻塞客户端的 timeout 作用于等待响应和每一次读取，相当于读取（空闲）超时；总时长由 copy_until 另行限制
+    let read_timeout = (config.read_timeout_secs > 0).then(|| Duration::from_secs(config.read_timeout_secs));
     let client = Client::builder()
-        .timeout(Duration::from_secs(600))
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .timeout(read_timeout)
         .build()
         .expect("Failed to build client");
 
-    let response = client.get(url).send().expect("Failed to send request");
+    // 如果输出目录不存在，则创建
+    if let Some(parent) = Path::new(output_path).parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create directories");
+    }
+    // 按服务器给出的大小先确认磁盘空间足够，避免下载到一半写满磁盘
+    let expected = client
+        .head(url)
+        .send()
+        .ok()
+        .filter(|head| head.status().is_success())
+        .and_then(|head| head.headers().get(CONTENT_LENGTH)?.to_str().ok()?.parse::<u64>().ok());
+    if let Err(e) = disk_space::ensure_free_space(Path::new(output_path), expected) {
+        panic!("Not enough disk space to download {}: {:#}", url, e);
+    }
+
+    let mut attempt = 0;
+    loop {
+        // 大模型下载时间很长，0 表示不设总时长上限
+        let deadline = (config.max_time_secs > 0).then(|| Instant::now() + Duration::from_secs(config.max_time_secs));
+        let result = match config.connections {
+            1 => try_download(&client, url, output_path, deadline),
+            connections => try_download_ranged(&client, url, output_path, connections as u64, deadline),
+        };
+        match result {
+            Ok(()) => break,
+            Err(e) => {
+                let _ = std::fs::remove_file(output_path);
+                if attempt >= config.retries {
+                    panic!("Failed to download {}: {}", url, e);
+                }
+                attempt += 1;
+                let delay = Duration::from_secs(1 << attempt.min(5));
+                eprintln!(
+                    "Download failed: {}. Retrying in {}s ({}/{})",
+                    e,
+                    delay.as_secs(),
+                    attempt,
+                    config.retries
+                );
+                std::thread::sleep(delay);
+            }
+        }
+    }
+    status!("Download completed and saved to {}", output_path);
+}
+
+/// 发起一次下载，边接收边写入文件，不把整个响应体放进内存
+fn try_download(client: &Client, url: &str, output_path: &str, deadline: Option<Instant>) -> Result<(), String> {
+    let mut response = client.get(url).send().map_err(|e| e.to_string())?;
 
     // 检查响应状态码
     if !response.status().is_success() {
-        panic!("Failed to download file: HTTP {}", response.status());
+        return Err(format!("HTTP {}", response.status()));
     }
 
-    // 如果输出目录不存在，则创建
-    if let Some(parent) = Path::new(output_path).parent() {
-        std::fs::create_dir_all(parent).expect("Failed to create directories");
+    let progress = ByteProgress::new("download", output_path, response.content_length());
+    let mut file = progress.writer(File::create(output_path).map_err(|e| e.to_string())?);
+    copy_until(&mut response, &mut file, deadline)?;
+    file.flush().map_err(|e| e.to_string())
+}
+
+/// 把 `reader` 的内容复制到 `writer`，返回复制的字节数；超过 `deadline`（`--download-timeout`）时返回错误
+fn copy_until(reader: &mut impl Read, writer: &mut impl Write, deadline: Option<Instant>) -> Result<u64, String> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(format!("download did not finish within --download-timeout of {}s", config().max_time_secs));
+        }
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        writer.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+        copied += n as u64;
+    }
+}
+
+/// 锁文件超过该时长未被释放时视为持有者已崩溃，可以接管
+const STALE_LOCK_AGE: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// 模型下载锁：以 `create_new` 原子地创建 `<path>.lock`，离开作用域（包括 panic 时）删除。
+///
+/// 同一文件同一时刻只有一个持有者，其他进程或线程会等待它完成。
+struct DownloadLock {
+    path: PathBuf,
+}
+
+impl DownloadLock {
+    fn lock_path(target: &str) -> PathBuf {
+        PathBuf::from(format!("{}.lock", target))
+    }
+
+    /// 尝试获取锁，已被占用时返回 `Ok(None)`
+    fn try_acquire(target: &str) -> io::Result<Option<Self>> {
+        let path = Self::lock_path(target);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let _ = writeln!(file, "{}", std::process::id());
+                Ok(Some(Self { path }))
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 锁文件是否已存在过久，持有者很可能已经异常退出
+    fn is_stale(target: &str) -> bool {
+        std::fs::metadata(Self::lock_path(target))
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map(|age| age > STALE_LOCK_AGE)
+            .unwrap_or(false)
+    }
+}
+
+impl Drop for DownloadLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 在并发环境下安全地下载模型文件到 `path`。
+///
+/// 先获取 `<path>.lock`，下载到带进程号的 `.part` 文件并校验 SHA-256（若提供），
+/// 最后原子地重命名为目标文件。其他进程或线程发现锁被占用时会等待，完成后直接复用结果。
+/// 下载或校验失败时 panic。
+pub fn download_exclusive(url: &str, path: &str, sha256: Option<&str>) {
+    let mut waiting = false;
+    loop {
+        if Path::new(path).exists() {
+            return;
+        }
+        match DownloadLock::try_acquire(path).expect("Failed to create download lock") {
+            Some(lock) => {
+                // 等待锁期间可能已经有人下载完成
+                if Path::new(path).exists() {
+                    return;
+                }
+                let part = format!("{}.{}.part", path, std::process::id());
+                download_file(url, &part);
+                if let Some(expected) = sha256 {
+                    let valid = verify_sha256(&part, expected).expect("Failed to hash downloaded model");
+                    if !valid {
+                        let _ = std::fs::remove_file(&part);
+                        panic!("Checksum mismatch for {}; the corrupt download has been removed", path);
+                    }
+                }
+                std::fs::rename(&part, path).expect("Failed to move downloaded model into place");
+                drop(lock);
+                return;
+            }
+            None if DownloadLock::is_stale(path) => {
+                eprintln!("Removing stale download lock {}", DownloadLock::lock_path(path).display());
+                let _ = std::fs::remove_file(DownloadLock::lock_path(path));
+            }
+            None => {
+                if !waiting {
+                    status!("Another process is downloading {}, waiting for it to finish...", path);
+                    waiting = true;
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// 分片下载时每个连接至少负责的字节数，小文件直接单连接下载
+const MIN_RANGE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// 使用多个 HTTP Range 请求并行下载，各连接直接写入文件中各自的区间。
+///
+/// 服务器未声明 `Accept-Ranges: bytes`、没有给出长度或文件较小时退回 [`try_download`]。
+/// 下载完成后核对文件大小，SHA-256 校验由调用方负责。
+fn try_download_ranged(client: &Client, url: &str, output_path: &str, connections: u64, deadline: Option<Instant>) -> Result<(), String> {
+    let head = client.head(url).send().map_err(|e| e.to_string())?;
+    let total = head
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let ranges_supported = head
+        .headers()
+        .get(ACCEPT_RANGES)
+        .map(|v| v.as_bytes().eq_ignore_ascii_case(b"bytes"))
+        .unwrap_or(false);
+    let total = match total {
+        Some(total) if head.status().is_success() && ranges_supported && total >= MIN_RANGE_BYTES * 2 => total,
+        _ => return try_download(client, url, output_path, deadline),
+    };
+
+    let connections = connections.min(total / MIN_RANGE_BYTES).max(1);
+    status!("Downloading {} bytes over {} connections", total, connections);
+    let file = File::create(output_path).map_err(|e| e.to_string())?;
+    file.set_len(total).map_err(|e| e.to_string())?;
+    drop(file);
+
+    let chunk = total.div_ceil(connections);
+    let progress = ByteProgress::new("download", output_path, Some(total));
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..connections)
+            .map(|index| {
+                let start = index * chunk;
+                let end = ((index + 1) * chunk).min(total) - 1;
+                let progress = &progress;
+                scope.spawn(move || download_range(client, url, output_path, start, end, progress, deadline))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|_| Err("download worker panicked".to_string())))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    let size = std::fs::metadata(output_path).map_err(|e| e.to_string())?.len();
+    if size != total {
+        return Err(format!("downloaded {} bytes, expected {}", size, total));
+    }
+    Ok(())
+}
+
+/// 下载 `[start, end]` 字节区间并写入文件的对应位置，写入的字节计入共享的 `progress`
+fn download_range(
+    client: &Client,
+    url: &str,
+    output_path: &str,
+    start: u64,
+    end: u64,
+    progress: &ByteProgress,
+    deadline: Option<Instant>,
+) -> Result<(), String> {
+    let mut response = client
+        .get(url)
+        .header(RANGE, format!("bytes={}-{}", start, end))
+        .send()
+        .map_err(|e| e.to_string())?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(format!("range request returned HTTP {}", response.status()));
+    }
+    let mut file = std::fs::OpenOptions::new().write(true).open(output_path).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+    let written = copy_until(&mut response, &mut progress.writer(&mut file), deadline)?;
+    if written != end - start + 1 {
+        return Err(format!("range {}-{} returned {} bytes", start, end, written));
+    }
+    file.flush().map_err(|e| e.to_string())
+}
+
+/// 判断输入是否为 http(s) 地址
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// 向 `url` 发送一次 HEAD 请求，检查下载服务器是否可达，返回 HTTP 状态码。
+///
+/// 只使用配置中的连接超时，整个请求最多等待该时长的两倍。
+pub fn check_reachable(url: &str) -> Result<StatusCode, String> {
+    let timeout = Duration::from_secs(config().connect_timeout_secs);
+    let client = Client::builder()
+        .connect_timeout(timeout)
+        .timeout(timeout * 2)
+        .build()
+        .map_err(|e| e.to_string())?;
+    client.head(url).send().map(|response| response.status()).map_err(|e| e.to_string())
+}
+
+/// 临时文件，离开作用域时自动删除
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// 在系统临时目录下生成一个带进程号前缀的路径（不创建文件）
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_{}", std::process::id(), name));
+        Self { path }
     }
 
-    let mut file = File::create(output_path).expect("Failed to create file");
-    // 直接将响应体复制到文件中
-    let content = response.bytes().expect("Failed to read response bytes");
-    file.write_all(&content).expect("Failed to write to file");
-    println!("Download completed and saved to {}", output_path);
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if self.path.exists() {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                eprintln!("Failed to remove temp file {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// 下载 `url` 到临时文件，文件名保留 URL 中的最后一段（去掉查询参数）
+pub fn download_to_temp(url: &str) -> TempFile {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|n| !n.is_empty())
+        .unwrap_or("download");
+    let temp = TempFile::new(name);
+    download_file(url, temp.path().to_str().expect("Temp path is not valid UTF-8"));
+    temp
+}
+
+/// 计算文件的 SHA-256 并与期望值（十六进制，不区分大小写）比较
+pub fn verify_sha256(path: &str, expected: &str) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    let actual: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(actual.eq_ignore_ascii_case(expected.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_until_copies_everything_without_a_deadline() {
+        let data = vec![7u8; 200 * 1024];
+        let mut copied = Vec::new();
+        assert_eq!(copy_until(&mut data.as_slice(), &mut copied, None), Ok(data.len() as u64));
+        assert_eq!(copied, data);
+    }
+
+    #[test]
+    fn copy_until_stops_after_the_deadline() {
+        let data = vec![7u8; 1024];
+        let mut copied = Vec::new();
+        let error = copy_until(&mut data.as_slice(), &mut copied, Some(Instant::now())).unwrap_err();
+        assert!(error.contains("--download-timeout"), "{}", error);
+        assert!(copied.is_empty());
+    }
 }
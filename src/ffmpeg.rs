@@ -0,0 +1,40 @@
+use crate::download_model::TempFile;
+use anyhow::{bail, Context};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 检查 PATH 中是否有可用的 ffmpeg
+pub fn is_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// 调用 ffmpeg 将任意格式的输入解码为 16kHz 单声道 16-bit PCM WAV 临时文件
+pub fn decode_to_wav(input: &Path) -> anyhow::Result<TempFile> {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "input".to_string());
+    let output = TempFile::new(&format!("{}.ffmpeg.wav", stem));
+    let result = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"])
+        .arg(output.path())
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run ffmpeg")?;
+    if !result.status.success() {
+        bail!(
+            "ffmpeg failed to decode {}: {}",
+            input.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    Ok(output)
+}
@@ -0,0 +1,50 @@
+/// 判断静音时使用的短时帧长度（秒）
+const FRAME_SECS: f64 = 0.02;
+
+/// 帧的 RMS 电平低于该值（dBFS）时视为静音
+pub const SILENCE_THRESHOLD_DBFS: f64 = -50.0;
+
+/// 一段音频的电平统计
+#[derive(Debug, Clone, Copy)]
+pub struct Levels {
+    /// 所有通道中最大的绝对采样值（dBFS），全零时为负无穷
+    pub peak_dbfs: f64,
+    /// 整段音频的 RMS 电平（dBFS），全零时为负无穷
+    pub rms_dbfs: f64,
+    /// RMS 低于 [`SILENCE_THRESHOLD_DBFS`] 的 20ms 帧所占的百分比
+    pub silence_percent: f64,
+}
+
+/// 一次遍历归一化后的交错采样，同时统计峰值、整体 RMS 与静音帧占比
+pub fn measure(samples: &[f32], channels: usize, sample_rate: u32) -> Levels {
+    let frame_len = ((sample_rate as f64 * FRAME_SECS) as usize).max(1) * channels.max(1);
+    // 直接比较均方值，省去每帧的对数运算
+    let silence_mean_square = 10f64.powf(SILENCE_THRESHOLD_DBFS / 10.0);
+
+    let mut peak = 0.0f32;
+    let mut total_square = 0.0f64;
+    let (mut frame_square, mut frame_count) = (0.0f64, 0usize);
+    let (mut frames, mut silent_frames) = (0usize, 0usize);
+    for &sample in samples {
+        peak = peak.max(sample.abs());
+        let square = sample as f64 * sample as f64;
+        total_square += square;
+        frame_square += square;
+        frame_count += 1;
+        // 最后一个不完整的帧也计入
+        if frame_count == frame_len || frame_count + frames * frame_len == samples.len() {
+            frames += 1;
+            if frame_square / (frame_count as f64) < silence_mean_square {
+                silent_frames += 1;
+            }
+            frame_square = 0.0;
+            frame_count = 0;
+        }
+    }
+
+    Levels {
+        peak_dbfs: 20.0 * (peak as f64).log10(),
+        rms_dbfs: 10.0 * (total_square / samples.len().max(1) as f64).log10(),
+        silence_percent: if frames == 0 { 0.0 } else { silent_frames as f64 * 100.0 / frames as f64 },
+    }
+}
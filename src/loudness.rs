@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// 绝对门限（LUFS），低于该响度的块不参与积分响度计算
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// 相对门限（LU），相对于首轮门限后的响度
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// 归一化后允许的最大峰值（dBFS），避免增益过大导致削波
+const MAX_PEAK_DBFS: f64 = -1.0;
+
+/// 二阶 IIR 滤波器（Direct Form I）
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0]
+            - self.a[2] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// 按 ITU-R BS.1770 构造 K 加权滤波器（高架滤波 + 高通滤波），系数随采样率计算
+fn k_weighting(sample_rate: u32) -> (Biquad, Biquad) {
+    let rate = sample_rate as f64;
+
+    // 第一级：模拟头部声学效应的高架滤波
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    // 第二级：RLB 高通滤波
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    (shelf, high_pass)
+}
+
+fn mean_square_to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// 计算交错采样的积分响度（LUFS），所有通道权重均为 1。
+///
+/// 采用 400ms 块、75% 重叠，并依次应用 -70 LUFS 绝对门限和 -10 LU 相对门限。
+/// 音频过短或全部为静音时返回 `None`。
+pub fn integrated_loudness(samples: &[f32], channels: usize, sample_rate: u32) -> Option<f64> {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+
+    // 对每个通道做 K 加权，并累计每帧的能量（各通道能量之和）
+    let mut filters: Vec<_> = (0..channels).map(|_| k_weighting(sample_rate)).collect();
+    let mut energy = vec![0.0f64; frames];
+    for (frame, chunk) in samples.chunks_exact(channels).enumerate() {
+        for (channel, &sample) in chunk.iter().enumerate() {
+            let (shelf, high_pass) = &mut filters[channel];
+            let weighted = high_pass.process(shelf.process(sample as f64));
+            energy[frame] += weighted * weighted;
+        }
+    }
+
+    let block = (sample_rate as usize * 400) / 1000;
+    let step = block / 4;
+    if block == 0 || frames < block {
+        return None;
+    }
+    let blocks: Vec<f64> = (0..=(frames - block) / step)
+        .map(|i| energy[i * step..i * step + block].iter().sum::<f64>() / block as f64)
+        .filter(|&z| z > 0.0 && mean_square_to_lufs(z) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let relative_gate = mean_square_to_lufs(blocks.iter().sum::<f64>() / blocks.len() as f64) + RELATIVE_GATE_LU;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|&z| mean_square_to_lufs(z) > relative_gate)
+        .collect();
+    if gated.is_empty() {
+        return None;
+    }
+    Some(mean_square_to_lufs(gated.iter().sum::<f64>() / gated.len() as f64))
+}
+
+/// 测量 16-bit PCM WAV 文件的积分响度，并施加增益使其达到 `target_lufs`，原地重写文件。
+///
+/// 为避免削波，增益会被限制在峰值不超过 -1 dBFS 的范围内。
+/// 返回 `(测量响度, 实际施加的增益 dB)`；音频过短或静音时返回 `Ok(None)`，文件保持不变。
+pub fn normalize_wav(path: impl AsRef<Path>, target_lufs: f64) -> hound::Result<Option<(f64, f64)>> {
+    let path = path.as_ref();
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    if spec.sample_format != hound::SampleFormat::Int || spec.bits_per_sample != 16 {
+        return Err(hound::Error::Unsupported);
+    }
+    let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>()?;
+    drop(reader);
+
+    let normalized: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+    let loudness = match integrated_loudness(&normalized, spec.channels as usize, spec.sample_rate) {
+        Some(loudness) => loudness,
+        None => return Ok(None),
+    };
+
+    let peak = samples.iter().map(|&s| (s as i32).abs()).max().unwrap_or(0) as f64 / 32768.0;
+    let max_gain_db = if peak > 0.0 { MAX_PEAK_DBFS - 20.0 * peak.log10() } else { f64::INFINITY };
+    let gain_db = (target_lufs - loudness).min(max_gain_db);
+    let gain = 10f64.powf(gain_db / 20.0);
+
+    // 先写到临时文件，再替换原文件
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".part");
+    let mut writer = hound::WavWriter::create(&tmp, spec)?;
+    for &sample in &samples {
+        let scaled = (sample as f64 * gain).round().clamp(i16::MIN as f64, i16::MAX as f64);
+        writer.write_sample(scaled as i16)?;
+    }
+    writer.finalize()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(Some((loudness, gain_db)))
+}
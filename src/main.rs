@@ -1,100 +1,1878 @@
-use clap::{Parser, Subcommand};
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+// 需在其他模块之前声明，`status!` 宏才能在这些模块中使用
+#[macro_use]
+mod progress;
+use progress::Spinner;
 mod audio_capture;
-use audio_capture::AudioCapture;
+use audio_capture::{AudioCapture, CaptureFormat, CaptureSummary, Downmix, STDOUT_PATH};
 mod download_model;
-use download_model::download_file;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::io::{BufRead, IsTerminal, Write};
 
 mod audio_transcribe;
-use audio_transcribe::Whisper;
+use audio_transcribe::{PartialTranscript, ResampleQuality, TranscribeOptions, Whisper, WordTimestamps};
+use whisper_rs::{DtwMode, DtwParameters, WhisperContextParameters};
 
 mod translate;
+use translate::{TranslationFormat, Translator};
+
+mod translation_cache;
+mod replacements;
+
+mod text_encoding;
+use translation_cache::TranslationCache;
+use replacements::Replacements;
+
+mod transcript;
+use transcript::{Cleanup, OutputFormat};
+
+mod batch;
+
+mod segment_hook;
+use segment_hook::SegmentHook;
+mod translation_worker;
+use translation_worker::TranslationWorker;
+#[cfg(feature = "websocket")]
+mod caption_server;
+
+mod checkpoint;
+
+mod output;
+use output::{write_output, AtomicFile, WriteOptions};
+
+mod models;
+use models::{MarianModel, ModelEntry};
+
+mod bench;
+
+mod doctor;
+use bench::BenchDevice;
+
+#[cfg(feature = "ffmpeg")]
+mod ffmpeg;
+
+#[cfg(feature = "diarize")]
+mod diarize;
+
+mod loudness;
+
+mod agc;
+
+mod vad;
+
+mod manifest;
+use manifest::{AlignedSegment, PipelineManifest};
+
+mod waveform;
+
+mod levels;
+
+mod wav64;
+mod playback;
+
+mod paths;
+mod disk_space;
+mod rolling_buffer;
+use rolling_buffer::RollingBuffer;
 
 #[derive(Parser)]
 #[command(name = "AudioTransVox", version = "1.0", author = "Swartz Lubel <swartz_luel@outlook.com>", about = "Audio translation tool", long_about = "AudioTransVox is a tool for capturing, transcribing, and translating audio files.")]
 struct Cli {
+    #[arg(short, long, global = true, help = "Hide progress indicators")]
+    quiet: bool,
+    #[arg(long, global = true, help = "Print the effective configuration as JSON and exit without doing anything")]
+    print_config: bool,
+    #[arg(short, long, global = true, help = "Print extra diagnostic output on stderr, such as the files skipped in batch mode and each translation step")]
+    verbose: bool,
+    #[arg(long, global = true, help = "Print only the result on stdout and send every status message to stderr, for use in scripts and pipes")]
+    raw: bool,
+    #[arg(long, global = true, help = "Emit newline-delimited JSON progress events (stage, percent, file) on stderr instead of spinners")]
+    progress_json: bool,
+    #[command(flatten)]
+    download: download_model::DownloadConfig,
+    #[command(flatten)]
+    translation_cache: translation_cache::CacheConfig,
+    #[arg(long, global = true, value_name = "FILE", value_parser = paths::expand_path, help = "Fix recurring mistranslations with find=>replace rules from this file (one per line, re: prefix for a regex), applied to every translated segment")]
+    replace: Option<String>,
+    #[arg(long, global = true, value_name = "SIZE", default_value = disk_space::DEFAULT_MIN_FREE, value_parser = disk_space::parse_size, help = "Refuse to start a capture or model download unless this much disk space (e.g. 500M, 2G; 0 to disable) would remain free on the target volume afterwards")]
+    min_free_space: u64,
     #[command(subcommand)]
     command: Commands,
 }
 
-fn ensure_model_exists(model_path: &str, download_url: &str) {
-    if !Path::new(model_path).exists() {
-        println!("Model file not found at {}. Downloading...", model_path);
-        download_file(download_url, model_path);
-    } 
+/// 确保模型文件存在，不存在时下载。多个进程或线程同时首次运行时只会有一个实际下载，其余等待并复用结果。
+///
+/// `allow_download` 为 `false`（`--no-download`）时，缺少模型直接报错退出，并给出期望的路径和下载地址。
+pub(crate) fn ensure_model_exists(model: &ModelEntry, allow_download: bool) {
+    if !Path::new(&model.path).exists() {
+        if !allow_download {
+            eprintln!("Model file not found at {} and --no-download was given.", model.path);
+            eprintln!("Download it from {} or run without --no-download.", model.url);
+            std::process::exit(1);
+        }
+        status!("Model file not found at {}. Downloading...", model.path);
+        download_model::download_exclusive(&model.url, &model.path, model.sha256.as_deref());
+    }
+}
+
+/// 选择 Whisper 模型的命令行参数
+#[derive(Args)]
+struct ModelArgs {
+    #[arg(long, value_name = "NAME", help = "Whisper model name, from the built-in table (tiny, base, small, medium, large-v3) or --model-registry (default: base; asked interactively on first run in a terminal)")]
+    model: Option<String>,
+    #[arg(long, value_name = "FILE", value_parser = paths::expand_path, help = "JSON file mapping model names to {path, url, sha256}, merged over the built-in table")]
+    model_registry: Option<String>,
+    #[arg(long, value_name = "INDEX", value_parser = clap::value_parser!(i32).range(0..), help = "Index of the GPU whisper should run on (default 0)")]
+    gpu_device: Option<i32>,
+    #[arg(long, help = "Fail instead of falling back to the CPU when the GPU backend cannot be initialized")]
+    require_gpu: bool,
+    #[arg(long, help = "Fail immediately when a model file is missing instead of downloading it")]
+    no_download: bool,
 }
 
+impl ModelArgs {
+    /// 实际使用的模型名称，未指定 `--model` 时为默认模型
+    fn model_name(&self) -> &str {
+        self.model.as_deref().unwrap_or(models::DEFAULT_WHISPER_MODEL)
+    }
+
+    /// 解析要加载的模型名称。
+    ///
+    /// 未指定 `--model`/`--model-registry`/`--no-download`、默认模型尚未下载且标准输入输出都是终端时，
+    /// 列出内置模型及其下载大小让用户选择；脚本等非交互运行沿用默认模型。
+    fn pick_model_name(&self) -> String {
+        let default_missing = || {
+            models::WhisperModel::find(models::DEFAULT_WHISPER_MODEL).is_some_and(|m| !Path::new(&m.path()).exists())
+        };
+        let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+        if self.model.is_some() || self.model_registry.is_some() || self.no_download || !interactive || !default_missing() {
+            return self.model_name().to_string();
+        }
+        prompt_whisper_model()
+    }
+}
+
+/// 在终端中列出内置 Whisper 模型并读取用户的选择，直接回车使用默认模型
+fn prompt_whisper_model() -> String {
+    println!("No whisper model downloaded yet. Available models:");
+    for (index, model) in models::WHISPER_MODELS.iter().enumerate() {
+        let default = if model.name == models::DEFAULT_WHISPER_MODEL { " (default)" } else { "" };
+        println!("  {}) {:<9} ~{} MB{}", index + 1, model.name, model.size_mb, default);
+    }
+    loop {
+        print!("Choose a model [1-{}, or a name; Enter for {}]: ", models::WHISPER_MODELS.len(), models::DEFAULT_WHISPER_MODEL);
+        std::io::stdout().flush().expect("Failed to flush stdout");
+        let mut answer = String::new();
+        // 读到 EOF（例如 Ctrl+D）时同样使用默认模型
+        if std::io::stdin().read_line(&mut answer).expect("Failed to read from stdin") == 0 {
+            println!();
+            return models::DEFAULT_WHISPER_MODEL.to_string();
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return models::DEFAULT_WHISPER_MODEL.to_string();
+        }
+        let chosen = match answer.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|i| models::WHISPER_MODELS.get(i)),
+            Err(_) => models::WhisperModel::find(answer),
+        };
+        match chosen {
+            Some(model) => return model.name.to_string(),
+            None => println!("Unknown choice '{}'.", answer),
+        }
+    }
+}
+
+/// 影响 Whisper 转录行为的命令行参数，对应 [`TranscribeOptions`]
+#[derive(Args)]
+struct TranscribeArgs {
+    #[arg(long, value_name = "SEC", help = "Start transcribing at this offset in seconds")]
+    start: Option<f32>,
+    #[arg(long, value_name = "SEC", help = "Stop transcribing at this offset in seconds")]
+    end: Option<f32>,
+    #[arg(long, value_enum, default_value_t = ResampleQuality::Best, help = "Resampling quality used when the input is not 16kHz")]
+    resample_quality: ResampleQuality,
+    #[arg(long, help = "Allow whisper to emit blank output at the start of a segment (suppressed by default)")]
+    no_suppress_blank: bool,
+    #[arg(long, help = "Suppress non-speech tokens such as music symbols and punctuation-only output")]
+    suppress_non_speech: bool,
+    #[arg(long, help = "Feed the text already recognized back to whisper as context for the next window (off by default)")]
+    keep_context: bool,
+    #[arg(long, help = "Force a single segment per decoding window, for short independent clips")]
+    single_segment: bool,
+    #[arg(long, value_name = "H", default_value_t = audio_transcribe::DEFAULT_ENTROPY_THRESHOLD, help = "Re-decode a window at a higher temperature when its average entropy is below this value (repetitive text)")]
+    entropy_threshold: f32,
+    #[arg(long, value_name = "LOGP", default_value_t = audio_transcribe::DEFAULT_LOGPROB_THRESHOLD, allow_negative_numbers = true, help = "Re-decode a window at a higher temperature when its average log probability is below this value (low-confidence gibberish)")]
+    logprob_threshold: f32,
+    #[arg(long, value_name = "N", default_value_t = audio_transcribe::DEFAULT_BEST_OF, value_parser = clap::value_parser!(i32).range(1..), help = "With greedy decoding, sample N candidates when falling back to a higher temperature and keep the best one")]
+    best_of: i32,
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(1..), conflicts_with = "best_of", help = "Use beam search with N beams instead of greedy decoding; more accurate but noticeably slower")]
+    beam_size: Option<i32>,
+    #[arg(long, value_name = "P", requires = "beam_size", allow_negative_numbers = true, help = "Beam search patience factor passed to whisper.cpp (default -1, disabled)")]
+    patience: Option<f32>,
+    #[arg(long, value_enum, value_name = "MODE", help = "Emit per-word timestamps as SRT (one cue per word), or as a words array per segment with --format json; dtw is more precise but uses extra memory")]
+    word_timestamps: Option<WordTimestamps>,
+    #[arg(long, value_name = "SEC", value_parser = parse_positive_secs, help = "Transcribe in chunks of this many seconds and detect the language of each chunk, for code-switched audio")]
+    detect_language_every: Option<f32>,
+    #[arg(long, value_name = "SEC", value_parser = parse_positive_secs, requires = "detect_language_every", help = "Overlap adjacent --detect-language-every chunks by this many seconds and de-duplicate the text recognized twice")]
+    chunk_overlap: Option<f32>,
+    #[arg(long, value_name = "DBFS", num_args = 0..=1, default_missing_value = "-40", allow_negative_numbers = true, conflicts_with = "detect_language_every", help = "Only transcribe regions louder than this level (default -40 dBFS), skipping long silences")]
+    vad: Option<f32>,
+    #[arg(long, value_name = "CODE", conflicts_with = "detect_language_every", help = "Language spoken in the audio (e.g. en, zh); detected automatically by default")]
+    language: Option<String>,
+    #[arg(long, value_name = "CODE", help = "Use this language instead of automatic detection for clips shorter than --short-clip-secs")]
+    force_language_on_short: Option<String>,
+    #[arg(long, value_name = "SEC", default_value_t = 3.0, value_parser = parse_positive_secs, help = "Clips shorter than this are considered too short for reliable language detection")]
+    short_clip_secs: f32,
+    #[arg(long, value_name = "P", value_parser = parse_probability, help = "Drop segments whose mean token probability is below this value (0-1) before writing any output format")]
+    min_segment_confidence: Option<f32>,
+    #[arg(long, value_enum, value_name = "RULE", value_delimiter = ',', num_args = 0..=1, default_missing_value = "spaces,ellipsis,cjk-punct", help = "Clean up segment text before writing or translating: spaces, ellipsis, cjk-punct (comma-separated; all of them when given without a value)")]
+    cleanup: Vec<Cleanup>,
+    #[arg(long, value_name = "SEC", value_parser = parse_positive_secs, help = "Stop transcribing after this many seconds and keep the segments decoded so far")]
+    timeout: Option<f32>,
+    #[arg(long, value_name = "CMD", help = "Run this shell command for every segment as soon as it is decoded; the segment is passed as a JSON line on stdin and in AUDIO_TRANS_VOX_SEGMENT_* variables")]
+    segment_callback_script: Option<String>,
+}
+
+/// 解析大于零的秒数
+fn parse_positive_secs(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(secs) if secs > 0.0 => Ok(secs),
+        _ => Err(format!("expected a positive number of seconds, got '{}'", value)),
+    }
+}
+
+/// 解析 0 到 1 之间的概率
+fn parse_probability(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("expected a probability between 0 and 1, got '{}'", value)),
+    }
+}
+
+impl TranscribeArgs {
+    fn options(&self) -> TranscribeOptions {
+        TranscribeOptions {
+            start_secs: self.start,
+            end_secs: self.end,
+            resample_quality: self.resample_quality,
+            suppress_blank: !self.no_suppress_blank,
+            suppress_non_speech_tokens: self.suppress_non_speech,
+            no_context: !self.keep_context,
+            single_segment: self.single_segment,
+            entropy_threshold: self.entropy_threshold,
+            logprob_threshold: self.logprob_threshold,
+            best_of: self.best_of,
+            beam_size: self.beam_size,
+            patience: self.patience.unwrap_or(audio_transcribe::DEFAULT_PATIENCE),
+            word_timestamps: self.word_timestamps,
+            language_chunk_secs: self.detect_language_every,
+            chunk_overlap_secs: self.chunk_overlap,
+            checkpoint: None,
+            resume: false,
+            vad_threshold_dbfs: self.vad,
+            language: self.language.clone(),
+            short_clip_secs: self.short_clip_secs,
+            short_clip_language: self.force_language_on_short.clone(),
+            abort: None,
+            timeout: self.timeout.map(Duration::from_secs_f32),
+            min_segment_confidence: self.min_segment_confidence,
+            cleanup: self.cleanup.clone(),
+            segment_hooks: Vec::new(),
+        }
+    }
+}
+
+/// 说话人分离的命令行参数（需要 `diarize` feature）
+#[cfg(feature = "diarize")]
+#[derive(Args)]
+struct DiarizeArgs {
+    #[arg(long, help = "Label each segment with a rough speaker id based on pitch and timbre")]
+    diarize: bool,
+    #[arg(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..=8), requires = "diarize", help = "Number of speakers to separate with --diarize")]
+    speakers: u32,
+}
+
+#[cfg(feature = "diarize")]
+impl DiarizeArgs {
+    /// 启用 `--diarize` 时重新解码音频并为段落标注说话人；解码失败只给出警告
+    fn apply(&self, whisper: &Whisper, input: &str, quality: ResampleQuality, segments: &mut [audio_transcribe::Segment]) {
+        if !self.diarize {
+            return;
+        }
+        match whisper.load_mono_16k(input, quality) {
+            Ok(samples) => diarize::assign_speakers(&samples, audio_transcribe::WHISPER_SAMPLE_RATE, segments, self.speakers as usize),
+            Err(e) => eprintln!("Speaker diarization skipped: {:#}", e),
+        }
+    }
+}
+
+// 只在启动时解析一次，不值得为了变体大小把参数装箱
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
-    #[command(about = "Capture audio from the default output", long_about = "Capture audio from the default output and save it to a file with a timestamped name.\n\nUsage:\n  audio_trans_vox.exe capture")]
-    Capture,
-    #[command(about = "Transcribe audio to text", long_about = "Transcribe the given audio file to text and display the result in the terminal.\n\nArguments:\n  -i, --input <FILE>    The input audio file to transcribe\n  -o, --output <FILE>   The output text file to save the transcription result\n\nUsage:\n  audio_trans_vox.exe transcribe -i <FILE> [-o <FILE>]")]
+    #[command(about = "Capture audio from the default output", long_about = "Capture audio from the default output and save it to a file with a timestamped name.\n\nArguments:\n  -o, --output <FILE>  Write the recording to FILE; use - to stream the WAV to stdout\n      --archive-dir <DIR>  Write the recording into DIR/<year>/<month>/\n      --device <NAME>      Loop back this output device instead of the default one\n      --list-devices       List the output devices that can be captured\n      --format <wav|raw>\n                           Container format; raw writes headerless s16le mono PCM (default: wav)\n      --match <FILE>       Match the sample rate, channels and bit depth of a reference WAV\n      --sample-rate <HZ>   Preferred capture sample rate\n      --channels <N>       Preferred number of device channels (1 or 2)\n      --force-mono         Treat the device as mono, whatever it reports\n      --force-stereo       Treat the device as stereo, whatever it reports\n      --channel <N,...>    Only record these device channels (1-based), mixed down to mono\n      --downmix <average|left|right|mid>\n                           How stereo is collapsed to mono (default: average)\n      --loudnorm [LUFS]    Normalize the recording to a target loudness (default -16 LUFS)\n      --agc [DBFS]         Automatic gain control towards a target RMS level (default -20 dBFS)\n      --max-duration <SEC> Stop automatically after this many seconds\n      --buffer-size <FRAMES>\n                           Fixed capture buffer size for lower latency\n\nUsage:\n  audio_trans_vox.exe capture [-o <FILE>] [--sample-rate <HZ>] [--channels <N>] [--loudnorm [LUFS]] [--buffer-size <FRAMES>]\n  audio_trans_vox.exe capture -o - | <consumer>\n  audio_trans_vox.exe capture --device \"Speakers\" [--force-stereo]\n  audio_trans_vox.exe capture --match dataset/sample-001.wav\n  audio_trans_vox.exe capture --archive-dir recordings")]
+    Capture {
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, help = "Write the recording to this file instead of a timestamped name; use - to stream the WAV to stdout")]
+        output: Option<String>,
+        #[arg(long, value_name = "DIR", value_parser = paths::expand_path, conflicts_with = "output", help = "Write the timestamped recording into a dated subdirectory, DIR/<year>/<month>/, creating it as needed")]
+        archive_dir: Option<String>,
+        #[arg(long, value_name = "NAME", help = "Loop back this output device (render endpoint) instead of the default one; matched case-insensitively, a unique substring is enough")]
+        device: Option<String>,
+        #[arg(long, help = "List the output devices that can be captured and exit")]
+        list_devices: bool,
+        #[arg(long, value_enum, default_value_t = CaptureFormat::Wav, help = "Recording format; raw writes headerless 16-bit little-endian mono PCM and reports its parameters on stderr")]
+        format: CaptureFormat,
+        #[arg(long = "match", value_name = "FILE", value_parser = paths::expand_path, conflicts_with_all = ["format", "sample_rate", "channels", "channel"], help = "Record in the same sample rate, channel count and bit depth as this reference WAV file, resampling and remixing as needed")]
+        match_file: Option<String>,
+        #[arg(long, value_name = "HZ", help = "Preferred capture sample rate; the nearest supported rate is used")]
+        sample_rate: Option<u32>,
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=2), help = "Preferred number of device channels (1 or 2)")]
+        channels: Option<u16>,
+        #[arg(long, conflicts_with_all = ["channels", "force_stereo", "match_file"], help = "Open the device as mono and treat every captured sample as one frame, whatever channel layout the device reports")]
+        force_mono: bool,
+        #[arg(long, conflicts_with_all = ["channels", "match_file"], help = "Open the device as stereo and mix every pair of captured samples down per --downmix, whatever channel layout the device reports")]
+        force_stereo: bool,
+        #[arg(long, value_name = "N,...", value_delimiter = ',', value_parser = clap::value_parser!(u16).range(1..=64), conflicts_with = "channels", help = "Only record these device channels (1-based, comma-separated, e.g. 3 or 2,3), mixed down to mono")]
+        channel: Option<Vec<u16>>,
+        #[arg(long, value_enum, default_value_t = Downmix::Average, conflicts_with = "channel", help = "How to collapse stereo to mono: average both channels, keep only left or right, or the mid (center) signal")]
+        downmix: Downmix,
+        #[arg(long, value_name = "LUFS", num_args = 0..=1, default_missing_value = "-16", allow_negative_numbers = true, help = "After recording, normalize the integrated loudness to this target (default -16 LUFS)")]
+        loudnorm: Option<f64>,
+        #[arg(long, value_name = "DBFS", num_args = 0..=1, default_missing_value = "-20", allow_negative_numbers = true, help = "While recording, smoothly adjust the gain towards this RMS level (default -20 dBFS); off by default")]
+        agc: Option<f32>,
+        #[arg(long, value_name = "SEC", help = "Safety cap: stop and finalize the recording after this many seconds")]
+        max_duration: Option<u64>,
+        #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..), help = "Request a fixed capture buffer size for lower latency; falls back to the driver default if rejected")]
+        buffer_size: Option<u32>,
+    },
+    #[command(about = "Transcribe audio to text", long_about = "Transcribe the given audio file to text and display the result in the terminal.\n\nArguments:\n  -i, --input <FILE>    The input audio file (or http(s) URL) to transcribe; repeat to transcribe several files\n  -o, --output <FILE>   The output text file to save the transcription result\n      --input-dir <DIR> Transcribe every audio file in the directory\n      --extensions <EXT,...>\n                        With --input-dir, only pick up these extensions (default: wav; with ffmpeg also mp3,flac,ogg,m4a)\n      --recursive       With --input-dir, also transcribe files in subdirectories\n      --append <FILE>   With --input-dir or several -i, append all transcripts to a single file\n      --output-dir <DIR>\n                        With --input-dir or several -i, write outputs into DIR instead of next to the inputs\n      --output-template <TEMPLATE>\n                        With --input-dir or several -i, name outputs from a template, e.g. {stem}.{lang}.{ext}\n      --jsonl <FILE>    With --input-dir or several -i, also append one JSON line per input file to FILE\n      --format <FORMAT> Output format: txt, srt, vtt, json or csv (default: txt)\n      --list-formats    List the supported output formats and exit\n      --only <START-END>\n                        Only output segments overlapping this time range\n      --translate-to <LANG>\n                        Translate the transcript (e.g. to zh) and output the translation\n      --keep-source     With --translate-to, keep each source line above its translation\n      --overlap-translation\n                        With --translate-to, translate segments while transcription is still running\n      --write-empty     Write output files even when no speech was detected\n      --no-clobber      Refuse to overwrite an existing output file\n      --bom             Start output files with a UTF-8 BOM\n      --line-ending <lf|crlf|auto>\n                        Line endings of output files (default: auto, the platform's native style)\n      --start <SEC>     Start transcribing at this offset in seconds\n      --end <SEC>       Stop transcribing at this offset in seconds\n      --resample-quality <fast|medium|best>\n                        Resampling quality for non-16kHz input (default: best)\n      --no-suppress-blank\n                        Allow blank output at the start of a segment\n      --suppress-non-speech\n                        Suppress non-speech tokens (music symbols, punctuation-only output)\n      --keep-context    Use the text already recognized as context for the next window\n      --single-segment  Force one segment per decoding window, for short independent clips\n      --entropy-threshold <H>\n                        Retry at a higher temperature below this entropy (default 2.4)\n      --logprob-threshold <LOGP>\n                        Retry at a higher temperature below this average log probability (default -1.0)\n      --best-of <N>     Candidates sampled per temperature fallback with greedy decoding (default 1)\n      --beam-size <N>   Use beam search with N beams instead of greedy decoding (slower, more accurate)\n      --patience <P>    Beam search patience factor (default -1, disabled)\n      --word-timestamps <token|dtw>\n                        Output per-word timestamps as SRT, or per segment with --format json\n      --detect-language-every <SEC>\n                        Detect the language per chunk and tag each line with it\n      --chunk-overlap <SEC>\n                        Overlap adjacent chunks and drop the text recognized twice\n      --resume          Continue an interrupted chunked transcription from its checkpoint\n      --preview <SEC>   Only transcribe and print the first SEC seconds\n      --detect-only     Only detect and print the spoken language (from the first 30 seconds)\n      --vad [DBFS]      Only transcribe detected speech regions (default threshold -40 dBFS)\n      --language <CODE> Language spoken in the audio (default: auto-detect)\n      --force-language-on-short <CODE>\n                        Use this language for clips shorter than --short-clip-secs\n      --short-clip-secs <SEC>\n                        Length below which language detection is unreliable (default 3)\n      --min-segment-confidence <P>\n                        Drop segments whose mean token probability is below P (0-1)\n      --cleanup [RULE,...]\n                        Clean up segment text: spaces, ellipsis, cjk-punct (default: all when given)\n      --timeout <SEC>   Stop after SEC seconds and keep the segments decoded so far\n      --segment-callback-script <CMD>\n                        Run CMD for every segment as soon as it is decoded\n\nUsage:\n  audio_trans_vox.exe transcribe -i <FILE> [-o <FILE>] [--format <FORMAT>] [--start <SEC>] [--end <SEC>]\n  audio_trans_vox.exe transcribe -i <FILE> --translate-to zh [--keep-source] [--overlap-translation]\n  audio_trans_vox.exe transcribe -i <FILE> --preview 30 [--language <CODE>]\n  audio_trans_vox.exe transcribe --input-dir <DIR> --detect-only\n  audio_trans_vox.exe transcribe --input-dir <DIR> [--recursive] [--append <FILE> | --output-dir <DIR>] [--jsonl <FILE>]\n  audio_trans_vox.exe transcribe -i <FILE> -i <FILE> ... [--append <FILE> | --output-dir <DIR>]")]
     Transcribe {
-        #[arg(short, long, value_name = "FILE", help = "The input audio file to transcribe")]
-        input: String,
-        #[arg(short, long, value_name = "FILE", help = "The output text file to save the transcription result")]
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, required_unless_present_any = ["input_dir", "list_formats"], conflicts_with = "input_dir", help = "The input audio file to transcribe, or an http(s):// URL to download it from; repeat -i to transcribe several files as a batch")]
+        input: Vec<String>,
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, conflicts_with = "input_dir", help = "The output text file to save the transcription result")]
         output: Option<String>,
+        #[arg(long = "input-dir", value_name = "DIR", value_parser = paths::expand_path, help = "Transcribe every audio file in the directory, writing <name>.txt next to each")]
+        input_dir: Option<String>,
+        #[arg(long, value_name = "EXT", value_delimiter = ',', default_values = batch::AUDIO_EXTENSIONS, requires = "input_dir", help = "With --input-dir, only transcribe files with these extensions; non-WAV files can only be decoded when built with the ffmpeg feature")]
+        extensions: Vec<String>,
+        #[arg(long, requires = "input_dir", help = "With --input-dir, also transcribe files in subdirectories; --output-dir mirrors the directory structure")]
+        recursive: bool,
+        #[arg(long, value_name = "FILE", value_parser = paths::expand_path, help = "With --input-dir or several -i, append every transcript, prefixed with its file name, to a single file instead of writing per-file outputs")]
+        append: Option<String>,
+        #[arg(long = "output-dir", value_name = "DIR", value_parser = paths::expand_path, conflicts_with = "append", help = "With --input-dir or several -i, write outputs into this directory instead of next to the inputs")]
+        output_dir: Option<String>,
+        #[arg(long, value_name = "TEMPLATE", value_parser = batch::OutputTemplate::parse, conflicts_with = "append", help = "With --input-dir or several -i, name each output file from this template, e.g. {stem}.{lang}.{ext}; placeholders: {stem}, {ext}, {lang}, {date}")]
+        output_template: Option<batch::OutputTemplate>,
+        #[arg(long, value_name = "FILE", value_parser = paths::expand_path, help = "With --input-dir or several -i, also append one JSON object per input file (path, language, text, segments or error) to this JSON Lines file, flushed after each file")]
+        jsonl: Option<String>,
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Txt, conflicts_with = "translate_to", help = "Output format of the transcript; batch outputs use the matching extension")]
+        format: OutputFormat,
+        #[arg(long, conflicts_with_all = ["input", "input_dir"], help = "List the supported output formats and exit")]
+        list_formats: bool,
+        #[arg(long, value_name = "START-END", conflicts_with = "input_dir", value_parser = transcript::parse_time_range, help = "Only output segments overlapping this range, e.g. 90-120 or 1:30-2:00")]
+        only: Option<(i64, i64)>,
+        #[arg(long, value_name = "LANG", conflicts_with_all = ["input_dir", "word_timestamps"], help = "Translate the transcript into this language (e.g. zh) and output the translation instead")]
+        translate_to: Option<String>,
+        #[arg(long, requires = "translate_to", help = "With --translate-to, output each source line followed by its translation")]
+        keep_source: bool,
+        #[arg(long, requires = "translate_to", help = "With --translate-to, translate segments in a background thread while the audio is still being transcribed")]
+        overlap_translation: bool,
+        #[arg(long, help = "Write the output file even when no speech was detected (skipped by default)")]
+        write_empty: bool,
+        #[arg(long, requires = "detect_language_every", conflicts_with = "input_dir", help = "Continue an interrupted chunked transcription from the <input>.checkpoint.json file written next to the input")]
+        resume: bool,
+        #[arg(long, value_name = "SEC", value_parser = parse_positive_secs, conflicts_with_all = ["input_dir", "output", "start", "end", "resume"], help = "Only transcribe the first SEC seconds and print them, to quickly check the language and model before a full run")]
+        preview: Option<f32>,
+        #[arg(long, conflicts_with_all = ["output", "append", "output_dir", "output_template", "jsonl", "translate_to", "resume", "preview", "language", "detect_language_every", "word_timestamps"], help = "Only detect the spoken language from the first 30 seconds (after --start) and print the most likely languages, without transcribing")]
+        detect_only: bool,
+        #[cfg(feature = "ffmpeg")]
+        #[arg(long, help = "Decode the input with ffmpeg (any format ffmpeg supports) before transcribing")]
+        use_ffmpeg: bool,
+        #[cfg(feature = "websocket")]
+        #[arg(long, value_name = "ADDR", conflicts_with = "detect_only", help = "Serve each segment as a JSON message to WebSocket clients on this address (e.g. 127.0.0.1:9001) as soon as it is decoded, for live captions in a browser")]
+        websocket: Option<String>,
+        #[cfg(feature = "diarize")]
+        #[command(flatten)]
+        diarize_args: DiarizeArgs,
+        #[command(flatten)]
+        model_args: ModelArgs,
+        #[command(flatten)]
+        transcribe_args: TranscribeArgs,
+        #[command(flatten)]
+        write_options: WriteOptions,
     },
-    #[command(about = "Translate text to Chinese", long_about = "Translate the given text file to Chinese and display the result in the terminal.\n\nArguments:\n  -i, --input <FILE>    The input text file to translate\n\nUsage:\n  audio_trans_vox.exe translate -i <FILE>")]
+    #[command(about = "Translate text to Chinese", long_about = "Translate the given text file to Chinese and display the result in the terminal.\n\nArguments:\n  -i, --input <FILE>      The input text file to translate\n      --text <STRING>     Translate this string instead of a file\n  -o, --output <FILE>     The output text file to save the translation result\n      --input-dir <DIR>   Translate every .txt/.srt file in the directory\n      --recursive         With --input-dir, also translate files in subdirectories\n      --output-dir <DIR>  With --input-dir, write outputs into DIR instead of next to the inputs\n      --output-template <TEMPLATE>\n                          With --input-dir, name outputs from a template (default: {stem}.{lang}.{ext})\n      --format <txt|json> Output plain text or JSON chunks with source offsets (default: txt)\n      --stream            Print each sentence as soon as it is translated\n      --no-download       Fail instead of downloading a missing translation model\n      --input-encoding <ENCODING>\n                          Encoding of the input files, e.g. gbk (default: detected)\n      --no-clobber        Refuse to overwrite existing output files\n      --bom               Start output files with a UTF-8 BOM\n      --line-ending <lf|crlf|auto>\n                          Line endings of output files (default: auto)\n\nUsage:\n  audio_trans_vox.exe translate -i <FILE> [-o <FILE>] [--format json]\n  audio_trans_vox.exe translate --text \"<STRING>\" [-o <FILE>]\n  audio_trans_vox.exe translate --input-dir <DIR> [--recursive] [--output-dir <DIR>]")]
     Translate {
-        #[arg(short = 'i', long = "input", value_name = "FILE", help = "The input text file to translate")]
+        #[arg(short = 'i', long = "input", value_name = "FILE", value_parser = paths::expand_path, required_unless_present_any = ["input_dir", "text"], conflicts_with_all = ["input_dir", "text"], help = "The input text file to translate")]
+        input: Option<String>,
+        #[arg(long, value_name = "STRING", conflicts_with = "input_dir", help = "Translate this string directly instead of reading a file")]
+        text: Option<String>,
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, conflicts_with = "input_dir", help = "The output text file to save the translation result")]
+        output: Option<String>,
+        #[arg(long = "input-dir", value_name = "DIR", value_parser = paths::expand_path, help = "Translate every .txt/.srt file in the directory, writing <name>.zh.txt next to each")]
+        input_dir: Option<String>,
+        #[arg(long = "output-dir", value_name = "DIR", value_parser = paths::expand_path, requires = "input_dir", help = "With --input-dir, write outputs into this directory instead of next to the inputs")]
+        output_dir: Option<String>,
+        #[arg(long, value_name = "TEMPLATE", value_parser = batch::OutputTemplate::parse, requires = "input_dir", help = "With --input-dir, name each output file from this template (default {stem}.{lang}.{ext}); placeholders: {stem}, {ext}, {lang}, {date}")]
+        output_template: Option<batch::OutputTemplate>,
+        #[arg(long, requires = "input_dir", help = "With --input-dir, also translate files in subdirectories; --output-dir mirrors the directory structure")]
+        recursive: bool,
+        #[arg(long, value_enum, default_value_t = TranslationFormat::Txt, conflicts_with = "input_dir", help = "Output format; json lists every translated chunk with its source text and character offsets in the input")]
+        format: TranslationFormat,
+        #[arg(long, conflicts_with_all = ["input_dir", "output", "format"], help = "Print each sentence to stdout as soon as it is translated instead of line by line")]
+        stream: bool,
+        #[arg(long, help = "Fail immediately when the translation model is missing instead of downloading it")]
+        no_download: bool,
+        #[arg(long, value_name = "ENCODING", value_parser = text_encoding::parse_encoding, conflicts_with = "text", help = "Encoding of the input files, e.g. gbk or utf-16le (default: detected; UTF-8 when valid)")]
+        input_encoding: Option<&'static encoding_rs::Encoding>,
+        #[command(flatten)]
+        write_options: WriteOptions,
+    },
+    #[command(about = "Capture, transcribe and translate in one go", long_about = "Capture audio until Ctrl+C, then transcribe the recording and translate the transcript to Chinese.\n\nArguments:\n      --json       Print a JSON manifest of the result instead of plain text\n      --segments   Include per-segment aligned source and translated text in the manifest\n\nUsage:\n  audio_trans_vox.exe pipeline [--json [--segments]]")]
+    Pipeline {
+        #[arg(long, help = "Print a JSON manifest (audio path, duration, language, transcript, translation) to stdout")]
+        json: bool,
+        #[arg(long, requires = "json", help = "Translate segment by segment and include start_ms/end_ms/source_text/translated_text entries in the manifest")]
+        segments: bool,
+        #[cfg(feature = "diarize")]
+        #[command(flatten)]
+        diarize_args: DiarizeArgs,
+        #[command(flatten)]
+        model_args: ModelArgs,
+    },
+    #[command(about = "Keep the last seconds of audio in memory and transcribe them on demand", long_about = "Capture the default output continuously into an in-memory ring that only holds the most recent audio, and transcribe that audio each time Enter is pressed, e.g. to check what was just said without transcribing everything.\n\nArguments:\n      --keep-seconds <SEC> How much recent audio to keep and transcribe (default 30)\n      --device <NAME>      Loop back this output device instead of the default one\n\nUsage:\n  audio_trans_vox.exe listen [--keep-seconds 30] [--language <CODE>]")]
+    Listen {
+        #[arg(long, value_name = "SEC", default_value_t = 30.0, value_parser = parse_positive_secs, help = "Seconds of recent audio kept in memory and transcribed when Enter is pressed")]
+        keep_seconds: f32,
+        #[arg(long, value_name = "NAME", help = "Loop back this output device (render endpoint) instead of the default one")]
+        device: Option<String>,
+        #[command(flatten)]
+        model_args: ModelArgs,
+        #[command(flatten)]
+        transcribe_args: TranscribeArgs,
+    },
+    #[command(about = "Preview the waveform of a WAV file", long_about = "Print a downsampled ASCII amplitude envelope of a WAV file, to check that a recording actually contains signal.\n\nArguments:\n  -i, --input <FILE>   The WAV file to preview\n      --width <COLS>   Number of columns (default 80)\n      --height <ROWS>  Number of rows (default 10)\n\nUsage:\n  audio_trans_vox.exe waveform -i <FILE> [--width <COLS>] [--height <ROWS>]")]
+    Waveform {
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, help = "The WAV file to preview")]
+        input: String,
+        #[arg(long, value_name = "COLS", default_value_t = 80, value_parser = clap::value_parser!(u16).range(1..), help = "Number of columns in the preview")]
+        width: u16,
+        #[arg(long, value_name = "ROWS", default_value_t = 10, value_parser = clap::value_parser!(u16).range(2..), help = "Number of rows in the preview")]
+        height: u16,
+    },
+    #[command(about = "Play the audio exactly as Whisper receives it", long_about = "Decode a file into the 16kHz mono buffer that transcribe feeds to Whisper and play it through the default output device, to hear whether a failed transcription is caused by bad decoding.\n\nArguments:\n  -i, --input <FILE>   The audio file to play\n      --start <SEC>    Start playing at this offset in seconds\n      --end <SEC>      Stop playing at this offset in seconds\n      --resample-quality <fast|medium|best>\n                       Resampling quality for non-16kHz input, as in transcribe (default: best)\n\nUsage:\n  audio_trans_vox.exe play -i <FILE> [--start <SEC>] [--end <SEC>]")]
+    Play {
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, help = "The audio file to decode and play; non-WAV files need the ffmpeg feature")]
         input: String,
+        #[arg(long, value_name = "SEC", help = "Start playing at this offset in seconds")]
+        start: Option<f32>,
+        #[arg(long, value_name = "SEC", help = "Stop playing at this offset in seconds")]
+        end: Option<f32>,
+        #[arg(long, value_enum, default_value_t = ResampleQuality::Best, help = "Resampling quality used when the input is not 16kHz, as in transcribe")]
+        resample_quality: ResampleQuality,
+    },
+    #[command(about = "Show the format and levels of a WAV file", long_about = "Decode a WAV file and print its format, duration, peak and RMS levels and the share of silence, to judge whether a recording is usable before transcribing it.\n\nArguments:\n  -i, --input <FILE>   The WAV file to inspect\n\nUsage:\n  audio_trans_vox.exe info -i <FILE>")]
+    Info {
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, help = "The WAV file to inspect")]
+        input: String,
+    },
+    #[command(about = "Check that a model file loads and print its parameters", long_about = "Load a Whisper ggml model (.bin) or a Marian translation model (.safetensors), print its basic parameters and exit non-zero if it is corrupt or in the wrong format.\n\nArguments:\n      --path <FILE>    The model file to validate\n      --sha256 <HEX>   Also verify the file against this SHA-256 checksum\n\nUsage:\n  audio_trans_vox.exe validate-model --path models/ggml-base.bin [--sha256 <HEX>]")]
+    ValidateModel {
+        #[arg(long, value_name = "FILE", value_parser = paths::expand_path, help = "Whisper ggml model (.bin) or Marian translation model (.safetensors) to validate")]
+        path: String,
+        #[arg(long, value_name = "HEX", help = "Also verify the file against this SHA-256 checksum")]
+        sha256: Option<String>,
+    },
+    #[command(about = "Check the environment and report problems", long_about = "Check the audio device, the GPU backend, the Whisper and translation models, write access to the model directory and whether the download host is reachable. Prints one PASS/WARN/FAIL line per check and exits non-zero if any check fails.\n\nArguments:\n      --model <NAME>            Whisper model to check (default: base)\n      --model-registry <FILE>   JSON file mapping model names to {path, url, sha256}\n      --require-gpu             Treat a missing GPU backend as a failure\n\nUsage:\n  audio_trans_vox.exe doctor [--model <NAME>]")]
+    Doctor {
+        #[command(flatten)]
+        model_args: ModelArgs,
+    },
+    #[command(about = "List supported languages", long_about = "List the language codes whisper can transcribe and the available translation pairs.\n\nUsage:\n  audio_trans_vox.exe languages")]
+    Languages,
+    #[command(hide = true, about = "Benchmark transcription speed across models and devices")]
+    Bench {
+        #[arg(short, long, value_name = "FILE", value_parser = paths::expand_path, help = "A short WAV sample to transcribe repeatedly")]
+        input: String,
+        #[arg(short = 'n', long, default_value_t = 3, help = "Number of runs per configuration")]
+        runs: usize,
+        #[arg(long, value_delimiter = ',', default_value = "base", help = "Comma-separated model names, e.g. tiny,base,small")]
+        models: Vec<String>,
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "gpu,cpu", help = "Comma-separated devices to test")]
+        devices: Vec<BenchDevice>,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
+    download_model::set_config(cli.download.clone());
+    disk_space::set_min_free(cli.min_free_space);
+    progress::set_json(cli.progress_json);
+    progress::set_raw(cli.raw);
+    progress::set_verbose(cli.verbose);
+    if cli.print_config {
+        println!("{}", serde_json::to_string_pretty(&effective_config(&cli)).expect("Failed to serialize configuration"));
+        return;
+    }
 
     match &cli.command {
-        Commands::Capture => {
-            let output = format!("audio_{}.wav", chrono::Local::now().format("%Y%m%d%H%M%S"));
-            println!("Capturing audio to {}", output);
+        Commands::Capture { output, archive_dir, device, list_devices, format, match_file, sample_rate, channels, force_mono, force_stereo, channel, downmix, loudnorm, agc, max_duration, buffer_size } => {
+            if *list_devices {
+                println!("Output devices (--device):");
+                for name in audio_capture::output_device_names() {
+                    println!("  {}", name);
+                }
+                return;
+            }
+            let to_stdout = output.as_deref() == Some(STDOUT_PATH);
+            if to_stdout && loudnorm.is_some() {
+                eprintln!("--loudnorm rewrites the recording in place and cannot be used with -o -");
+                std::process::exit(1);
+            }
+            if *format == CaptureFormat::Raw && loudnorm.is_some() {
+                eprintln!("--loudnorm only supports WAV recordings and cannot be used with --format raw");
+                std::process::exit(1);
+            }
+            if to_stdout && std::io::stdout().is_terminal() {
+                eprintln!("Refusing to write WAV data to a terminal; pipe or redirect stdout when using -o -");
+                std::process::exit(1);
+            }
+            // 录到标准输出时 stdout 上只能有音频数据
+            if to_stdout {
+                progress::set_raw(true);
+            }
+            let interrupted = install_interrupt_handler();
+            let file_name = match (output, archive_dir) {
+                (Some(output), _) => output.clone(),
+                (None, Some(dir)) => archived_capture_name(Path::new(dir), *format).unwrap_or_else(|e| {
+                    eprintln!("Failed to create the archive directory under {}: {}", dir, e);
+                    std::process::exit(1);
+                }),
+                (None, None) => timestamped_capture_name(*format),
+            };
+            // --match：采样率、通道数和位深都取自参考文件
+            let mut audio_capture = match match_file {
+                Some(reference) => {
+                    let spec = match hound::WavReader::open(reference) {
+                        Ok(reader) => reader.spec(),
+                        Err(e) => {
+                            eprintln!("Failed to read reference file {}: {}", reference, e);
+                            std::process::exit(1);
+                        }
+                    };
+                    if loudnorm.is_some() && (spec.bits_per_sample != 16 || spec.sample_format != hound::SampleFormat::Int) {
+                        eprintln!("--loudnorm only supports 16-bit recordings, but {} is {}-bit", reference, spec.bits_per_sample);
+                        std::process::exit(1);
+                    }
+                    let audio_capture = AudioCapture::from_wav_spec(file_name, spec).unwrap_or_else(|e| {
+                        eprintln!("Cannot match {}: {}", reference, e);
+                        std::process::exit(1);
+                    });
+                    status!(
+                        "Matching {}: {} Hz, {} channel(s), {}-bit {}",
+                        reference,
+                        spec.sample_rate,
+                        spec.channels,
+                        spec.bits_per_sample,
+                        if spec.sample_format == hound::SampleFormat::Float { "float" } else { "PCM" }
+                    );
+                    audio_capture
+                }
+                None => {
+                    let mut audio_capture = AudioCapture::new(file_name);
+                    audio_capture.set_format(*format);
+                    audio_capture.set_preferred_format(*sample_rate, *channels);
+                    audio_capture.set_forced_channels(match (force_mono, force_stereo) {
+                        (true, _) => Some(1),
+                        (_, true) => Some(2),
+                        _ => None,
+                    });
+                    audio_capture.set_channel_selection(channel.as_deref());
+                    audio_capture
+                }
+            };
+            audio_capture.set_device(device.clone());
+            audio_capture.set_agc(*agc);
+            audio_capture.set_downmix(*downmix);
+            audio_capture.set_buffer_size(*buffer_size);
+            record_until_interrupted(&mut audio_capture, &interrupted, max_duration.map(Duration::from_secs));
+
+            if let Some(target) = loudnorm {
+                match loudness::normalize_wav(audio_capture.file_name(), *target) {
+                    Ok(Some((measured, gain_db))) => status!(
+                        "Loudness normalized: measured {:.1} LUFS, applied {:+.1} dB (target {:.1} LUFS)",
+                        measured, gain_db, target
+                    ),
+                    Ok(None) => status!("Recording is too short or silent, skipping loudness normalization."),
+                    Err(e) => eprintln!("Loudness normalization failed: {}", e),
+                }
+            }
+        }
+        Commands::Transcribe {
+            input,
+            output,
+            input_dir,
+            extensions,
+            recursive,
+            append,
+            output_dir,
+            output_template,
+            jsonl,
+            format,
+            list_formats,
+            only,
+            translate_to,
+            keep_source,
+            overlap_translation,
+            write_empty,
+            resume,
+            preview,
+            detect_only,
+            #[cfg(feature = "ffmpeg")]
+            use_ffmpeg,
+            #[cfg(feature = "websocket")]
+            websocket,
+            #[cfg(feature = "diarize")]
+            diarize_args,
+            model_args,
+            transcribe_args,
+            write_options,
+        } => {
+            if *list_formats {
+                println!("Output formats (--format):");
+                for format in OutputFormat::value_variants() {
+                    let name = format.to_possible_value().expect("formats are never skipped");
+                    println!("  {:<6} {}", name.get_name(), format.description());
+                }
+                return;
+            }
+            // 多个 -i 与 --input-dir 一样按批处理：只接受批处理的输出选项，不接受只针对单个文件的选项
+            let batch_inputs = input.len() > 1;
+            let batch_only = [("--append", append.is_some()), ("--output-dir", output_dir.is_some()), ("--output-template", output_template.is_some()), ("--jsonl", jsonl.is_some())];
+            let single_only = [("--output", output.is_some()), ("--only", only.is_some()), ("--translate-to", translate_to.is_some()), ("--resume", *resume), ("--preview", preview.is_some())];
+            if input_dir.is_none() && !batch_inputs {
+                if let Some((flag, _)) = batch_only.iter().find(|(_, given)| *given) {
+                    eprintln!("{} needs --input-dir or more than one -i", flag);
+                    std::process::exit(1);
+                }
+            }
+            if batch_inputs {
+                if let Some((flag, _)) = single_only.iter().find(|(_, given)| *given) {
+                    eprintln!("{} only works with a single -i; use --append or --output-dir for several inputs", flag);
+                    std::process::exit(1);
+                }
+                if let Some(url) = input.iter().find(|i| download_model::is_url(i)) {
+                    eprintln!("URL inputs such as {} only work with a single -i", url);
+                    std::process::exit(1);
+                }
+            }
+            // 词级时间戳只能以逐词 SRT 或 JSON 中的 words 数组输出
+            if transcribe_args.word_timestamps.is_some() && matches!(format, OutputFormat::Vtt | OutputFormat::Csv) {
+                eprintln!("--word-timestamps only works with --format txt, srt (one cue per word) or json (a words array per segment)");
+                std::process::exit(1);
+            }
+            let interrupted = install_interrupt_handler();
+            let mut options = transcribe_args.options();
+            // Ctrl+C 直接中止正在进行的转录，而不是等整个文件跑完
+            options.abort = Some(interrupted.clone());
+            // 回调命令在后台线程中运行，丢弃 segment_hook 时等待已入队的段落处理完
+            let segment_hook = transcribe_args.segment_callback_script.as_deref().map(SegmentHook::spawn);
+            options.segment_hooks.extend(segment_hook.as_ref().map(SegmentHook::sender));
+            // --websocket：同样逐段推送，丢弃 caption_server 时发送结束消息并断开客户端
+            #[cfg(feature = "websocket")]
+            let caption_server = websocket.as_deref().map(|addr| match caption_server::CaptionServer::bind(addr) {
+                Ok(server) => {
+                    status!("Serving live captions on ws://{}", server.local_addr());
+                    server
+                }
+                Err(e) => {
+                    eprintln!("Failed to listen on {}: {}", addr, e);
+                    std::process::exit(1);
+                }
+            });
+            #[cfg(feature = "websocket")]
+            options.segment_hooks.extend(caption_server.as_ref().map(caption_server::CaptionServer::sender));
+            // --preview：沿用 --start/--end 的截取逻辑，只转写开头的一段
+            if let Some(secs) = preview {
+                options.start_secs = Some(0.0);
+                options.end_secs = Some(*secs);
+            }
 
-            let mut audio_capture = AudioCapture::new(output);
+            let batch = match input_dir {
+                Some(dir) => {
+                    let (files, skipped) = batch::collect_input_files(Path::new(dir), extensions, *recursive).expect("Failed to read input directory");
+                    if !skipped.is_empty() {
+                        status!("Skipping {} file(s) not matching --extensions {}", skipped.len(), extensions.join(","));
+                        if cli.verbose {
+                            for path in &skipped {
+                                eprintln!("  skipped {}", path.display());
+                            }
+                        }
+                    }
+                    status!("Found {} file(s) to transcribe in {}", files.len(), dir);
+                    Some((PathBuf::from(dir), files))
+                }
+                None if batch_inputs => {
+                    let files: Vec<PathBuf> = input.iter().map(PathBuf::from).collect();
+                    status!("Transcribing {} input file(s)", files.len());
+                    Some((batch::common_parent(&files), files))
+                }
+                None => None,
+            };
+            if let Some((dir, files)) = batch {
+                let mut whisper = load_whisper(model_args, options.word_timestamps);
+                // --detect-only：每个文件输出一行 `路径<TAB>语言<TAB>概率`，方便按语言分拣
+                if *detect_only {
+                    for path in &files {
+                        if interrupted.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        match with_wav(path, |input| detect_wav_language(&mut whisper, input, &options)) {
+                            Ok(languages) => {
+                                let (code, probability) = &languages[0];
+                                println!("{}\t{}\t{:.3}", path.display(), code, probability);
+                            }
+                            Err(e) => eprintln!("Failed to detect the language of {}: {:#}", path.display(), e),
+                        }
+                    }
+                    exit_if_interrupted(&interrupted);
+                    return;
+                }
+                transcribe_dir(
+                    &mut whisper,
+                    &dir,
+                    &files,
+                    output_dir.as_deref().map(Path::new),
+                    output_template.as_ref(),
+                    jsonl.as_deref(),
+                    &options,
+                    *format,
+                    append.as_deref(),
+                    *write_empty,
+                    write_options,
+                    &interrupted,
+                );
+                drop(segment_hook);
+                #[cfg(feature = "websocket")]
+                drop(caption_server);
+                exit_if_interrupted(&interrupted);
+                return;
+            }
+
+            let input = input.first().map(String::as_str).expect("--input is required without --input-dir");
+            if let Some(output_file) = output {
+                write_options.check_writable(output_file).expect("Cannot write output file");
+            }
+            // 在开始转写前确认有对应的翻译模型，避免转写完才发现无法翻译
+            if let Some(target) = translate_to {
+                if MarianModel::find("en", target).is_none() {
+                    eprintln!("No translation model for en -> {}; run `languages` to list the available pairs", target);
+                    std::process::exit(1);
+                }
+            }
+            // 分块转写本地文件时在旁边写检查点，中断后可用 --resume 继续；URL 每次都下载到新的临时文件，无法续转
+            if download_model::is_url(input) {
+                if *resume {
+                    eprintln!("--resume needs a local input file, not a URL");
+                    std::process::exit(1);
+                }
+            } else if options.language_chunk_secs.is_some() {
+                options.checkpoint = Some(checkpoint::sidecar_path(input));
+                options.resume = *resume;
+            }
+            // 输入为 URL 时先下载到临时文件，转写完成后自动删除
+            let remote = download_model::is_url(input).then(|| download_model::download_to_temp(input));
+            let input = match &remote {
+                Some(temp) => temp.path().to_str().expect("Temp path is not valid UTF-8"),
+                None => input,
+            };
+            // 下载的文件与批处理中的本地文件一样按扩展名选择解码方式，见 [`with_wav`]
+            let remote_needs_ffmpeg = remote.is_some() && !is_wav_path(Path::new(input));
+            #[cfg(not(feature = "ffmpeg"))]
+            if remote_needs_ffmpeg {
+                eprintln!("only WAV files can be decoded; rebuild with --features ffmpeg to transcribe {}", input);
+                std::process::exit(1);
+            }
+            // --use-ffmpeg：先用 ffmpeg 解码为 16kHz 单声道 WAV，ffmpeg 不可用时退回内置解码；下载的非 WAV 文件必须经 ffmpeg 解码
+            #[cfg(feature = "ffmpeg")]
+            let decoded = (*use_ffmpeg || remote_needs_ffmpeg)
+                .then(|| {
+                    if !remote_needs_ffmpeg && !ffmpeg::is_available() {
+                        eprintln!("ffmpeg not found on PATH, falling back to the built-in WAV decoder");
+                        return None;
+                    }
+                    match ffmpeg::decode_to_wav(Path::new(input)) {
+                        Ok(temp) => Some(temp),
+                        Err(e) => {
+                            eprintln!("{:#}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                })
+                .flatten();
+            #[cfg(feature = "ffmpeg")]
+            let input = match &decoded {
+                Some(temp) => temp.path().to_str().expect("Temp path is not valid UTF-8"),
+                None => input,
+            };
+            if *detect_only {
+                let mut whisper = load_whisper(model_args, None);
+                match detect_wav_language(&mut whisper, input, &options) {
+                    Ok(languages) => {
+                        for (code, probability) in languages.iter().take(DETECT_ONLY_TOP) {
+                            println!("{:<4} {:>5.1}%", code, probability * 100.0);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Language detection failed: {:#}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            match preview {
+                Some(secs) => status!("Previewing the first {}s of {}", secs, input),
+                None => status!("Transcribing audio file {}", input),
+            }
+            progress::set_current_file(Some(input));
+            // --overlap-translation：先加载翻译模型，转写过程中由后台线程逐段翻译
+            let translation_worker = match translate_to {
+                Some(target) if *overlap_translation => {
+                    let translator = load_translator(target, !cli.quiet, &cli.translation_cache, cli.replace.as_deref(), !model_args.no_download);
+                    let worker = TranslationWorker::spawn(translator, options.cleanup.clone());
+                    options.segment_hooks.push(worker.sender());
+                    Some(worker)
+                }
+                _ => None,
+            };
+            let mut whisper = load_whisper(model_args, options.word_timestamps);
+            let result = whisper.transcribe_segments(input, &options);
+            drop(segment_hook);
+            #[cfg(feature = "websocket")]
+            drop(caption_server);
+            // 转写结束后等后台线程翻译完队列中剩余的段落
+            let prefetched = translation_worker.map(TranslationWorker::finish);
+            let (segments, partial) = match result {
+                Ok(segments) => (segments, false),
+                // 中止或中途失败时保留已完成的段落，照常输出并标记为不完整
+                Err(e) => match e.downcast_ref::<PartialTranscript>() {
+                    Some(partial) if !partial.segments.is_empty() => {
+                        if !interrupted.load(Ordering::SeqCst) {
+                            eprintln!("Transcription failed: {:#}", e);
+                        }
+                        (partial.segments.clone(), true)
+                    }
+                    _ => {
+                        eprintln!("Transcription failed: {:#}", e);
+                        #[cfg(feature = "ffmpeg")]
+                        drop(decoded);
+                        drop(remote);
+                        std::process::exit(if interrupted.load(Ordering::SeqCst) { EXIT_INTERRUPTED } else { 1 });
+                    }
+                },
+            };
+            #[cfg(feature = "diarize")]
+            let segments = {
+                let mut segments = segments;
+                diarize_args.apply(&whisper, input, options.resample_quality, &mut segments);
+                segments
+            };
+            // --only：在已有结果上按时间范围筛选，无需重新转写
+            let segments = match only {
+                Some((start_ms, end_ms)) => transcript::segments_in_range(&segments, *start_ms, *end_ms),
+                None => segments,
+            };
+            let segments = filter_segments(segments, &options);
+            let result = match translate_to {
+                // --translate-to：逐段翻译，直接输出译文，不产生中间文件
+                Some(target) => {
+                    let (mut translator, mut translations) = prefetched.unwrap_or_else(|| {
+                        let translator = load_translator(target, !cli.quiet, &cli.translation_cache, cli.replace.as_deref(), !model_args.no_download);
+                        (translator, HashMap::new())
+                    });
+                    let sources: Vec<&str> = segments.iter().map(|s| s.text.trim()).collect();
+                    // 只翻译转写期间还没翻译到的段落，最后仍按段落顺序组装译文
+                    let mut missing: Vec<&str> = sources.iter().copied().filter(|s| !translations.contains_key(*s)).collect();
+                    missing.dedup();
+                    let spinner = Spinner::start("Translating...", !cli.quiet);
+                    let translated = translator
+                        .translate_batch(&missing, |current, total| {
+                            spinner.set_message(format!("Translating segment {}/{}", current, total))
+                        })
+                        .expect("Translation failed");
+                    spinner.stop();
+                    translations.extend(missing.iter().map(|s| s.to_string()).zip(translated));
+                    let translated: Vec<String> = sources.iter().map(|s| translations[*s].clone()).collect();
+                    transcript::to_translated_text(&sources, &translated, *keep_source)
+                }
+                None => render_transcript(&segments, &options, *format),
+            };
+            let label = if translate_to.is_some() { "Translation result" } else { "Transcription result" };
+            status!("{}:", label);
+            println!("{}", result);
+            if options.language_chunk_secs.is_some() {
+                let languages = transcript::languages(&segments);
+                if !languages.is_empty() {
+                    status!("Languages detected: {}", languages.join(", "));
+                }
+            }
+
+            if let Some(output_file) = output {
+                if segments.is_empty() && !*write_empty {
+                    status!("No speech detected, not writing {} (use --write-empty to write it anyway)", output_file);
+                } else {
+                    write_output(output_file, &result, write_options).expect("Failed to write to output file");
+                    status!("{} saved to {}", label, output_file);
+                }
+            }
+            if partial {
+                let end_ms = segments.last().map(|s| s.end_ms).unwrap_or(0);
+                eprintln!(
+                    "Warning: the transcript is partial, covering only the first {:.1}s of audio ({} segment(s)).",
+                    end_ms as f64 / 1000.0,
+                    segments.len()
+                );
+            }
+            // process::exit 不会运行析构函数，先清理临时文件
+            #[cfg(feature = "ffmpeg")]
+            drop(decoded);
+            drop(remote);
+            exit_if_interrupted(&interrupted);
+            if partial {
+                std::process::exit(1);
+            }
+        }
+        Commands::Translate {
+            input,
+            text,
+            output,
+            input_dir,
+            output_dir,
+            output_template,
+            recursive,
+            format,
+            stream,
+            no_download,
+            input_encoding,
+            write_options,
+        } => {
+            if let Some(output_file) = output {
+                write_options.check_writable(output_file).expect("Cannot write output file");
+            }
+            let mut translator = load_translator("zh", !cli.quiet, &cli.translation_cache, cli.replace.as_deref(), !*no_download);
+
+            let interrupted = install_interrupt_handler();
+
+            if let Some(dir) = input_dir {
+                translate_dir(
+                    &mut translator,
+                    Path::new(dir),
+                    output_dir.as_deref().map(Path::new),
+                    output_template.as_ref(),
+                    *recursive,
+                    *input_encoding,
+                    write_options,
+                    &interrupted,
+                );
+                exit_if_interrupted(&interrupted);
+                return;
+            }
+
+            // --format json：输出每个片段的原文、译文及其在输入中的字符偏移
+            if *format == TranslationFormat::Json {
+                let reader = open_translation_input(text.as_deref(), input.as_deref(), *input_encoding);
+                let spinner = Spinner::start("Translating...", !cli.quiet);
+                let chunks = translator
+                    .translate_aligned(reader, |line| spinner.set_message(format!("Translated {} line(s)", line)))
+                    .expect("Translation failed");
+                spinner.stop();
+                let json = serde_json::to_string_pretty(&chunks).expect("Failed to serialize translation");
+                match output {
+                    Some(output_file) => {
+                        write_output(output_file, &format!("{}\n", json), write_options).expect("Failed to write to output file");
+                        status!("Translation result saved to {}", output_file);
+                    }
+                    None => println!("{}", json),
+                }
+                exit_if_interrupted(&interrupted);
+                return;
+            }
+
+            // --stream：每个片段翻译完就立即打印，不等整行完成
+            if *stream {
+                let reader = open_translation_input(text.as_deref(), input.as_deref(), *input_encoding);
+                status!("Translation result:");
+                for (index, line) in reader.lines().enumerate() {
+                    let line = line.expect("Failed to read input");
+                    let line = if index == 0 { line.trim_start_matches('\u{feff}') } else { &line };
+                    let (timestamp, chunks) = translate::line_chunks(line);
+                    translate::write_streamed_line(std::io::stdout().lock(), timestamp, translator.translate_stream(chunks))
+                        .expect("Translation failed");
+                    if interrupted.load(Ordering::SeqCst) {
+                        break;
+                    }
+                }
+                exit_if_interrupted(&interrupted);
+                return;
+            }
+
+            if let Some(text) = text {
+                let result = translator.translate(text).expect("Translation failed");
+                match output {
+                    Some(output_file) => {
+                        write_output(output_file, &format!("{}\n", result), write_options).expect("Failed to write to output file");
+                        status!("Translation result saved to {}", output_file);
+                    }
+                    None => {
+                        status!("Translation result:");
+                        println!("{}", result);
+                    }
+                }
+                return;
+            }
+
+            let input = input.as_deref().expect("--input is required without --input-dir");
+            // 边读边解码为 UTF-8，逐行翻译、逐行写出
+            let reader = open_translation_input(None, Some(input), *input_encoding);
+            match output {
+                Some(output_file) => {
+                    let mut file = AtomicFile::create(output_file, write_options).expect("Failed to create output file");
+                    let spinner = Spinner::start("Translating...", !cli.quiet);
+                    translator
+                        .translate_reader(reader, &mut file, |line| spinner.set_message(format!("Translated {} line(s)", line)))
+                        .expect("Translation failed");
+                    spinner.stop();
+                    file.commit().expect("Failed to write to output file");
+                    status!("Translation result saved to {}", output_file);
+                }
+                None => {
+                    status!("Translation result:");
+                    translator
+                        .translate_reader(reader, std::io::stdout().lock(), |_| {})
+                        .expect("Translation failed");
+                }
+            }
+            exit_if_interrupted(&interrupted);
+        }
+        Commands::Listen { keep_seconds, device, model_args, transcribe_args } => {
+            let interrupted = install_interrupt_handler();
+            let mut options = transcribe_args.options();
+            options.abort = Some(interrupted.clone());
+            // 先加载模型，按下回车后只需转写
+            let mut whisper = load_whisper(model_args, options.word_timestamps);
+            let rolling = RollingBuffer::new(*keep_seconds, audio_transcribe::WHISPER_SAMPLE_RATE);
+            // 采集直接重采样到 16kHz，环形缓冲区中的采样可以原样交给 Whisper；不写出文件
+            let mut audio_capture = AudioCapture::in_memory();
+            audio_capture.set_device(device.clone());
+            audio_capture.set_preferred_format(Some(audio_transcribe::WHISPER_SAMPLE_RATE), None);
+            rolling.attach(&mut audio_capture);
             audio_capture.start();
-            println!("Audio capture started. Press Ctrl+C to stop.");
-            let running = Arc::new(AtomicBool::new(true));
-            let r = running.clone();
-            ctrlc::set_handler(move || {
-                r.store(false, Ordering::SeqCst);
-            }).expect("Error setting Ctrl-C handler");
 
-            while running.load(Ordering::SeqCst) {
-                std::thread::sleep(std::time::Duration::from_secs(1));
+            // 标准输入在单独的线程中读取，主线程才能同时响应 Ctrl+C
+            let (enter, pressed) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                for _ in std::io::stdin().lock().lines() {
+                    if enter.send(()).is_err() {
+                        break;
+                    }
+                }
+            });
+            status!("Listening, keeping the last {}s in memory. Press Enter to transcribe them, Ctrl+C to quit.", keep_seconds);
+            while !interrupted.load(Ordering::SeqCst) {
+                match pressed.recv_timeout(Duration::from_millis(200)) {
+                    Ok(()) => {}
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
+                    // 标准输入已关闭（例如重定向自文件）
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+                let samples = rolling.snapshot(None);
+                if samples.is_empty() {
+                    status!("Nothing captured yet.");
+                    continue;
+                }
+                status!("Transcribing the last {:.1}s...", samples.len() as f32 / rolling.sample_rate() as f32);
+                match whisper.transcribe_samples_with(&samples, rolling.sample_rate(), &options) {
+                    Ok(segments) => println!("{}", render_transcript(&filter_segments(segments, &options), &options, OutputFormat::Txt)),
+                    // Ctrl+C 中止了这次转写，直接退出
+                    Err(_) if interrupted.load(Ordering::SeqCst) => {}
+                    Err(e) => eprintln!("Transcription failed: {:#}", e),
+                }
             }
             audio_capture.stop();
-            println!("Audio capture stopped.");
-        }
-        Commands::Transcribe { input, output } => {
-            let model_path = "models/ggml-base.bin";
-            let download_url = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin";
-            ensure_model_exists(model_path, download_url);
-            println!("Transcribing audio file {}", input);
-            let mut whisper = Whisper::new("models/ggml-base.bin");
-            let result = whisper
-               .transcribe_file(input)
-               .expect("Transcription failed");
-            println!("Transcription result:\n{}", result);
+            exit_if_interrupted(&interrupted);
+        }
+        Commands::Waveform { input, width, height } => {
+            let (peaks, duration) = match waveform::bucket_peaks(input, *width as usize) {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {:#}", input, e);
+                    std::process::exit(1);
+                }
+            };
+            let peak = peaks.iter().copied().fold(0.0f32, f32::max);
+            let peak_dbfs = 20.0 * (peak as f64).log10();
+            println!("{}: {:.2}s, peak {:.1} dBFS", input, duration, peak_dbfs);
+            print!("{}", waveform::render(&peaks, *height as usize));
+            if peak_dbfs < -60.0 {
+                println!("Warning: the file looks silent.");
+            }
+        }
+        Commands::Play { input, start, end, resample_quality } => {
+            // 与转写走同一条解码路径：非 WAV 先经 ffmpeg 解码，再读取并重采样为 16kHz 单声道
+            let samples = with_wav(Path::new(input), |path| audio_transcribe::load_mono_16k(path, *resample_quality)).unwrap_or_else(|e| {
+                eprintln!("Failed to decode {}: {:#}", input, e);
+                std::process::exit(1);
+            });
+            let rate = audio_transcribe::WHISPER_SAMPLE_RATE as f32;
+            let from = start.map(|s| (s * rate) as usize).unwrap_or(0).min(samples.len());
+            let to = end.map(|s| (s * rate) as usize).unwrap_or(samples.len()).clamp(from, samples.len());
+            if from == to {
+                eprintln!("No audio to play in {}", input);
+                std::process::exit(1);
+            }
+            let interrupted = install_interrupt_handler();
+            status!("Playing {:.1}s of 16 kHz mono audio decoded from {} (Ctrl+C to stop)", (to - from) as f32 / rate, input);
+            if let Err(e) = playback::play(&samples[from..to], audio_transcribe::WHISPER_SAMPLE_RATE, &interrupted) {
+                eprintln!("Playback failed: {:#}", e);
+                std::process::exit(1);
+            }
+            exit_if_interrupted(&interrupted);
+        }
+        Commands::Info { input } => {
+            let wav = match waveform::decode(input) {
+                Ok(wav) => wav,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {:#}", input, e);
+                    std::process::exit(1);
+                }
+            };
+            let levels = levels::measure(&wav.samples, wav.channels, wav.sample_rate);
+            println!("{}", input);
+            println!("  Format:    {}, {} Hz, {} channel(s)", wav.format, wav.sample_rate, wav.channels);
+            println!("  Duration:  {:.2}s", wav.duration());
+            println!("  Peak:      {:.1} dBFS", levels.peak_dbfs);
+            println!("  RMS:       {:.1} dBFS", levels.rms_dbfs);
+            println!("  Silence:   {:.1}% (20ms frames below {} dBFS)", levels.silence_percent, levels::SILENCE_THRESHOLD_DBFS);
+            if levels.peak_dbfs >= -0.1 {
+                println!("Warning: the peak reaches full scale, the recording may be clipped.");
+            }
+            if levels.silence_percent >= 95.0 {
+                println!("Warning: the file is almost entirely silent.");
+            }
+        }
+        Commands::ValidateModel { path, sha256 } => {
+            if let Err(e) = validate_model(path, sha256.as_deref()) {
+                eprintln!("{} is not a valid model: {:#}", path, e);
+                std::process::exit(1);
+            }
+            println!("{} is valid.", path);
+        }
+        Commands::Doctor { model_args } => {
+            let name = model_args.model_name();
+            let whisper = models::resolve_whisper_model(name, model_args.model_registry.as_deref().map(Path::new));
+            if !doctor::run(name, whisper, model_args.require_gpu) {
+                std::process::exit(1);
+            }
+        }
+        Commands::Languages => {
+            println!("Transcription languages (whisper):");
+            for (code, name) in models::whisper_languages() {
+                println!("  {:<6} {}", code, name);
+            }
+            println!();
+            println!("Translation pairs (Marian):");
+            for model in models::MARIAN_MODELS {
+                println!("  {} -> {}", model.source, model.target);
+            }
+        }
+        Commands::Bench { input, runs, models, devices } => {
+            bench::run(input, models, devices, *runs);
+        }
+        Commands::Pipeline {
+            json,
+            segments: aligned,
+            #[cfg(feature = "diarize")]
+            diarize_args,
+            model_args,
+        } => {
+            let interrupted = install_interrupt_handler();
+            let audio_path = timestamped_capture_name(CaptureFormat::Wav);
+            let mut audio_capture = AudioCapture::new(audio_path.clone());
+            record_until_interrupted(&mut audio_capture, &interrupted, None);
+            // 第一次 Ctrl+C 用于结束录制，后续步骤重新开始监听中断
+            interrupted.store(false, Ordering::SeqCst);
 
-            if let Some(output_file) = output {
-                std::fs::write(output_file, &result).expect("Failed to write to output file");
-                println!("Transcription result saved to {}", output_file);
+            let mut whisper = load_whisper(model_args, None);
+            status!("Transcribing audio file {}", audio_path);
+            progress::set_current_file(Some(&audio_path));
+            let segments = whisper.transcribe_file(&audio_path).expect("Transcription failed");
+            #[cfg(feature = "diarize")]
+            let segments = {
+                let mut segments = segments;
+                diarize_args.apply(&whisper, &audio_path, ResampleQuality::default(), &mut segments);
+                segments
+            };
+            let transcript = transcript::to_text(&segments);
+            exit_if_interrupted(&interrupted);
+
+            let mut translator = load_translator("zh", !cli.quiet, &cli.translation_cache, cli.replace.as_deref(), !model_args.no_download);
+            let (translation, aligned_segments) = if *aligned {
+                // 逐段翻译，整体译文由各段译文拼接而成
+                let texts: Vec<&str> = segments.iter().map(|s| s.text.as_str()).collect();
+                let spinner = Spinner::start("Translating...", !cli.quiet);
+                let translated = translator
+                    .translate_batch(&texts, |current, total| {
+                        spinner.set_message(format!("Translating segment {}/{}", current, total))
+                    })
+                    .expect("Translation failed");
+                spinner.stop();
+                let aligned_segments: Vec<AlignedSegment> = segments
+                    .iter()
+                    .zip(translated)
+                    .map(|(segment, translated_text)| AlignedSegment {
+                        start_ms: segment.start_ms,
+                        end_ms: segment.end_ms,
+                        source_text: segment.text.trim().to_string(),
+                        translated_text,
+                        speaker: segment.speaker,
+                    })
+                    .collect();
+                let translation = aligned_segments
+                    .iter()
+                    .map(|s| s.translated_text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                (translation, Some(aligned_segments))
+            } else {
+                let spinner = Spinner::start("Translating...", !cli.quiet);
+                let translation = translator.translate(&transcript).expect("Translation failed");
+                spinner.stop();
+                (translation, None)
+            };
+
+            let manifest = PipelineManifest {
+                duration_secs: manifest::wav_duration_secs(&audio_path).expect("Failed to read captured audio"),
+                audio_path,
+                language: whisper.detected_language(),
+                transcript,
+                translation,
+                segments: aligned_segments,
+            };
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&manifest).expect("Failed to serialize manifest"));
+            } else {
+                status!("Transcription result:");
+                println!("{}", manifest.transcript);
+                status!("Translation result:");
+                println!("{}", manifest.translation);
             }
+            exit_if_interrupted(&interrupted);
         }
-        Commands::Translate { input } => {
-            println!("Translating text file {} to Chinese", input);
-            let content = std::fs::read_to_string(&input).expect("Failed to read input file");
-            let model_path = "models/model.safetensors";
-            let download_url = "https://huggingface.co/Helsinki-NLP/opus-mt-en-zh/resolve/refs%2Fpr%2F26/model.safetensors";
-            ensure_model_exists(model_path, download_url);
+    }
+}
+
+/// 汇总命令行参数、环境变量和默认值合并后实际生效的设置，供 `--print-config` 输出
+fn effective_config(cli: &Cli) -> serde_json::Value {
+    use serde_json::json;
 
-            let tokenizer_path_en = "models/tokenizer-marian-base-en.json";
-            let tokenizer_path_zh = "models/tokenizer-marian-base-zh.json";
+    // 模型解析失败时把错误原因一并输出，这正是排查“为什么用错了模型”时需要的信息
+    let whisper_config = |model_args: &ModelArgs| {
+        let resolved = models::resolve_whisper_model(model_args.model_name(), model_args.model_registry.as_deref().map(Path::new))
+            .map(|entry| json!(entry))
+            .unwrap_or_else(|e| json!({ "error": format!("{:#}", e) }));
+        json!({
+            "model": model_args.model_name(),
+            "model_registry": model_args.model_registry,
+            "resolved": resolved,
+            "gpu_device": model_args.gpu_device.unwrap_or(0),
+            "require_gpu": model_args.require_gpu,
+            "no_download": model_args.no_download,
+        })
+    };
+    let transcribe_config = |options: &TranscribeOptions| {
+        json!({
+            "start_secs": options.start_secs,
+            "end_secs": options.end_secs,
+            "resample_quality": options.resample_quality.to_possible_value().map(|v| v.get_name().to_string()),
+            "suppress_blank": options.suppress_blank,
+            "suppress_non_speech_tokens": options.suppress_non_speech_tokens,
+            "no_context": options.no_context,
+            "single_segment": options.single_segment,
+            "entropy_threshold": options.entropy_threshold,
+            "logprob_threshold": options.logprob_threshold,
+            "best_of": options.best_of,
+            "beam_size": options.beam_size,
+            "patience": options.patience,
+            "word_timestamps": options.word_timestamps.and_then(|w| w.to_possible_value()).map(|v| v.get_name().to_string()),
+            "language_chunk_secs": options.language_chunk_secs,
+            "chunk_overlap_secs": options.chunk_overlap_secs,
+            "vad_threshold_dbfs": options.vad_threshold_dbfs,
+            "timeout_secs": options.timeout.map(|t| t.as_secs_f64()),
+            "language": options.language.as_deref().unwrap_or("auto"),
+            "short_clip_secs": options.short_clip_secs,
+            "short_clip_language": options.short_clip_language,
+            "min_segment_confidence": options.min_segment_confidence,
+            "cleanup": options
+                .cleanup
+                .iter()
+                .filter_map(|rule| rule.to_possible_value())
+                .map(|v| v.get_name().to_string())
+                .collect::<Vec<_>>(),
+        })
+    };
+    let translation_config = |target: &str| match MarianModel::find("en", target) {
+        Some(model) => json!({ "source": model.source, "target": model.target, "model": model.entry() }),
+        None => json!({ "source": "en", "target": target, "error": "no translation model for this pair" }),
+    };
+
+    let mut config = json!({
+        "quiet": cli.quiet,
+        "progress_json": cli.progress_json,
+        "download": cli.download,
+        "translation_cache": cli.translation_cache,
+        "replace": cli.replace,
+        "min_free_space_bytes": cli.min_free_space,
+    });
+    let command = match &cli.command {
+        Commands::Capture { output, archive_dir, device, format, match_file, sample_rate, channels, force_mono, force_stereo, channel, downmix, loudnorm, agc, max_duration, buffer_size, .. } => json!({
+            "name": "capture",
+            "output": output,
+            "archive_dir": archive_dir,
+            "device": device,
+            "format": format.extension(),
+            "match": match_file,
+            "sample_rate": sample_rate,
+            "channels": channels,
+            "force_mono": force_mono,
+            "force_stereo": force_stereo,
+            "channel": channel,
+            "downmix": downmix.to_possible_value().map(|v| v.get_name().to_string()),
+            "loudnorm_lufs": loudnorm,
+            "agc_target_dbfs": agc,
+            "max_duration_secs": max_duration,
+            "buffer_size_frames": buffer_size,
+        }),
+        Commands::Transcribe { format, translate_to, overlap_translation, resume, preview, detect_only, model_args, transcribe_args, write_options, .. } => json!({
+            "name": "transcribe",
+            "format": format.extension(),
+            "resume": resume,
+            "preview_secs": preview,
+            "detect_only": detect_only,
+            "whisper": whisper_config(model_args),
+            "transcribe": transcribe_config(&transcribe_args.options()),
+            "segment_callback_script": transcribe_args.segment_callback_script,
+            "translation": translate_to.as_deref().map(translation_config),
+            "overlap_translation": overlap_translation,
+            "output": write_options,
+        }),
+        Commands::Translate { format, no_download, write_options, .. } => json!({
+            "name": "translate",
+            "format": format.to_possible_value().map(|v| v.get_name().to_string()),
+            "no_download": no_download,
+            "translation": translation_config("zh"),
+            "output": write_options,
+        }),
+        Commands::Pipeline { json, segments, model_args, .. } => json!({
+            "name": "pipeline",
+            "json": json,
+            "segments": segments,
+            "whisper": whisper_config(model_args),
+            "transcribe": transcribe_config(&TranscribeOptions::default()),
+            "translation": translation_config("zh"),
+        }),
+        Commands::Listen { keep_seconds, device, model_args, transcribe_args } => json!({
+            "name": "listen",
+            "keep_seconds": keep_seconds,
+            "device": device,
+            "whisper": whisper_config(model_args),
+            "transcribe": transcribe_config(&transcribe_args.options()),
+        }),
+        Commands::Waveform { width, height, .. } => json!({ "name": "waveform", "width": width, "height": height }),
+        Commands::Play { input, start, end, resample_quality } => json!({
+            "name": "play",
+            "input": input,
+            "start_secs": start,
+            "end_secs": end,
+            "resample_quality": resample_quality.to_possible_value().map(|v| v.get_name().to_string()),
+        }),
+        Commands::Info { input } => json!({ "name": "info", "input": input }),
+        Commands::ValidateModel { path, sha256 } => json!({ "name": "validate-model", "path": path, "sha256": sha256 }),
+        Commands::Doctor { model_args } => json!({ "name": "doctor", "whisper": whisper_config(model_args) }),
+        Commands::Languages => json!({ "name": "languages" }),
+        Commands::Bench { runs, models, devices, .. } => json!({
+            "name": "bench",
+            "runs": runs,
+            "models": models,
+            "devices": devices.iter().filter_map(|d| d.to_possible_value()).map(|v| v.get_name().to_string()).collect::<Vec<_>>(),
+        }),
+    };
+    config["command"] = command;
+    config
+}
 
-            let mut translator = translate::Translator::new(model_path,tokenizer_path_en,tokenizer_path_zh).expect("Failed to load translator model");
-            let result = translator.translate(&content).expect("Translation failed");
-            println!("Translation result:\n{}", result);
+/// 生成形如 `audio_20250101123000.wav` 的录音文件名，扩展名随录音格式而定
+fn timestamped_capture_name(format: CaptureFormat) -> String {
+    capture_name_at(&chrono::Local::now(), format)
+}
+
+/// `--archive-dir`：按录制时间分目录存放，返回 `<dir>/<年>/<月>/audio_<时间戳>.<扩展名>`，目录不存在时自动创建
+fn archived_capture_name(dir: &Path, format: CaptureFormat) -> std::io::Result<String> {
+    // 目录和文件名取自同一时刻，跨月的瞬间也不会对不上
+    let now = chrono::Local::now();
+    let dir = dir.join(now.format("%Y").to_string()).join(now.format("%m").to_string());
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(capture_name_at(&now, format)).to_string_lossy().into_owned())
+}
+
+fn capture_name_at(time: &chrono::DateTime<chrono::Local>, format: CaptureFormat) -> String {
+    format!("audio_{}.{}", time.format("%Y%m%d%H%M%S"), format.extension())
+}
+
+/// 开始录制，直到收到 Ctrl+C 或达到 `max_duration` 上限后停止并写回 WAV 头，打印并返回录制摘要。
+///
+/// 提示信息通过 `status!` 输出；录到标准输出时调用方已开启 `--raw` 模式，提示会改写到 stderr。
+/// 开始前检查目标卷的可用空间（有 `max_duration` 时按预计大小），不足时打印错误并退出。
+fn record_until_interrupted(
+    audio_capture: &mut AudioCapture,
+    interrupted: &AtomicBool,
+    max_duration: Option<Duration>,
+) -> Option<CaptureSummary> {
+    if audio_capture.is_stdout() {
+        status!("Capturing audio to stdout");
+    } else {
+        let needed = max_duration.map(|duration| audio_capture.estimated_size(duration));
+        if let Err(e) = disk_space::ensure_free_space(Path::new(audio_capture.file_name()), needed) {
+            eprintln!("Cannot start recording: {:#}", e);
+            std::process::exit(1);
+        }
+        status!("Capturing audio to {}", audio_capture.file_name());
+    }
+    audio_capture.start();
+    status!("Audio capture started. Press Ctrl+C to stop.");
+
+    let started = Instant::now();
+    while !interrupted.load(Ordering::SeqCst) {
+        let mut tick = Duration::from_secs(1);
+        if let Some(limit) = max_duration {
+            let elapsed = started.elapsed();
+            if elapsed >= limit {
+                status!("Maximum capture duration of {}s reached, stopping.", limit.as_secs());
+                break;
+            }
+            tick = tick.min(limit - elapsed);
+        }
+        std::thread::sleep(tick);
+    }
+    let summary = audio_capture.stop();
+    status!("Audio capture stopped.");
+    if let Some(summary) = &summary {
+        status!(
+            "Recorded {}: {:.2}s, {} Hz, {} channel(s), peak {:.1} dBFS, {} clipped sample(s)",
+            if audio_capture.is_stdout() { "to stdout" } else { summary.path.as_str() },
+            summary.duration_secs,
+            summary.sample_rate,
+            summary.channels,
+            summary.peak_dbfs,
+            summary.clipped_samples
+        );
+    }
+    summary
+}
+
+/// 校验模型文件：可选的 SHA-256 校验与下载时相同，随后按扩展名用 whisper.cpp 或 candle 实际加载并打印参数
+fn validate_model(path: &str, sha256: Option<&str>) -> anyhow::Result<()> {
+    let size = std::fs::metadata(path).with_context(|| format!("cannot read {}", path))?.len();
+    println!("File: {} ({:.1} MB)", path, size as f64 / (1024.0 * 1024.0));
+    if let Some(expected) = sha256 {
+        if !download_model::verify_sha256(path, expected)? {
+            anyhow::bail!("SHA-256 checksum does not match {}", expected);
+        }
+        println!("SHA-256: ok");
+    }
+
+    if Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("safetensors")) {
+        let (tensors, params, vocab_size) = translate::inspect_safetensors(path)?;
+        println!("Type: Marian translation model (safetensors)");
+        println!("Tensors: {}, parameters: {:.1}M", tensors, params as f64 / 1e6);
+        if let Some(vocab_size) = vocab_size {
+            println!("Vocabulary size: {}", vocab_size);
+        }
+    } else {
+        let info = audio_transcribe::inspect_model(path)?;
+        println!("Type: Whisper {} (ggml, ftype {})", info.model_type, info.ftype);
+        println!("Vocabulary size: {}", info.n_vocab);
+        println!("Multilingual: {}", if info.multilingual { "yes" } else { "no (English only)" });
+        println!("Layers: {} audio / {} text, {} mel bins", info.n_audio_layer, info.n_text_layer, info.n_mels);
+    }
+    Ok(())
+}
+
+/// 按 `--model`/`--model-registry` 解析模型，确保其存在并加载转写器。
+/// 未指定模型时可能先在终端中询问要使用的模型，见 [`ModelArgs::pick_model_name`]。
+///
+/// 请求 DTW 词级时间戳时，会按模型名称选择对应的对齐预设并在创建上下文时启用。
+/// GPU 后端初始化失败时给出警告并改用 CPU 重试，除非指定了 `--require-gpu`。
+fn load_whisper(model_args: &ModelArgs, word_timestamps: Option<WordTimestamps>) -> Whisper {
+    let model_name = model_args.pick_model_name();
+    let model = models::resolve_whisper_model(&model_name, model_args.model_registry.as_deref().map(Path::new))
+        .expect("Failed to resolve whisper model");
+    ensure_model_exists(&model, !model_args.no_download);
+
+    let dtw_preset = match word_timestamps {
+        Some(WordTimestamps::Dtw) => match models::dtw_preset(&model_name) {
+            Some(preset) => Some(preset),
+            None => {
+                eprintln!("No DTW alignment preset for model '{}'; use --word-timestamps token instead", model_name);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    let params = |use_gpu: bool| {
+        let mut params = WhisperContextParameters {
+            use_gpu,
+            flash_attn: false,
+            gpu_device: model_args.gpu_device.unwrap_or(0),
+            ..Default::default()
+        };
+        if let Some(model_preset) = &dtw_preset {
+            params.dtw_parameters = DtwParameters {
+                mode: DtwMode::ModelPreset { model_preset: model_preset.clone() },
+                ..Default::default()
+            };
+        }
+        params
+    };
+
+    let result = Whisper::with_params(&model.path, params(true)).or_else(|e| {
+        if model_args.require_gpu {
+            return Err(e);
+        }
+        eprintln!("Warning: {:#}. Falling back to CPU (pass --require-gpu to fail instead).", e);
+        Whisper::with_params(&model.path, params(false))
+    });
+    match result {
+        Ok(whisper) => whisper,
+        Err(e) => {
+            eprintln!("Failed to load whisper model: {:#}", e);
+            std::process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
+
+/// 在渲染任何输出格式之前按 `--cleanup` 清理段落文本，再按 `--min-segment-confidence` 筛选段落，
+/// 筛掉的数量打印到 stderr
+fn filter_segments(segments: Vec<audio_transcribe::Segment>, options: &TranscribeOptions) -> Vec<audio_transcribe::Segment> {
+    let mut segments = segments;
+    transcript::clean_segments(&mut segments, &options.cleanup);
+    let Some(min_confidence) = options.min_segment_confidence else {
+        return segments;
+    };
+    let before = segments.len();
+    let segments = transcript::filter_by_confidence(segments, min_confidence);
+    if segments.len() < before {
+        eprintln!("Dropped {} segment(s) below confidence {:.2}", before - segments.len(), min_confidence);
+    }
+    segments
+}
+
+/// 按转写选项输出结果：请求词级时间戳且格式为 txt 或 srt 时输出逐词 SRT，否则按 `--format` 渲染（JSON 中每段带有 words 数组）
+fn render_transcript(segments: &[audio_transcribe::Segment], options: &TranscribeOptions, format: OutputFormat) -> String {
+    match (options.word_timestamps, format) {
+        (Some(_), OutputFormat::Txt | OutputFormat::Srt) => transcript::to_word_srt(segments),
+        _ => format.render(segments),
+    }
+}
+
+/// 被 Ctrl+C 中断时使用的退出码（与 shell 约定的 128 + SIGINT 一致）
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// 安装 Ctrl+C 处理器，返回“是否已被中断”的标志。
+///
+/// 第一次 Ctrl+C 只设置标志，由调用方在当前步骤结束后自行收尾；
+/// 再次按下则立即退出。
+fn install_interrupt_handler() -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            eprintln!("Interrupted again, exiting immediately.");
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        eprintln!("Interrupt received, finishing the current step. Press Ctrl+C again to force exit.");
+    }).expect("Error setting Ctrl-C handler");
+    interrupted
+}
+
+/// 如果收到过 Ctrl+C，则以 [`EXIT_INTERRUPTED`] 退出
+fn exit_if_interrupted(interrupted: &AtomicBool) {
+    if interrupted.load(Ordering::SeqCst) {
+        eprintln!("Stopped by user.");
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+}
+
+/// 确保英文到 `target` 的 Marian 模型存在并加载翻译器，加载期间显示进度指示；按 `cache` 设置启用译文缓存，
+/// 给出 `replace` 规则文件时对每段译文做替换。`allow_download` 为 `false` 时缺少模型直接报错，见 [`ensure_model_exists`]
+fn load_translator(
+    target: &str,
+    show_progress: bool,
+    cache: &translation_cache::CacheConfig,
+    replace: Option<&str>,
+    allow_download: bool,
+) -> Translator {
+    // 先检查规则文件，避免加载完模型才发现写错了
+    let replacements = replace.map(|path| match Replacements::load(path) {
+        Ok(replacements) => {
+            status!("Loaded {} replacement rule(s) from {}", replacements.rule_count(), path);
+            replacements
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+    });
+    let model = MarianModel::find("en", target).expect("translation model missing from the model table");
+    let entry = model.entry();
+    ensure_model_exists(&entry, allow_download);
+
+    let spinner = Spinner::start("Loading translation model...", show_progress);
+    let translator = Translator::new(&entry.path, &model.tokenizer_source_path(), &model.tokenizer_target_path());
+    spinner.stop();
+    let mut translator = translator.unwrap_or_else(|e| {
+        eprintln!("Failed to load translation model: {:#}", e);
+        std::process::exit(1);
+    });
+    translator.set_cache(TranslationCache::open(cache, &format!("{}-{}", model.source, model.target)));
+    translator.set_replacements(replacements);
+    translator
+}
+
+/// 批量转写 `--input-dir` 下收集到的或多个 `-i` 给出的 `files`，复用同一个 Whisper 实例，单个文件失败不会中断整个批次。
+///
+/// 指定 `append` 时所有结果追加到同一个文件（每段以文件名开头），否则各自写入 `<name>.<format 扩展名>`
+/// （位于输入旁边或 `output_dir` 中）。未检测到语音的文件默认不写出结果，除非指定 `write_empty`。
+#[allow(clippy::too_many_arguments)]
+fn transcribe_dir(
+    whisper: &mut Whisper,
+    dir: &Path,
+    files: &[PathBuf],
+    output_dir: Option<&Path>,
+    output_template: Option<&batch::OutputTemplate>,
+    jsonl: Option<&str>,
+    options: &TranscribeOptions,
+    format: OutputFormat,
+    append: Option<&str>,
+    write_empty: bool,
+    write_options: &WriteOptions,
+    interrupted: &AtomicBool,
+) {
+    let total = files.len();
+    let default_template = batch::OutputTemplate::parse(batch::TRANSCRIPT_TEMPLATE).expect("default template is valid");
+    let output_template = output_template.unwrap_or(&default_template);
+
+    let mut append_file = append.map(|path| {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("Failed to open append file");
+        // 追加模式只在新建（空）文件时写入 BOM
+        if write_options.bom && file.metadata().map(|m| m.len() == 0).unwrap_or(false) {
+            file.write_all(output::UTF8_BOM).expect("Failed to write append file");
+        }
+        file
+    });
+    // --jsonl：每处理完一个文件追加一行记录，失败的文件记录错误信息
+    let mut jsonl_file = jsonl.map(|path| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("Failed to open JSONL file")
+    });
+
+    let mut summary = batch::BatchSummary::default();
+    for (index, path) in files.iter().enumerate() {
+        if interrupted.load(Ordering::SeqCst) {
+            status!("Interrupted, skipping the remaining {} file(s).", total - index);
+            break;
+        }
+        status!("[{}/{}] Transcribing {}", index + 1, total, path.display());
+        progress::set_current_file(path.to_str());
+        let segments = transcribe_any(whisper, path, options).map(|segments| filter_segments(segments, options));
+        // 指定的语言，否则为 Whisper 检测到的语言
+        let language = options.language.clone().or_else(|| whisper.detected_language());
+        if let Some(file) = jsonl_file.as_mut() {
+            let record = match &segments {
+                Ok(segments) => transcript::jsonl_record(path, language.as_deref(), segments),
+                Err(e) => transcript::jsonl_error_record(path, e),
+            };
+            if let Err(e) = output::append_json_line(file, &record) {
+                eprintln!("Warning: failed to write {}: {}", jsonl.unwrap_or_default(), e);
+            }
+        }
+        let result = segments
+            .and_then(|segments| {
+                if segments.is_empty() && !write_empty {
+                    return Ok(None);
+                }
+                let text = render_transcript(&segments, options, format);
+                match append_file.as_mut() {
+                    Some(file) => {
+                        let title = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                        output::append_section(file, &title, &text, write_options.line_ending)?;
+                        Ok(Some(PathBuf::from(append.unwrap_or_default())))
+                    }
+                    None => {
+                        let file_name = output_template.render(path, format.extension(), language.as_deref().unwrap_or("und"));
+                        let output = batch::batch_output_path(path, dir, output_dir, &file_name)?;
+                        write_output(&output, &text, write_options)?;
+                        Ok(Some(output))
+                    }
+                }
+            });
+        match result {
+            Ok(Some(output)) => {
+                status!("[{}/{}] Saved {}", index + 1, total, output.display());
+                summary.succeeded += 1;
+            }
+            Ok(None) => {
+                status!("[{}/{}] No speech detected, nothing written", index + 1, total);
+                summary.succeeded += 1;
+            }
+            Err(e) => {
+                eprintln!("[{}/{}] Failed to transcribe {}: {:#}", index + 1, total, path.display(), e);
+                summary.failed.push((path.clone(), e.to_string()));
+            }
+        }
+    }
+    summary.print(total);
+}
+
+/// 转写批处理中的一个文件，非 WAV 文件的解码见 [`with_wav`]
+fn transcribe_any(whisper: &mut Whisper, path: &Path, options: &TranscribeOptions) -> anyhow::Result<Vec<audio_transcribe::Segment>> {
+    with_wav(path, |input| whisper.transcribe_segments(input, options))
+}
+
+/// 以 WAV 文件的形式处理批处理中的一个文件：WAV 直接交给 `f`，其他格式先用 ffmpeg 解码为临时 WAV（需要 `ffmpeg` feature）
+fn with_wav<T>(path: &Path, f: impl FnOnce(&str) -> anyhow::Result<T>) -> anyhow::Result<T> {
+    let input = path.to_str().ok_or_else(|| anyhow::anyhow!("path is not valid UTF-8"))?;
+    if is_wav_path(path) {
+        return f(input);
+    }
+    #[cfg(feature = "ffmpeg")]
+    {
+        let decoded = ffmpeg::decode_to_wav(path)?;
+        f(decoded.path().to_str().expect("Temp path is not valid UTF-8"))
+    }
+    #[cfg(not(feature = "ffmpeg"))]
+    anyhow::bail!("only WAV files can be decoded; rebuild with --features ffmpeg to transcribe {}", input)
+}
+
+/// 按扩展名判断文件能否由内置解码器直接读取
+fn is_wav_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+/// `--detect-only` 在单个文件上打印的候选语言数
+const DETECT_ONLY_TOP: usize = 3;
+
+/// `--detect-only`：读取 WAV 文件，从 `--start` 处开始做语言检测，返回按概率从高到低排列的候选语言
+fn detect_wav_language(whisper: &mut Whisper, input: &str, options: &TranscribeOptions) -> anyhow::Result<Vec<(String, f32)>> {
+    let samples = whisper.load_mono_16k(input, options.resample_quality)?;
+    let start = options.start_secs.map(|s| (s * audio_transcribe::WHISPER_SAMPLE_RATE as f32) as usize).unwrap_or(0).min(samples.len());
+    whisper.detect_language(&samples[start..])
+}
+
+/// 批量翻译目录下（`recursive` 时包括子目录）的 .txt/.srt 文件，结果写在输入旁边或 `output_dir` 中，单个文件失败不会中断整个批次
+#[allow(clippy::too_many_arguments)]
+fn translate_dir(
+    translator: &mut Translator,
+    dir: &Path,
+    output_dir: Option<&Path>,
+    output_template: Option<&batch::OutputTemplate>,
+    recursive: bool,
+    encoding: Option<&'static encoding_rs::Encoding>,
+    write_options: &WriteOptions,
+    interrupted: &AtomicBool,
+) {
+    let default_template = batch::OutputTemplate::parse(batch::TRANSLATION_TEMPLATE).expect("default template is valid");
+    let output_template = output_template.unwrap_or(&default_template);
+    let (mut files, _) = batch::collect_input_files(dir, &["txt", "srt"], recursive).expect("Failed to read input directory");
+    // 上次运行写在输入旁边的译文（例如 talk.zh.txt）本身也是 .txt，不能再当作输入翻译一遍
+    if let Some(suffix) = output_template.suffix("txt", "zh") {
+        let before = files.len();
+        files.retain(|path| !path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.ends_with(&suffix)));
+        if files.len() < before {
+            status!("Skipping {} file(s) ending in {} (earlier translation outputs)", before - files.len(), suffix);
+        }
+    }
+    let total = files.len();
+    status!("Found {} file(s) to translate in {}", total, dir.display());
+
+    let mut summary = batch::BatchSummary::default();
+    for (index, path) in files.iter().enumerate() {
+        if interrupted.load(Ordering::SeqCst) {
+            status!("Interrupted, skipping the remaining {} file(s).", total - index);
+            break;
+        }
+        status!("[{}/{}] Translating {}", index + 1, total, path.display());
+        progress::set_current_file(path.to_str());
+        match batch::batch_output_path(path, dir, output_dir, &output_template.render(path, "txt", "zh"))
+            .map_err(anyhow::Error::from)
+            .and_then(|output| translate_file(translator, path, &output, encoding, write_options))
+        {
+            Ok(output) => {
+                status!("[{}/{}] Saved {}", index + 1, total, output.display());
+                summary.succeeded += 1;
+            }
+            Err(e) => {
+                eprintln!("[{}/{}] Failed to translate {}: {:#}", index + 1, total, path.display(), e);
+                summary.failed.push((path.clone(), e.to_string()));
+            }
+        }
+    }
+    summary.print(total);
+}
+
+/// `translate` 的输入：`--text` 给出的字符串，或 `--input` 指定的文件（按 `encoding` 或自动检测的编码解码为 UTF-8）
+fn open_translation_input(text: Option<&str>, input: Option<&str>, encoding: Option<&'static encoding_rs::Encoding>) -> Box<dyn BufRead> {
+    match (text, input) {
+        (Some(text), _) => Box::new(std::io::Cursor::new(text.to_string().into_bytes())),
+        (None, Some(input)) => {
+            status!("Translating text file {} to Chinese", input);
+            progress::set_current_file(Some(input));
+            match text_encoding::open_text(Path::new(input), encoding) {
+                Ok(reader) => Box::new(reader),
+                Err(e) => {
+                    eprintln!("{:#}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        (None, None) => unreachable!("--input is required without --input-dir or --text"),
+    }
+}
+
+/// 翻译单个文件并写入 `output`，返回输出路径
+fn translate_file(
+    translator: &mut Translator,
+    path: &Path,
+    output: &Path,
+    encoding: Option<&'static encoding_rs::Encoding>,
+    write_options: &WriteOptions,
+) -> anyhow::Result<PathBuf> {
+    // 在翻译之前检查，避免白白跑完模型
+    write_options.check_writable(output)?;
+    let reader = text_encoding::open_text(path, encoding)?;
+    let mut file = AtomicFile::create(output, write_options)?;
+    translator.translate_reader(reader, &mut file, |_| {})?;
+    file.commit()?;
+    Ok(output.to_path_buf())
+}
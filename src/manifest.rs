@@ -0,0 +1,39 @@
+use serde::Serialize;
+use std::path::Path;
+
+/// capture → transcribe → translate 流水线的结果清单，序列化为 JSON 供自动化流程使用
+#[derive(Debug, Serialize)]
+pub struct PipelineManifest {
+    /// 录制得到的音频文件路径
+    pub audio_path: String,
+    /// 音频时长（秒）
+    pub duration_secs: f64,
+    /// Whisper 检测到的语言代码
+    pub language: Option<String>,
+    /// 转写文本
+    pub transcript: String,
+    /// 翻译后的文本
+    pub translation: String,
+    /// 逐段对齐的原文与译文，仅在请求时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<AlignedSegment>>,
+}
+
+/// 带时间戳的一段原文及其译文
+#[derive(Debug, Serialize)]
+pub struct AlignedSegment {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub source_text: String,
+    pub translated_text: String,
+    /// 说话人编号，仅在启用说话人分离时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<u32>,
+}
+
+/// 根据 WAV 头中的采样帧数计算音频时长（秒）
+pub fn wav_duration_secs(path: impl AsRef<Path>) -> hound::Result<f64> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    Ok(reader.duration() as f64 / spec.sample_rate as f64)
+}
@@ -0,0 +1,158 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use whisper_rs::DtwModelPreset;
+
+/// 内置的 Whisper 模型表：名称、文件名以及大致下载大小（MB）
+pub const WHISPER_MODELS: &[WhisperModel] = &[
+    WhisperModel { name: "tiny", file: "ggml-tiny.bin", size_mb: 75 },
+    WhisperModel { name: "base", file: "ggml-base.bin", size_mb: 142 },
+    WhisperModel { name: "small", file: "ggml-small.bin", size_mb: 466 },
+    WhisperModel { name: "medium", file: "ggml-medium.bin", size_mb: 1500 },
+    WhisperModel { name: "large-v3", file: "ggml-large-v3.bin", size_mb: 3100 },
+];
+
+/// 未指定模型时使用的 Whisper 模型
+pub const DEFAULT_WHISPER_MODEL: &str = "base";
+
+/// 一个可下载的 Whisper ggml 模型
+#[derive(Debug, Clone, Copy)]
+pub struct WhisperModel {
+    pub name: &'static str,
+    pub file: &'static str,
+    pub size_mb: u32,
+}
+
+impl WhisperModel {
+    /// 按名称查找内置模型
+    pub fn find(name: &str) -> Option<&'static WhisperModel> {
+        WHISPER_MODELS.iter().find(|m| m.name == name)
+    }
+
+    /// 模型在本地 models 目录中的路径
+    pub fn path(&self) -> String {
+        format!("models/{}", self.file)
+    }
+
+    /// 模型在 Hugging Face 上的下载地址
+    pub fn url(&self) -> String {
+        format!("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/{}", self.file)
+    }
+}
+
+/// 按模型名称选择 DTW 对齐所需的注意力头预设，未知模型返回 `None`
+pub fn dtw_preset(name: &str) -> Option<DtwModelPreset> {
+    let preset = match name {
+        "tiny" => DtwModelPreset::Tiny,
+        "tiny.en" => DtwModelPreset::TinyEn,
+        "base" => DtwModelPreset::Base,
+        "base.en" => DtwModelPreset::BaseEn,
+        "small" => DtwModelPreset::Small,
+        "small.en" => DtwModelPreset::SmallEn,
+        "medium" => DtwModelPreset::Medium,
+        "medium.en" => DtwModelPreset::MediumEn,
+        "large-v1" => DtwModelPreset::LargeV1,
+        "large-v2" => DtwModelPreset::LargeV2,
+        "large-v3" => DtwModelPreset::LargeV3,
+        _ => return None,
+    };
+    Some(preset)
+}
+
+/// 解析后的模型条目：本地路径、下载地址以及可选的 SHA-256 校验值
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ModelEntry {
+    pub path: String,
+    pub url: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+impl From<&WhisperModel> for ModelEntry {
+    fn from(model: &WhisperModel) -> Self {
+        Self {
+            path: model.path(),
+            url: model.url(),
+            sha256: None,
+        }
+    }
+}
+
+/// 按名称解析 Whisper 模型。
+///
+/// `registry` 为 JSON 文件，格式为 `{"名称": {"path": "...", "url": "...", "sha256": "..."}}`，
+/// 其中的条目与内置模型表合并，同名时以注册表为准；`path` 中的 `~` 与环境变量会被展开。
+pub fn resolve_whisper_model(name: &str, registry: Option<&Path>) -> anyhow::Result<ModelEntry> {
+    if let Some(registry) = registry {
+        let content = std::fs::read_to_string(registry)
+            .with_context(|| format!("failed to read model registry {}", registry.display()))?;
+        let mut entries: HashMap<String, ModelEntry> = serde_json::from_str(&content)
+            .with_context(|| format!("invalid model registry {}", registry.display()))?;
+        if let Some(mut entry) = entries.remove(name) {
+            // 注册表中的路径同样支持 `~` 与环境变量，便于在不同机器间共用
+            entry.path = crate::paths::expand_path(&entry.path).map_err(anyhow::Error::msg)?;
+            return Ok(entry);
+        }
+    }
+    WhisperModel::find(name)
+        .map(ModelEntry::from)
+        .with_context(|| format!("unknown whisper model '{}'", name))
+}
+
+/// 内置的 Marian 翻译模型表，每项对应一个源语言 → 目标语言的翻译方向
+pub const MARIAN_MODELS: &[MarianModel] = &[MarianModel {
+    source: "en",
+    target: "zh",
+    file: "model.safetensors",
+    url: "https://huggingface.co/Helsinki-NLP/opus-mt-en-zh/resolve/refs%2Fpr%2F26/model.safetensors",
+    tokenizer_source: "tokenizer-marian-base-en.json",
+    tokenizer_target: "tokenizer-marian-base-zh.json",
+}];
+
+/// 一个 Marian 翻译模型及其分词器文件
+#[derive(Debug, Clone, Copy)]
+pub struct MarianModel {
+    pub source: &'static str,
+    pub target: &'static str,
+    pub file: &'static str,
+    pub url: &'static str,
+    pub tokenizer_source: &'static str,
+    pub tokenizer_target: &'static str,
+}
+
+impl MarianModel {
+    /// 按翻译方向查找内置模型
+    pub fn find(source: &str, target: &str) -> Option<&'static MarianModel> {
+        MARIAN_MODELS.iter().find(|m| m.source == source && m.target == target)
+    }
+
+    /// 模型在本地 models 目录中的路径
+    pub fn path(&self) -> String {
+        format!("models/{}", self.file)
+    }
+
+    pub fn tokenizer_source_path(&self) -> String {
+        format!("models/{}", self.tokenizer_source)
+    }
+
+    pub fn tokenizer_target_path(&self) -> String {
+        format!("models/{}", self.tokenizer_target)
+    }
+
+    /// 模型文件对应的条目
+    pub fn entry(&self) -> ModelEntry {
+        ModelEntry {
+            path: self.path(),
+            url: self.url.to_string(),
+            sha256: None,
+        }
+    }
+}
+
+/// Whisper 支持的语言，返回 `(代码, 英文名称)` 列表
+pub fn whisper_languages() -> Vec<(&'static str, &'static str)> {
+    (0..=whisper_rs::get_lang_max_id())
+        .filter_map(|id| Some((whisper_rs::get_lang_str(id)?, whisper_rs::get_lang_str_full(id)?)))
+        .collect()
+}
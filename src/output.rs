@@ -0,0 +1,222 @@
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// 写出结果文件时的通用选项，各子命令通过 `#[command(flatten)]` 共用
+#[derive(Args, Debug, Clone, Default, Serialize)]
+pub struct WriteOptions {
+    /// 输出文件已存在时报错，而不是覆盖
+    #[arg(long, help = "Refuse to overwrite output files that already exist")]
+    pub no_clobber: bool,
+    /// 在输出文件开头写入 UTF-8 BOM，方便旧版 Windows 编辑器识别编码
+    #[arg(long, help = "Start output files with a UTF-8 byte order mark for legacy Windows editors")]
+    pub bom: bool,
+    /// 文本输出使用的换行符
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = LineEnding::Auto, help = "Line endings of output files: lf, crlf, or auto (the platform's native style)")]
+    pub line_ending: LineEnding,
+}
+
+/// 输出文件的换行风格
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    /// Windows 记事本等旧编辑器只认 CRLF
+    Crlf,
+    /// 当前平台的原生风格：Windows 上为 CRLF，其他平台为 LF
+    #[default]
+    Auto,
+}
+
+impl LineEnding {
+    /// 换行时是否写出 CRLF
+    pub fn is_crlf(self) -> bool {
+        match self {
+            LineEnding::Lf => false,
+            LineEnding::Crlf => true,
+            LineEnding::Auto => cfg!(windows),
+        }
+    }
+}
+
+/// UTF-8 字节顺序标记
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+impl WriteOptions {
+    /// 在开始耗时的处理之前检查输出路径，`--no-clobber` 下文件已存在则返回错误
+    pub fn check_writable(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if self.no_clobber && path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists (--no-clobber)", path.display()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 写入输出文件：先写到同目录的临时文件，完成后再重命名，
+/// 这样进程中途退出时不会留下写了一半的结果文件。
+///
+/// 内容为 `&str`，因此写出的始终是合法的 UTF-8。
+pub fn write_output(path: impl AsRef<Path>, contents: &str, options: &WriteOptions) -> io::Result<()> {
+    let mut file = AtomicFile::create(path, options)?;
+    file.write_all(contents.as_bytes())?;
+    file.commit()
+}
+
+/// 可以逐步写入的输出文件：内容先写入 `<path>.part`，调用 [`AtomicFile::commit`] 后才替换目标文件。
+///
+/// 未提交就被丢弃时会删除临时文件，目标文件保持不变。指定 `--bom` 时创建后先写入 [`UTF8_BOM`]；
+/// 换行风格为 CRLF 时，写入内容中单独的 `\n` 会被转换为 `\r\n`，已有的 `\r\n` 保持不变。
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp: PathBuf,
+    writer: Option<BufWriter<File>>,
+    crlf: bool,
+    /// 上一次写入的最后一个字节是否为 `\r`，`\r\n` 可能被拆在两次写入之间
+    last_cr: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: impl AsRef<Path>, options: &WriteOptions) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        options.check_writable(&path)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".part");
+        let tmp = PathBuf::from(tmp);
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        if options.bom {
+            writer.write_all(UTF8_BOM)?;
+        }
+        Ok(Self { path, tmp, writer: Some(writer), crlf: options.line_ending.is_crlf(), last_cr: false })
+    }
+
+    /// 刷新缓冲并把临时文件重命名为目标文件
+    pub fn commit(mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        std::fs::rename(&self.tmp, &self.path)
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let writer = self.writer.as_mut().expect("AtomicFile already committed");
+        if !self.crlf {
+            return writer.write(buf);
+        }
+        let mut start = 0;
+        for (i, &byte) in buf.iter().enumerate() {
+            let after_cr = if i == 0 { self.last_cr } else { buf[i - 1] == b'\r' };
+            if byte == b'\n' && !after_cr {
+                writer.write_all(&buf[start..i])?;
+                writer.write_all(b"\r")?;
+                start = i;
+            }
+        }
+        writer.write_all(&buf[start..])?;
+        if let Some(&last) = buf.last() {
+            self.last_cr = last == b'\r';
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().expect("AtomicFile already committed").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = std::fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// 以文件名作为标题，把一段结果追加到已打开的文件末尾。
+///
+/// 每次追加后都会 flush 并同步到磁盘，进程中途崩溃时之前的结果仍然完整。
+pub fn append_section(file: &mut File, title: &str, contents: &str, line_ending: LineEnding) -> io::Result<()> {
+    let mut section = format!("==> {} <==\n{}", title, contents);
+    if !contents.ends_with('\n') {
+        section.push('\n');
+    }
+    section.push('\n');
+    if line_ending.is_crlf() {
+        section = section.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+    file.write_all(section.as_bytes())?;
+    file.flush()?;
+    file.sync_data()
+}
+
+/// 把一条记录作为一行 JSON 追加到已打开的文件末尾（JSON Lines），换行固定为 `\n`。
+///
+/// 与 [`append_section`] 一样每次追加后都会 flush 并同步到磁盘。
+pub fn append_json_line(file: &mut File, record: &serde_json::Value) -> io::Result<()> {
+    let mut line = record.to_string();
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    file.flush()?;
+    file.sync_data()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("audio_trans_vox_{}_{}", std::process::id(), name))
+    }
+
+    /// 用 `options` 创建文件，依次写入 `pieces` 后提交，返回写出的字节
+    fn write_pieces(name: &str, options: &WriteOptions, pieces: &[&str]) -> Vec<u8> {
+        let path = temp_path(name);
+        let mut file = AtomicFile::create(&path, options).unwrap();
+        for piece in pieces {
+            file.write_all(piece.as_bytes()).unwrap();
+        }
+        file.commit().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn crlf_converts_bare_newlines_only() {
+        let options = WriteOptions { line_ending: LineEnding::Crlf, ..Default::default() };
+        // 第二段的 `\r\n` 被拆在两次写入之间，不能变成 `\r\r\n`
+        let bytes = write_pieces("crlf.txt", &options, &["one\ntwo\r", "\nthree\r\n", "\n"]);
+        assert_eq!(bytes, b"one\r\ntwo\r\nthree\r\n\r\n");
+    }
+
+    #[test]
+    fn lf_keeps_the_text_as_written() {
+        let options = WriteOptions { line_ending: LineEnding::Lf, ..Default::default() };
+        assert_eq!(write_pieces("lf.txt", &options, &["one\ntwo\r\n"]), b"one\ntwo\r\n");
+    }
+
+    #[test]
+    fn bom_is_written_once_before_the_content() {
+        let options = WriteOptions { bom: true, line_ending: LineEnding::Crlf, ..Default::default() };
+        assert_eq!(write_pieces("bom.txt", &options, &["你好\n", "世界\n"]), "\u{feff}你好\r\n世界\r\n".as_bytes());
+        let options = WriteOptions { line_ending: LineEnding::Lf, ..Default::default() };
+        assert_eq!(write_pieces("no_bom.txt", &options, &["你好\n"]), "你好\n".as_bytes());
+    }
+
+    #[test]
+    fn dropping_without_commit_leaves_no_file() {
+        let path = temp_path("dropped.txt");
+        let mut file = AtomicFile::create(&path, &WriteOptions::default()).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+        assert!(!path.exists());
+        assert!(!temp_path("dropped.txt.part").exists());
+    }
+}
@@ -0,0 +1,49 @@
+use std::env;
+
+/// 展开用户给出的路径中的 `~` 与环境变量，供命令行参数的 `value_parser` 和模型注册表使用。
+///
+/// 支持开头的 `~`（`~` 或 `~/...`，取 `HOME`，Windows 上退回 `USERPROFILE`）、`$VAR`、`${VAR}`
+/// 以及 Windows 风格的 `%VAR%`。未设置的变量原样保留，`-`（标准输出）和 http(s) 地址不做处理。
+pub fn expand_path(value: &str) -> Result<String, String> {
+    if value == "-" || value.starts_with("http://") || value.starts_with("https://") {
+        return Ok(value.to_string());
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(['/', '\\']) {
+            let home = env::var("HOME")
+                .or_else(|_| env::var("USERPROFILE"))
+                .map_err(|_| format!("cannot expand '~' in '{}': HOME is not set", value))?;
+            result.push_str(&home);
+            rest = after;
+        }
+    }
+
+    while let Some(index) = rest.find(['$', '%']) {
+        result.push_str(&rest[..index]);
+        let tail = &rest[index..];
+        let (name, consumed) = match tail.as_bytes() {
+            [b'$', b'{', ..] => match tail.find('}') {
+                Some(end) => (&tail[2..end], end + 1),
+                None => ("", 1),
+            },
+            [b'$', ..] => {
+                let len = tail[1..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(tail.len() - 1);
+                (&tail[1..1 + len], 1 + len)
+            }
+            _ => match tail[1..].find('%') {
+                Some(end) => (&tail[1..1 + end], end + 2),
+                None => ("", 1),
+            },
+        };
+        match env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(expanded) => result.push_str(&expanded),
+            None => result.push_str(&tail[..consumed]),
+        }
+        rest = &tail[consumed..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
@@ -0,0 +1,93 @@
+use crate::audio_transcribe::{audio_resample, ResampleQuality};
+use anyhow::{bail, Context};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig, SupportedStreamConfig};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// 播完最后一个采样后再等这么久，让设备缓冲区中剩余的音频放完
+const DRAIN_DELAY: Duration = Duration::from_millis(300);
+
+/// 通过默认输出设备播放单声道采样，播完或 `interrupted` 被置位时返回。
+///
+/// 设备支持 `sample_rate` 时原样播放；否则按设备的默认采样率重采样，这只影响播放，不改变传入的采样。
+/// 单声道信号复制到设备的每个通道。
+pub fn play(samples: &[f32], sample_rate: u32, interrupted: &AtomicBool) -> anyhow::Result<()> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .context("no default output device")?;
+    let config = playback_config(&device, sample_rate)?;
+    let device_rate = config.sample_rate().0;
+    let samples = if device_rate == sample_rate {
+        samples.to_vec()
+    } else {
+        status!("Output device does not support {} Hz, resampling to {} Hz for playback only", sample_rate, device_rate);
+        audio_resample(samples, sample_rate, device_rate, ResampleQuality::Best)
+    };
+
+    let samples = Arc::new(samples);
+    let position = Arc::new(AtomicUsize::new(0));
+    let stream_config: StreamConfig = config.clone().into();
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, samples.clone(), position.clone()),
+        SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, samples.clone(), position.clone()),
+        SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, samples.clone(), position.clone()),
+        format => bail!("unsupported output sample format {:?}", format),
+    }
+    .context("failed to open the output stream")?;
+    stream.play().context("failed to start playback")?;
+
+    while position.load(Ordering::Relaxed) < samples.len() {
+        if interrupted.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    thread::sleep(DRAIN_DELAY);
+    Ok(())
+}
+
+/// 优先选用能以 `sample_rate` 播放的配置（通道最少的那个），没有时使用设备默认配置
+fn playback_config(device: &cpal::Device, sample_rate: u32) -> anyhow::Result<SupportedStreamConfig> {
+    let exact = device
+        .supported_output_configs()
+        .ok()
+        .into_iter()
+        .flatten()
+        .filter(|c| matches!(c.sample_format(), SampleFormat::F32 | SampleFormat::I16 | SampleFormat::U16))
+        .filter(|c| (c.min_sample_rate().0..=c.max_sample_rate().0).contains(&sample_rate))
+        .min_by_key(|c| c.channels());
+    match exact {
+        Some(range) => Ok(range.with_sample_rate(SampleRate(sample_rate))),
+        None => device.default_output_config().context("failed to query the default output config"),
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    samples: Arc<Vec<f32>>,
+    position: Arc<AtomicUsize>,
+) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut next = position.load(Ordering::Relaxed);
+            for frame in data.chunks_mut(channels) {
+                // 播完之后输出静音
+                let value = samples.get(next).copied().unwrap_or(0.0);
+                next = (next + 1).min(samples.len());
+                frame.fill(T::from_sample(value));
+            }
+            position.store(next, Ordering::Relaxed);
+        },
+        |err| eprintln!("An error occurred on the playback stream: {}", err),
+        None,
+    )
+}
@@ -0,0 +1,201 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const FRAMES: &[char] = &['|', '/', '-', '\\'];
+
+/// 打印状态信息（进度、保存位置等非结果内容）；`--raw` 模式下改写到 stderr，保证 stdout 上只有结果
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::progress::raw_enabled() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// 打印只在 `-v/--verbose` 下需要的诊断信息（例如每个翻译片段的处理步骤），写到 stderr，不会混入结果
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::progress::verbose_enabled() {
+            eprintln!($($arg)*)
+        }
+    };
+}
+
+/// 是否输出 `--progress-json` 进度事件
+static JSON_PROGRESS: AtomicBool = AtomicBool::new(false);
+/// 是否处于 `--raw` 模式：stdout 只输出结果，状态信息全部写到 stderr
+static RAW_OUTPUT: AtomicBool = AtomicBool::new(false);
+/// 是否输出 `-v/--verbose` 的诊断信息
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+/// 当前处理的文件，写入进度事件的 `file` 字段
+static CURRENT_FILE: Mutex<Option<String>> = Mutex::new(None);
+
+/// 开启或关闭 JSON 进度事件，只在启动时调用一次
+pub fn set_json(enabled: bool) {
+    JSON_PROGRESS.store(enabled, Ordering::SeqCst);
+}
+
+/// 是否需要输出 JSON 进度事件
+pub fn json_enabled() -> bool {
+    JSON_PROGRESS.load(Ordering::SeqCst)
+}
+
+/// 开启或关闭 `--raw` 模式，在输出任何状态信息之前调用
+pub fn set_raw(enabled: bool) {
+    RAW_OUTPUT.store(enabled, Ordering::SeqCst);
+}
+
+/// 状态信息是否需要写到 stderr
+pub fn raw_enabled() -> bool {
+    RAW_OUTPUT.load(Ordering::SeqCst)
+}
+
+/// 开启或关闭 [`verbose!`] 诊断信息，只在启动时调用一次
+pub fn set_verbose(enabled: bool) {
+    VERBOSE.store(enabled, Ordering::SeqCst);
+}
+
+/// 是否输出 [`verbose!`] 诊断信息
+pub fn verbose_enabled() -> bool {
+    VERBOSE.load(Ordering::SeqCst)
+}
+
+/// 设置之后进度事件中的 `file` 字段，`None` 表示不针对某个文件
+pub fn set_current_file(file: Option<&str>) {
+    *CURRENT_FILE.lock().unwrap() = file.map(str::to_string);
+}
+
+/// 报告当前文件在某个阶段（`transcribe`、`translate` 等）的进度，`percent` 未知时为 `None`
+pub fn report(stage: &str, percent: Option<f64>) {
+    if json_enabled() {
+        let file = CURRENT_FILE.lock().unwrap().clone();
+        emit(stage, percent, file.as_deref());
+    }
+}
+
+/// 在 stderr 上输出一行 JSON 进度事件，例如 `{"stage":"transcribe","percent":42.0,"file":"a.wav"}`
+fn emit(stage: &str, percent: Option<f64>, file: Option<&str>) {
+    let event = serde_json::json!({
+        "stage": stage,
+        "percent": percent.map(|p| (p.clamp(0.0, 100.0) * 10.0).round() / 10.0),
+        "file": file,
+    });
+    let mut stderr = io::stderr().lock();
+    let _ = writeln!(stderr, "{}", event);
+    let _ = stderr.flush();
+}
+
+/// 按字节数统计进度，可在多个线程间共享（例如分段并发下载）。
+///
+/// 百分比每变化 1% 输出一次事件；总量未知时每 1 MiB 输出一次，`percent` 为 `null`。
+pub struct ByteProgress {
+    stage: &'static str,
+    file: String,
+    total: Option<u64>,
+    done: AtomicU64,
+    last_step: AtomicU64,
+}
+
+impl ByteProgress {
+    pub fn new(stage: &'static str, file: &str, total: Option<u64>) -> Self {
+        Self { stage, file: file.to_string(), total, done: AtomicU64::new(0), last_step: AtomicU64::new(u64::MAX) }
+    }
+
+    pub fn add(&self, bytes: u64) {
+        if !json_enabled() {
+            return;
+        }
+        let done = self.done.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        let (step, percent) = match self.total {
+            Some(total) if total > 0 => (done * 100 / total, Some(done as f64 * 100.0 / total as f64)),
+            _ => (done >> 20, None),
+        };
+        if self.last_step.swap(step, Ordering::SeqCst) != step {
+            emit(self.stage, percent, Some(&self.file));
+        }
+    }
+
+    /// 包装一个写入器，写入的字节数计入进度
+    pub fn writer<W: Write>(&self, inner: W) -> ProgressWriter<'_, W> {
+        ProgressWriter { inner, progress: self }
+    }
+}
+
+/// 见 [`ByteProgress::writer`]
+pub struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: &'a ByteProgress,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.progress.add(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 在 stderr 上显示的简单旋转指示器，用于模型加载、推理等耗时步骤。
+///
+/// `enabled` 为 false、stderr 不是终端或开启了 `--progress-json` 时不输出任何内容。
+pub struct Spinner {
+    message: Arc<Mutex<String>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str, enabled: bool) -> Self {
+        let message = Arc::new(Mutex::new(message.to_string()));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = (enabled && std::io::stderr().is_terminal() && !json_enabled()).then(|| {
+            let message = message.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut frame = 0;
+                while running.load(Ordering::SeqCst) {
+                    let text = message.lock().unwrap().clone();
+                    eprint!("\r\x1b[2K{} {}", FRAMES[frame % FRAMES.len()], text);
+                    let _ = std::io::stderr().flush();
+                    frame += 1;
+                    thread::sleep(Duration::from_millis(100));
+                }
+                eprint!("\r\x1b[2K");
+                let _ = std::io::stderr().flush();
+            })
+        });
+        Self { message, running, handle }
+    }
+
+    /// 更新显示的文字，例如当前的进度
+    pub fn set_message(&self, message: impl Into<String>) {
+        *self.message.lock().unwrap() = message.into();
+    }
+
+    /// 停止并清除指示器
+    pub fn stop(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{bail, Context};
+use regex::Regex;
+use std::path::Path;
+
+/// 规则文件中分隔查找内容和替换内容的记号
+const SEPARATOR: &str = "=>";
+/// 以此开头的查找内容按正则表达式处理
+const REGEX_PREFIX: &str = "re:";
+
+/// `--replace` 的译后替换规则，按文件中的顺序依次应用到每段译文上，用来修正模型反复出现的固定误译。
+///
+/// 规则文件每行一条 `查找=>替换`，空行和以 `#` 开头的行被忽略；查找内容以 `re:` 开头时按正则表达式匹配，
+/// 替换内容中可以用 `$1`、`${name}` 引用分组。查找内容两侧的空白会被去掉，替换内容保持原样（可以替换为空）。
+#[derive(Debug)]
+pub struct Replacements {
+    rules: Vec<(Pattern, String)>,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Replacements {
+    /// 读取规则文件，格式错误或正则表达式无效时返回带行号的错误
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("invalid replacement rules in {}", path.display()))
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_start_matches('\u{feff}');
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let Some((find, replace)) = line.split_once(SEPARATOR) else {
+                bail!("line {}: expected `find{}replace`, got '{}'", index + 1, SEPARATOR, line);
+            };
+            let find = find.trim();
+            let pattern = match find.strip_prefix(REGEX_PREFIX) {
+                Some(regex) => Pattern::Regex(
+                    Regex::new(regex).with_context(|| format!("line {}: invalid regular expression", index + 1))?,
+                ),
+                None => Pattern::Literal(find.to_string()),
+            };
+            if matches!(&pattern, Pattern::Literal(find) if find.is_empty()) {
+                bail!("line {}: the text to find is empty", index + 1);
+            }
+            rules.push((pattern, replace.to_string()));
+        }
+        Ok(Self { rules })
+    }
+
+    /// 依次应用所有规则
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (pattern, replace) in &self.rules {
+            text = match pattern {
+                Pattern::Literal(find) => text.replace(find.as_str(), replace),
+                Pattern::Regex(regex) => regex.replace_all(&text, replace.as_str()).into_owned(),
+            };
+        }
+        text
+    }
+
+    /// 规则条数
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+}
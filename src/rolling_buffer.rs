@@ -0,0 +1,45 @@
+use crate::audio_capture::AudioCapture;
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use std::sync::{Arc, Mutex};
+
+/// 最近一段录音的环形缓冲区：只保留最后 `keep_seconds` 秒的 16-bit 单声道采样，更早的采样被覆盖。
+///
+/// 通过 [`AudioCapture::set_on_buffer`] 接在采集流后面，内存占用固定（16kHz 下每秒 32KB）；
+/// 需要时用 [`RollingBuffer::snapshot`] 取出当前内容交给 Whisper，而不必持续转写全部音频。
+#[derive(Clone)]
+pub struct RollingBuffer {
+    ring: Arc<Mutex<AllocRingBuffer<i16>>>,
+    sample_rate: u32,
+}
+
+impl RollingBuffer {
+    /// 容纳 `sample_rate` 下 `keep_seconds` 秒采样的空缓冲区
+    pub fn new(keep_seconds: f32, sample_rate: u32) -> Self {
+        let capacity = ((keep_seconds * sample_rate as f32).ceil() as usize).max(1);
+        Self { ring: Arc::new(Mutex::new(AllocRingBuffer::new(capacity))), sample_rate }
+    }
+
+    /// 把采集到的每个缓冲区追加进来；采集的输出采样率应与 [`RollingBuffer::sample_rate`] 一致，需在 [`AudioCapture::start`] 之前调用
+    pub fn attach(&self, capture: &mut AudioCapture) {
+        let ring = self.ring.clone();
+        capture.set_on_buffer(move |pcm| {
+            let mut ring = ring.lock().unwrap();
+            for &sample in pcm {
+                ring.push(sample);
+            }
+        });
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// 复制出最近 `last_secs` 秒（`None` 表示缓冲区中的全部）的采样，归一化为 [-1.0, 1.0) 的 f32，
+    /// 可以直接交给 [`crate::audio_transcribe::Whisper::transcribe_samples_with`]；采集在复制期间照常进行
+    pub fn snapshot(&self, last_secs: Option<f32>) -> Vec<f32> {
+        let ring = self.ring.lock().unwrap();
+        let wanted = last_secs.map(|secs| (secs * self.sample_rate as f32) as usize).unwrap_or(ring.len());
+        let skip = ring.len().saturating_sub(wanted);
+        ring.iter().skip(skip).map(|&s| s as f32 / 32768.0).collect()
+    }
+}
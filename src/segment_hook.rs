@@ -0,0 +1,146 @@
+use crate::audio_transcribe::Segment;
+use serde_json::json;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// `--segment-callback-script`：每解码出一段就运行一次用户给出的命令。
+///
+/// 命令在单独的线程中按顺序逐个运行，段落经无界队列送过去，Whisper 的解码线程只负责入队，
+/// 不会被运行缓慢或卡住的命令阻塞。丢弃时等待队列中已有的段落都处理完。
+///
+/// 调用约定：命令交给系统 shell 执行（`sh -c` / `cmd /C`），每段一次；
+/// 段落以一行 JSON `{"start_ms":…,"end_ms":…,"text":"…"}` 写入命令的标准输入，
+/// 同时通过环境变量 `AUDIO_TRANS_VOX_SEGMENT_START_MS`、`AUDIO_TRANS_VOX_SEGMENT_END_MS`、
+/// `AUDIO_TRANS_VOX_SEGMENT_TEXT` 提供；命令的标准输出转到本程序的 stderr，不会混入转写结果。
+#[derive(Debug)]
+pub struct SegmentHook {
+    sender: Sender<Option<Segment>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// 把段落送入后台队列的句柄，可以随 [`crate::audio_transcribe::TranscribeOptions`] 克隆。
+///
+/// 队列中的 `None` 表示不再有段落，由队列的所有者在丢弃或结束时发送。
+#[derive(Debug, Clone)]
+pub struct SegmentSender(Queue);
+
+#[derive(Debug, Clone)]
+enum Queue {
+    /// 无界队列，送入时从不阻塞
+    Unbounded(Sender<Option<Segment>>),
+    /// 有界队列，满了之后送入会阻塞，让解码等待消费者（背压）
+    Bounded(SyncSender<Option<Segment>>),
+}
+
+impl SegmentSender {
+    /// 送入无界队列 `sender` 的句柄
+    pub fn unbounded(sender: Sender<Option<Segment>>) -> Self {
+        Self(Queue::Unbounded(sender))
+    }
+
+    /// 送入有界队列 `sender` 的句柄
+    pub fn bounded(sender: SyncSender<Option<Segment>>) -> Self {
+        Self(Queue::Bounded(sender))
+    }
+
+    /// 把段落加入队列；无界队列立即返回，有界队列已满时等到有空位为止
+    pub fn send(&self, segment: Segment) {
+        let _ = match &self.0 {
+            Queue::Unbounded(sender) => sender.send(Some(segment)),
+            Queue::Bounded(sender) => sender.send(Some(segment)),
+        };
+    }
+}
+
+impl SegmentHook {
+    /// 启动运行 `command` 的后台线程
+    pub fn spawn(command: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let command = command.to_string();
+        let worker = thread::spawn(move || run_queue(&command, receiver));
+        Self { sender, worker: Some(worker) }
+    }
+
+    pub fn sender(&self) -> SegmentSender {
+        SegmentSender::unbounded(self.sender.clone())
+    }
+}
+
+impl Drop for SegmentHook {
+    fn drop(&mut self) {
+        // `None` 排在已入队的段落之后，后台线程处理完它们才会退出
+        let _ = self.sender.send(None);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_queue(command: &str, receiver: Receiver<Option<Segment>>) {
+    while let Ok(Some(segment)) = receiver.recv() {
+        if let Err(e) = run_once(command, &segment) {
+            eprintln!("Warning: segment callback script failed: {}", e);
+        }
+    }
+}
+
+/// 为一个段落运行一次命令并等待其结束
+fn run_once(command: &str, segment: &Segment) -> std::io::Result<()> {
+    let mut child = shell(command)
+        .env("AUDIO_TRANS_VOX_SEGMENT_START_MS", segment.start_ms.to_string())
+        .env("AUDIO_TRANS_VOX_SEGMENT_END_MS", segment.end_ms.to_string())
+        .env("AUDIO_TRANS_VOX_SEGMENT_TEXT", segment.text.trim())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::from(std::io::stderr()))
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let line = json!({ "start_ms": segment.start_ms, "end_ms": segment.end_ms, "text": segment.text.trim() });
+        // 命令不读标准输入就退出时写入会失败，这不算错误
+        let _ = writeln!(stdin, "{}", line);
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        eprintln!("Warning: segment callback script exited with {}", status);
+    }
+    Ok(())
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start_ms: i64, text: &str) -> Segment {
+        Segment { start_ms, end_ms: start_ms + 1000, text: text.to_string(), words: Vec::new(), speaker: None, language: None, confidence: None }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn drop_runs_the_command_for_every_queued_segment() {
+        let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_hook.txt", std::process::id()));
+        let command = format!(
+            "sleep 0.05; printf '%s %s ' \"$AUDIO_TRANS_VOX_SEGMENT_START_MS\" \"$AUDIO_TRANS_VOX_SEGMENT_TEXT\" >> '{}'; cat >> '{}'",
+            path.display(),
+            path.display()
+        );
+        let hook = SegmentHook::spawn(&command);
+        let sender = hook.sender();
+        sender.send(segment(0, " first "));
+        sender.send(segment(1000, "second"));
+        drop(hook);
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            output,
+            "0 first {\"end_ms\":1000,\"start_ms\":0,\"text\":\"first\"}\n1000 second {\"end_ms\":2000,\"start_ms\":1000,\"text\":\"second\"}\n"
+        );
+    }
+}
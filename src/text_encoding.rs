@@ -0,0 +1,211 @@
+use anyhow::Context;
+use chardetng::EncodingDetector;
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_8};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+
+/// 判断编码时读取的文件开头字节数，其余内容边读边解码
+const DETECT_PREFIX_LEN: usize = 64 * 1024;
+
+/// 解析 `--input-encoding` 给出的编码名称，接受 WHATWG 标签，例如 `utf-8`、`gbk`、`gb18030`、`big5`、`utf-16le`
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("unknown encoding '{}'; try utf-8, gbk, gb18030, big5, shift_jis or utf-16le", label))
+}
+
+/// 打开文本文件，返回按行读取 UTF-8 文本的流式解码器。
+///
+/// 编码只根据文件开头的 [`DETECT_PREFIX_LEN`] 字节确定，顺序为：开头的 BOM；`encoding`（`--input-encoding`）；
+/// 开头是合法的 UTF-8 时为 UTF-8；否则由 chardetng 猜测（例如旧版中文编辑器保存的 GBK），并提示实际使用的编码。
+/// 之后整个文件按该编码逐块解码，不会一次读入内存。遇到无法解码的字节时不做替换，
+/// 读取返回 [`io::ErrorKind::InvalidData`] 错误并指出其所在的字节偏移和行号。
+pub fn open_text(path: &Path, encoding: Option<&'static Encoding>) -> anyhow::Result<impl BufRead> {
+    let mut file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut prefix = Vec::with_capacity(DETECT_PREFIX_LEN);
+    file.by_ref()
+        .take(DETECT_PREFIX_LEN as u64)
+        .read_to_end(&mut prefix)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    let (encoding, bom_len) = detect(&prefix, encoding);
+    let mut prefix = Cursor::new(prefix);
+    prefix.set_position(bom_len as u64);
+    Ok(DecodingReader::new(BufReader::new(prefix.chain(file)), encoding, bom_len as u64))
+}
+
+/// 根据文件开头的字节确定编码，返回编码和 BOM 的长度
+fn detect(prefix: &[u8], encoding: Option<&'static Encoding>) -> (&'static Encoding, usize) {
+    // 不足 DETECT_PREFIX_LEN 时已经读到了文件末尾
+    let whole_file = prefix.len() < DETECT_PREFIX_LEN;
+    match (Encoding::for_bom(prefix), encoding) {
+        (Some((encoding, bom_len)), _) => (encoding, bom_len),
+        (None, Some(encoding)) => (encoding, 0),
+        (None, None) if is_utf8_prefix(prefix, whole_file) => (UTF_8, 0),
+        (None, None) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(prefix, whole_file);
+            let guess = detector.guess(None, false);
+            status!("Input is not valid UTF-8, decoding it as {} (pass --input-encoding to override)", guess.name());
+            (guess, 0)
+        }
+    }
+}
+
+/// `prefix` 是合法的 UTF-8；只截取了文件开头时，允许末尾是被截断的不完整字符
+fn is_utf8_prefix(prefix: &[u8], whole_file: bool) -> bool {
+    match std::str::from_utf8(prefix) {
+        Ok(_) => true,
+        Err(e) => !whole_file && e.error_len().is_none(),
+    }
+}
+
+/// 把按某种编码存储的字节流逐块解码为 UTF-8 的读取器，见 [`open_text`]
+struct DecodingReader<R> {
+    inner: R,
+    encoding: &'static Encoding,
+    decoder: Decoder,
+    /// 已解码、尚未被读走的 UTF-8 文本
+    decoded: String,
+    pos: usize,
+    /// 已送入解码器的字节在文件中的结束偏移
+    offset: u64,
+    /// 已解码的换行数，用于报告出错的行号
+    lines: usize,
+    finished: bool,
+}
+
+impl<R: BufRead> DecodingReader<R> {
+    /// `offset` 为 `inner` 的第一个字节在文件中的偏移（跳过的 BOM 长度）
+    fn new(inner: R, encoding: &'static Encoding, offset: u64) -> Self {
+        Self {
+            inner,
+            encoding,
+            decoder: encoding.new_decoder_without_bom_handling(),
+            decoded: String::new(),
+            pos: 0,
+            offset,
+            lines: 0,
+            finished: false,
+        }
+    }
+
+    /// 从 `inner` 取下一块字节解码到 `decoded`，读到末尾时冲刷解码器
+    fn decode_next(&mut self) -> io::Result<()> {
+        self.decoded.clear();
+        self.pos = 0;
+        let input = self.inner.fill_buf()?;
+        let last = input.is_empty();
+        let mut read = 0;
+        loop {
+            let (result, consumed) = self.decoder.decode_to_string_without_replacement(&input[read..], &mut self.decoded, last);
+            read += consumed;
+            match result {
+                DecoderResult::InputEmpty => break,
+                DecoderResult::OutputFull => {
+                    let needed = self.decoder.max_utf8_buffer_length_without_replacement(input.len() - read);
+                    self.decoded.reserve(needed.unwrap_or(input.len() - read).max(16));
+                }
+                DecoderResult::Malformed(bad, extra) => {
+                    // 已读取的字节包含非法序列本身以及其后的 `extra` 个字节；非法序列可能从上一块开始
+                    let end = read - extra as usize;
+                    let start = end.saturating_sub(bad as usize);
+                    let offset = self.offset + end as u64 - bad as u64;
+                    let line = self.lines + self.decoded.matches('\n').count() + 1;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "invalid {} byte sequence {:02X?} at byte {} (line {}); pass --input-encoding with the file's actual encoding",
+                            self.encoding.name(),
+                            &input[start..end],
+                            offset,
+                            line
+                        ),
+                    ));
+                }
+            }
+        }
+        self.inner.consume(read);
+        self.offset += read as u64;
+        self.lines += self.decoded.matches('\n').count();
+        self.finished = last;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for DecodingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // 一块输入可能只含半个字符而解码不出任何文本，继续读下一块
+        while self.pos == self.decoded.len() && !self.finished {
+            self.decode_next()?;
+        }
+        Ok(&self.decoded.as_bytes()[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.decoded.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, UTF_16LE};
+
+    fn decode_all(bytes: &[u8], encoding: &'static Encoding, chunk: usize) -> io::Result<String> {
+        let mut text = String::new();
+        DecodingReader::new(BufReader::with_capacity(chunk, bytes), encoding, 0).read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn decodes_characters_split_across_chunks() {
+        let (gbk, _, _) = GBK.encode("第一行\n第二行\n");
+        for chunk in 1..8 {
+            assert_eq!(decode_all(&gbk, GBK, chunk).unwrap(), "第一行\n第二行\n");
+        }
+    }
+
+    #[test]
+    fn reports_offset_and_line_of_malformed_bytes() {
+        let bytes = b"ok\nfine\nbad \xff here\n";
+        let error = decode_all(bytes, UTF_8, 4).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("[FF] at byte 12 (line 3)"), "{}", error);
+    }
+
+    #[test]
+    fn detects_bom_before_explicit_encoding() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("hi".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(detect(&bytes, Some(GBK)), (UTF_16LE, 2));
+        assert_eq!(detect(b"plain", Some(GBK)), (GBK, 0));
+        assert_eq!(detect("纯文本".as_bytes(), None), (UTF_8, 0));
+    }
+
+    #[test]
+    fn accepts_utf8_prefix_cut_inside_a_character() {
+        let text = "中文".as_bytes();
+        assert!(is_utf8_prefix(&text[..4], false));
+        assert!(!is_utf8_prefix(&text[..4], true));
+    }
+
+    #[test]
+    fn open_text_skips_the_bom() {
+        let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_bom.txt", std::process::id()));
+        std::fs::write(&path, b"\xEF\xBB\xBFhello\nworld\n").unwrap();
+        let lines: Vec<String> = open_text(&path, None).unwrap().lines().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines, ["hello", "world"]);
+    }
+}
@@ -0,0 +1,552 @@
+use crate::audio_transcribe::Segment;
+use serde_json::json;
+use std::path::Path;
+
+/// 转写结果的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// 纯文本，每段一行
+    #[default]
+    Txt,
+    /// SubRip 字幕，每段一条
+    Srt,
+    /// WebVTT 字幕，每段一条
+    Vtt,
+    /// JSON 数组，每段一个对象
+    Json,
+    /// CSV 表格，每段一行
+    Csv,
+}
+
+impl OutputFormat {
+    /// 该格式对应的文件扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Txt => "txt",
+            OutputFormat::Srt => "srt",
+            OutputFormat::Vtt => "vtt",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        }
+    }
+
+    /// 供 `--list-formats` 打印的简短说明
+    pub fn description(self) -> &'static str {
+        match self {
+            OutputFormat::Txt => "plain text, one line per segment",
+            OutputFormat::Srt => "SubRip subtitles, one cue per segment",
+            OutputFormat::Vtt => "WebVTT subtitles, one cue per segment",
+            OutputFormat::Json => "JSON array of {start_ms, end_ms, text, speaker, language, confidence} objects",
+            OutputFormat::Csv => "CSV with start_ms,end_ms,speaker,language,text columns",
+        }
+    }
+
+    /// 按格式渲染段落
+    pub fn render(self, segments: &[Segment]) -> String {
+        match self {
+            OutputFormat::Txt => to_text(segments),
+            OutputFormat::Srt => to_srt(segments),
+            OutputFormat::Vtt => to_vtt(segments),
+            OutputFormat::Json => to_json(segments),
+            OutputFormat::Csv => to_csv(segments),
+        }
+    }
+}
+
+/// 转写文本的可选清理规则（`--cleanup`），默认全部关闭
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Cleanup {
+    /// 合并连续的空白并去掉首尾空白
+    Spaces,
+    /// 把 `. . .`、`....`、`。。。`、`…` 等写法统一为 `...`
+    Ellipsis,
+    /// 去掉中日韩全角标点前后多余的空格，例如 `你好 ， 世界` -> `你好，世界`
+    CjkPunct,
+}
+
+/// 段落的说话人与语言标签，例如 `[Speaker 1] [en] `，没有时为空
+fn segment_tags(segment: &Segment) -> String {
+    let mut tags = String::new();
+    if let Some(speaker) = segment.speaker {
+        tags.push_str(&format!("[Speaker {}] ", speaker));
+    }
+    if let Some(language) = &segment.language {
+        tags.push_str(&format!("[{}] ", language));
+    }
+    tags
+}
+
+/// 将段落拼接为纯文本，每个段落占一行；做过说话人分离时行首带 `[Speaker N]` 标签，
+/// 逐块检测语言时再带上 `[语言代码]` 标签
+pub fn to_text(segments: &[Segment]) -> String {
+    let mut result = String::new();
+    for segment in segments {
+        result.push_str(&segment_tags(segment));
+        result.push_str(&segment.text);
+        result.push('\n');
+    }
+    result
+}
+
+/// 拼接逐段译文，每段一行；`keep_source` 为真时每段译文上方保留原文
+pub fn to_translated_text(sources: &[&str], translations: &[String], keep_source: bool) -> String {
+    let mut result = String::new();
+    for (source, translation) in sources.iter().zip(translations) {
+        if keep_source {
+            result.push_str(source);
+            result.push('\n');
+        }
+        result.push_str(translation);
+        result.push('\n');
+    }
+    result
+}
+
+/// 将词级时间戳输出为 SRT，每个词一条字幕，适合卡拉 OK 式的逐词高亮
+pub fn to_word_srt(segments: &[Segment]) -> String {
+    let mut result = String::new();
+    let words = segments.iter().flat_map(|s| &s.words);
+    for (index, word) in words.enumerate() {
+        result.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_srt_timestamp(word.start_ms),
+            format_srt_timestamp(word.end_ms),
+            word.text.trim()
+        ));
+    }
+    result
+}
+
+/// 将段落输出为 SRT，每段一条字幕
+pub fn to_srt(segments: &[Segment]) -> String {
+    let mut result = String::new();
+    for (index, segment) in segments.iter().enumerate() {
+        result.push_str(&format!(
+            "{}\n{} --> {}\n{}{}\n\n",
+            index + 1,
+            format_srt_timestamp(segment.start_ms),
+            format_srt_timestamp(segment.end_ms),
+            segment_tags(segment),
+            segment.text.trim()
+        ));
+    }
+    result
+}
+
+/// 将段落输出为 WebVTT，每段一条字幕
+pub fn to_vtt(segments: &[Segment]) -> String {
+    let mut result = String::from("WEBVTT\n\n");
+    for segment in segments {
+        result.push_str(&format!(
+            "{} --> {}\n{}{}\n\n",
+            format_srt_timestamp(segment.start_ms).replace(',', "."),
+            format_srt_timestamp(segment.end_ms).replace(',', "."),
+            segment_tags(segment),
+            segment.text.trim()
+        ));
+    }
+    result
+}
+
+/// 一个段落的 JSON 对象，未做说话人分离、语言检测或无法计算置信度时对应字段为 `null`
+///
+/// 段落带有词级时间戳（`--word-timestamps`）时另有 `words` 数组，每个词为 `{start_ms, end_ms, text}`。
+pub fn segment_json(s: &Segment) -> serde_json::Value {
+    let mut value = json!({
+        "start_ms": s.start_ms,
+        "end_ms": s.end_ms,
+        "text": s.text.trim(),
+        "speaker": s.speaker,
+        "language": s.language,
+        "confidence": s.confidence,
+    });
+    if !s.words.is_empty() {
+        value["words"] = s
+            .words
+            .iter()
+            .map(|w| json!({ "start_ms": w.start_ms, "end_ms": w.end_ms, "text": w.text.trim() }))
+            .collect();
+    }
+    value
+}
+
+/// 将段落输出为 JSON 数组，每段一个对象，字段见 [`segment_json`]
+pub fn to_json(segments: &[Segment]) -> String {
+    let entries: Vec<_> = segments.iter().map(segment_json).collect();
+    let mut result = serde_json::to_string_pretty(&entries).expect("Failed to serialize segments");
+    result.push('\n');
+    result
+}
+
+/// `--jsonl` 中一个输入文件的记录：文件路径、语言、全文（各段以换行连接）和各段落
+pub fn jsonl_record(path: &Path, language: Option<&str>, segments: &[Segment]) -> serde_json::Value {
+    let text: Vec<&str> = segments.iter().map(|s| s.text.trim()).collect();
+    json!({
+        "path": path.display().to_string(),
+        "language": language,
+        "text": text.join("\n"),
+        "segments": segments.iter().map(segment_json).collect::<Vec<_>>(),
+    })
+}
+
+/// `--jsonl` 中转写失败的输入文件的记录：文件路径和错误信息
+pub fn jsonl_error_record(path: &Path, error: &anyhow::Error) -> serde_json::Value {
+    json!({
+        "path": path.display().to_string(),
+        "error": format!("{:#}", error),
+    })
+}
+
+/// 将段落输出为带表头的 CSV，文本按 RFC 4180 加引号转义
+pub fn to_csv(segments: &[Segment]) -> String {
+    let mut result = String::from("start_ms,end_ms,speaker,language,text\n");
+    for segment in segments {
+        result.push_str(&format!(
+            "{},{},{},{},\"{}\"\n",
+            segment.start_ms,
+            segment.end_ms,
+            segment.speaker.map(|s| s.to_string()).unwrap_or_default(),
+            segment.language.as_deref().unwrap_or_default(),
+            segment.text.trim().replace('"', "\"\"")
+        ));
+    }
+    result
+}
+
+/// 将毫秒格式化为 SRT 时间戳 `HH:MM:SS,mmm`
+fn format_srt_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!("{:02}:{:02}:{:02},{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+/// 按首次出现的顺序列出各段落标注的语言
+pub fn languages(segments: &[Segment]) -> Vec<&str> {
+    let mut languages: Vec<&str> = Vec::new();
+    for language in segments.iter().filter_map(|s| s.language.as_deref()) {
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
+    languages
+}
+
+/// 丢弃置信度低于 `min_confidence` 的段落，没有置信度的段落保留。
+///
+/// 在渲染任何输出格式之前统一调用，保证各种格式的筛选结果一致。
+pub fn filter_by_confidence(segments: Vec<Segment>, min_confidence: f32) -> Vec<Segment> {
+    segments
+        .into_iter()
+        .filter(|s| s.confidence.is_none_or(|confidence| confidence >= min_confidence))
+        .collect()
+}
+
+/// 按 `rules` 清理每个段落的文本，渲染任何输出格式或送去翻译之前调用
+pub fn clean_segments(segments: &mut [Segment], rules: &[Cleanup]) {
+    if rules.is_empty() {
+        return;
+    }
+    for segment in segments {
+        segment.text = clean_text(&segment.text, rules);
+    }
+}
+
+/// 按固定顺序（省略号、全角标点空格、空白）应用启用的清理规则
+pub fn clean_text(text: &str, rules: &[Cleanup]) -> String {
+    let mut text = text.to_string();
+    if rules.contains(&Cleanup::Ellipsis) {
+        text = normalize_ellipses(&text);
+    }
+    if rules.contains(&Cleanup::CjkPunct) {
+        text = strip_spaces_around_cjk_punct(&text);
+    }
+    if rules.contains(&Cleanup::Spaces) {
+        text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    text
+}
+
+/// 把由 `.`、`。`、`…`（可夹杂空格）组成、相当于至少三个点的连续片段替换为 `...`
+fn normalize_ellipses(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        // 从当前位置向后找一段点号，点号之间允许单个空格
+        let (mut end, mut dots, mut last_dot) = (i, 0, i);
+        while end < chars.len() {
+            match chars[end] {
+                '.' | '。' => dots += 1,
+                '…' => dots += 3,
+                ' ' if dots > 0 && end + 1 < chars.len() && matches!(chars[end + 1], '.' | '。' | '…') => {}
+                _ => break,
+            }
+            if chars[end] != ' ' {
+                last_dot = end;
+            }
+            end += 1;
+        }
+        if dots >= 3 {
+            result.push_str("...");
+            i = last_dot + 1;
+        } else {
+            result.push(chars[i]);
+            i += 1;
+        }
+    }
+    result
+}
+
+/// 中日韩全角标点
+fn is_cjk_punct(c: char) -> bool {
+    matches!(c, '，' | '。' | '、' | '！' | '？' | '；' | '：' | '「' | '」' | '『' | '』' | '（' | '）' | '《' | '》')
+}
+
+/// 去掉紧挨在全角标点前后的空白
+fn strip_spaces_around_cjk_punct(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_whitespace() {
+            let before = chars[..i].iter().rev().find(|c| !c.is_whitespace());
+            let after = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if before.is_some_and(|&c| is_cjk_punct(c)) || after.is_some_and(|&c| is_cjk_punct(c)) {
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// 只保留与 `[start_ms, end_ms)` 时间范围有重叠的段落
+pub fn segments_in_range(segments: &[Segment], start_ms: i64, end_ms: i64) -> Vec<Segment> {
+    segments
+        .iter()
+        .filter(|s| s.end_ms > start_ms && s.start_ms < end_ms)
+        .cloned()
+        .collect()
+}
+
+/// 合并相邻两块重叠转写的结果，去掉重叠区 `[overlap_start_ms, overlap_end_ms)` 中重复识别的内容。
+///
+/// 以重叠区中点为界：前一块保留中心点在界线之前的段落，后一块保留其余段落；后一块中与前一块
+/// 保留段落时间重叠且文本相同（忽略大小写和标点）的段落视为重复丢弃。跨过界线的两段如果
+/// 首尾有至少两个相同的词（同一句话在两块中各识别了一部分），去掉后一段开头重复的词。
+pub fn merge_overlapping(left: Vec<Segment>, right: Vec<Segment>, overlap_start_ms: i64, overlap_end_ms: i64) -> Vec<Segment> {
+    if overlap_end_ms <= overlap_start_ms {
+        let mut merged = left;
+        merged.extend(right);
+        return merged;
+    }
+    let cut_ms = (overlap_start_ms + overlap_end_ms) / 2;
+    let center = |s: &Segment| (s.start_ms + s.end_ms) / 2;
+
+    let mut merged: Vec<Segment> = left
+        .into_iter()
+        .filter(|s| s.start_ms < overlap_start_ms || center(s) < cut_ms)
+        .collect();
+    let kept_left = merged.iter().rposition(|s| s.end_ms <= overlap_start_ms).map_or(0, |i| i + 1);
+    for mut segment in right {
+        if segment.end_ms <= overlap_end_ms && center(&segment) < cut_ms {
+            continue;
+        }
+        let duplicate = merged[kept_left..].iter().any(|kept| {
+            kept.end_ms > segment.start_ms && kept.start_ms < segment.end_ms && normalized_words(&kept.text) == normalized_words(&segment.text)
+        });
+        if duplicate {
+            continue;
+        }
+        if let Some(previous) = merged.last() {
+            if segment.start_ms < overlap_end_ms {
+                trim_repeated_prefix(previous, &mut segment);
+            }
+            // 保持时间戳单调，不与前一段重叠
+            segment.start_ms = segment.start_ms.max(previous.end_ms).min(segment.end_ms);
+        }
+        if !segment.text.trim().is_empty() {
+            merged.push(segment);
+        }
+    }
+    merged
+}
+
+/// 用于比较的词序列：转为小写并去掉标点
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// `previous` 结尾与 `segment` 开头有至少两个相同的词时，去掉 `segment` 开头的这些词
+fn trim_repeated_prefix(previous: &Segment, segment: &mut Segment) {
+    let tail = normalized_words(&previous.text);
+    let words: Vec<&str> = segment.text.split_whitespace().collect();
+    let head: Vec<String> = words.iter().flat_map(|word| normalized_words(word)).collect();
+    if head.len() != words.len() {
+        // 含有纯标点的“词”时无法逐词对应，保持原样
+        return;
+    }
+    let repeated = (2..=tail.len().min(head.len())).rev().find(|&n| tail[tail.len() - n..] == head[..n]);
+    if let Some(n) = repeated {
+        let leading_space = segment.text.starts_with(char::is_whitespace);
+        segment.text = format!("{}{}", if leading_space { " " } else { "" }, words[n..].join(" "));
+        segment.words.retain(|word| word.start_ms >= previous.end_ms);
+    }
+}
+
+/// 解析 `START-END` 形式的时间范围，返回毫秒。
+///
+/// 两端都可以写成秒数（`90`、`12.5`）或 `MM:SS`、`HH:MM:SS`，例如 `1:30-2:45`。
+pub fn parse_time_range(value: &str) -> Result<(i64, i64), String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected START-END, got '{}'", value))?;
+    let start_ms = parse_timestamp_ms(start.trim())?;
+    let end_ms = parse_timestamp_ms(end.trim())?;
+    if end_ms <= start_ms {
+        return Err(format!("range end must be after its start: '{}'", value));
+    }
+    Ok((start_ms, end_ms))
+}
+
+/// 解析 `SS[.fff]`、`MM:SS[.fff]` 或 `HH:MM:SS[.fff]` 为毫秒
+fn parse_timestamp_ms(value: &str) -> Result<i64, String> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        let number: f64 = part
+            .parse()
+            .map_err(|_| format!("invalid timestamp '{}'", value))?;
+        if number < 0.0 {
+            return Err(format!("invalid timestamp '{}'", value));
+        }
+        seconds = seconds * 60.0 + number;
+    }
+    Ok((seconds * 1000.0).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_transcribe::Word;
+
+    fn segment(start_ms: i64, end_ms: i64, text: &str) -> Segment {
+        Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            words: Vec::new(),
+            speaker: None,
+            language: None,
+            confidence: None,
+        }
+    }
+
+    fn texts(segments: &[Segment]) -> Vec<&str> {
+        segments.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn segment_json_lists_words_only_when_present() {
+        let mut s = segment(1000, 2000, " Hello there");
+        assert!(segment_json(&s).get("words").is_none());
+        s.words = vec![
+            Word { start_ms: 1000, end_ms: 1400, text: " Hello".to_string() },
+            Word { start_ms: 1400, end_ms: 2000, text: " there".to_string() },
+        ];
+        assert_eq!(
+            segment_json(&s)["words"],
+            json!([
+                { "start_ms": 1000, "end_ms": 1400, "text": "Hello" },
+                { "start_ms": 1400, "end_ms": 2000, "text": "there" },
+            ])
+        );
+    }
+
+    fn with_confidence(confidence: Option<f32>) -> Segment {
+        Segment { confidence, ..segment(0, 1000, " text") }
+    }
+
+    #[test]
+    fn filter_by_confidence_keeps_segments_at_the_threshold() {
+        let segments = vec![with_confidence(Some(0.5)), with_confidence(Some(0.49)), with_confidence(None)];
+        let kept = filter_by_confidence(segments, 0.5);
+        assert_eq!(kept.iter().map(|s| s.confidence).collect::<Vec<_>>(), [Some(0.5), None]);
+    }
+
+    #[test]
+    fn filter_by_confidence_can_drop_every_segment() {
+        let segments = vec![with_confidence(Some(0.1)), with_confidence(Some(0.2))];
+        assert!(filter_by_confidence(segments, 0.9).is_empty());
+    }
+
+    #[test]
+    fn filter_by_confidence_accepts_empty_input() {
+        assert!(filter_by_confidence(Vec::new(), 0.5).is_empty());
+    }
+
+    #[test]
+    fn cleanup_spaces_collapses_whitespace() {
+        for (input, expected) in [
+            ("  hello   world  ", "hello world"),
+            ("tab\there\nnewline", "tab here newline"),
+            ("你好 ， 世界", "你好 ， 世界"),
+            ("", ""),
+        ] {
+            assert_eq!(clean_text(input, &[Cleanup::Spaces]), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn cleanup_ellipsis_normalizes_dot_runs() {
+        for (input, expected) in [
+            ("wait. . . what", "wait... what"),
+            ("wait.... what", "wait... what"),
+            ("等等。。。好", "等等...好"),
+            ("end…", "end..."),
+            ("version 1.2. Done.", "version 1.2. Done."),
+            ("two.. dots", "two.. dots"),
+        ] {
+            assert_eq!(clean_text(input, &[Cleanup::Ellipsis]), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn cleanup_cjk_punct_strips_spaces_around_full_width_punctuation() {
+        for (input, expected) in [
+            ("你好 ， 世界", "你好，世界"),
+            ("他说 「 好 」 。", "他说「好」。"),
+            ("hello , world", "hello , world"),
+            ("中文 English", "中文 English"),
+        ] {
+            assert_eq!(clean_text(input, &[Cleanup::CjkPunct]), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn merge_overlapping_drops_text_recognized_twice() {
+        let left = vec![segment(0, 5000, " First."), segment(9500, 11500, " Hello world.")];
+        let right = vec![segment(10200, 12500, " hello, world!"), segment(12500, 15000, " Next.")];
+        let merged = merge_overlapping(left, right, 10000, 12000);
+        assert_eq!(texts(&merged), [" First.", " Hello world.", " Next."]);
+    }
+
+    #[test]
+    fn merge_overlapping_trims_partially_repeated_words() {
+        let left = vec![segment(8000, 11000, " we went to the store")];
+        let right = vec![segment(10500, 13000, " to the store and bought milk")];
+        let merged = merge_overlapping(left, right, 10000, 12000);
+        assert_eq!(texts(&merged), [" we went to the store", " and bought milk"]);
+        // 后一段从前一段结束处开始，时间戳保持单调
+        assert_eq!(merged[1].start_ms, 11000);
+    }
+
+    #[test]
+    fn merge_overlapping_keeps_both_sides_without_overlap() {
+        let left = vec![segment(0, 4000, " one two"), segment(4000, 10000, " three four")];
+        let right = vec![segment(10000, 14000, " three four"), segment(14000, 20000, " five")];
+        let merged = merge_overlapping(left.clone(), right.clone(), 10000, 10000);
+        assert_eq!(merged, [left, right].concat());
+    }
+}
@@ -1,9 +1,43 @@
-use anyhow::Error as E;
+use anyhow::{bail, Context, Error as E};
 use candle_core::{Device, Tensor, DType};
 use candle_nn::{Activation, VarBuilder};
 use candle_transformers::generation::LogitsProcessor;
 use candle_transformers::models::marian::{self, MTModel};
+use serde::Serialize;
+use std::io::{BufRead, Write};
 use tokenizers::Tokenizer;
+use crate::progress;
+use crate::replacements::Replacements;
+use crate::translation_cache::TranslationCache;
+
+/// 单次送入模型的最大字符数，过长的行会先按句子切分
+const MAX_CHUNK_CHARS: usize = 400;
+
+/// 用于检查分词器的探测文本，分别应能被英文和中文分词器完整切分，不产生 `<unk>`
+const ENGLISH_PROBE: &str = "The weather is nice today, so let's go for a walk in the park.";
+const CHINESE_PROBE: &str = "今天天气很好，我们去公园散步吧。";
+
+/// `translate` 子命令的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TranslationFormat {
+    /// 纯文本译文，与输入逐行对应
+    #[default]
+    Txt,
+    /// JSON 数组，每个送入模型的片段一个对象，带原文与其在输入中的位置
+    Json,
+}
+
+/// 一个送入模型的源文本片段及其译文。
+///
+/// `start`/`end` 为该片段在原始输入中的字符（Unicode 标量值）偏移，左闭右开；
+/// 输入开头的 BOM 不计入偏移。
+#[derive(Debug, Clone, Serialize)]
+pub struct AlignedChunk {
+    pub start: usize,
+    pub end: usize,
+    pub source: String,
+    pub translation: String,
+}
 
 pub struct Translator {
     model: MTModel,
@@ -11,6 +45,10 @@ pub struct Translator {
     tokenizer: Tokenizer,      // 用于对英文文本进行编码
     tokenizer_dec: Tokenizer,  // 用于对生成的 token 进行解码（中文）
     device: Device,
+    /// 以源文本为键的译文缓存，`None` 表示不缓存
+    cache: Option<TranslationCache>,
+    /// 应用到每段译文上的替换规则（`--replace`），`None` 表示不做替换
+    replacements: Option<Replacements>,
 }
 
 impl Translator {
@@ -20,14 +58,14 @@ impl Translator {
     /// - `en_token` 为英文分词器文件，比如 "opus-mt-en-zh/tokenizer-en.json"。
     /// - `zh_token` 为中文分词器文件，比如 "opus-mt-en-zh/tokenizer-zh.json"。
     pub fn new(model_path: &str, en_token: &str, zh_token: &str) -> anyhow::Result<Self> {
-        println!("Initializing Translator with model_path: {}, en_token: {}, zh_token: {}", model_path, en_token, zh_token);
+        status!("Initializing Translator with model_path: {}, en_token: {}, zh_token: {}", model_path, en_token, zh_token);
 
         // 选择设备，这里使用 CPU，如需 GPU/MPS 可改为 `Device::new_metal(0)` 等
         let device = Device::Cpu;
 
         // 从 safetensors 文件创建 VarBuilder，注意这里使用了 unsafe，
         // 但其含义只是“零拷贝”映射，不会真正不安全。
-        println!("Creating VarBuilder from model_path...");
+        status!("Creating VarBuilder from model_path...");
         let vb = unsafe {
             VarBuilder::from_mmaped_safetensors(&[model_path], DType::F32, &device)?
         };
@@ -57,37 +95,67 @@ impl Translator {
         };
 
         // 分别加载英文分词器和中文分词器
-        println!("Loading English tokenizer from file: {}", en_token);
+        status!("Loading English tokenizer from file: {}", en_token);
         let tokenizer = Tokenizer::from_file(en_token).map_err(E::msg)?;
-        println!("Loading Chinese tokenizer from file: {}", zh_token);
+        status!("Loading Chinese tokenizer from file: {}", zh_token);
         let tokenizer_dec = Tokenizer::from_file(zh_token).map_err(E::msg)?;
+        // 分词器与模型不配套时模型仍能运行，只会输出无意义的译文，因此在这里提前报错
+        check_tokenizer(&tokenizer, en_token, "English", ENGLISH_PROBE, &config)?;
+        check_tokenizer(&tokenizer_dec, zh_token, "Chinese", CHINESE_PROBE, &config)?;
 
         // 创建 Marian 模型
-        println!("Creating Marian model...");
-        let model = MTModel::new(&config, vb)?;
+        status!("Creating Marian model...");
+        let model = MTModel::new(&config, vb)
+            .with_context(|| format!("{} does not match the opus-mt-en-zh model configuration", model_path))?;
 
-        println!("Translator initialized successfully.");
+        status!("Translator initialized successfully.");
         Ok(Self {
             model,
             config,
             tokenizer,
             tokenizer_dec,
             device,
+            cache: None,
+            replacements: None,
         })
     }
 
-    /// 翻译：若文本是英文，则进行翻译；否则原样返回
+    /// 设置译文缓存：相同的源文本直接复用之前的译文，不再送入模型
+    pub fn set_cache(&mut self, cache: Option<TranslationCache>) {
+        self.cache = cache;
+    }
+
+    /// 设置译后替换规则，每段译文（包括从缓存取出的译文）返回前都会经过这些规则；缓存中保存的是替换前的译文
+    pub fn set_replacements(&mut self, replacements: Option<Replacements>) {
+        self.replacements = replacements;
+    }
+
+    /// 按 [`Translator::set_replacements`] 设置的规则修正译文
+    fn replace(&self, translation: String) -> String {
+        match &self.replacements {
+            Some(replacements) => replacements.apply(&translation),
+            None => translation,
+        }
+    }
+
+    /// 翻译：若文本是英文，则进行翻译；否则原样返回。
+    ///
+    /// 每个片段的处理步骤只在 `--verbose` 下写到 stderr，逐行或流式输出译文时 stdout 上只有译文
     pub fn translate(&mut self, text: &str) -> anyhow::Result<String> {
-        println!("Translating text: {}", text);
+        verbose!("Translating text: {}", text);
 
         // 1. 判断是否英文
         if !is_english(text) {
-            println!("Text is not in English, returning original text.");
+            verbose!("Text is not in English, returning original text.");
             return Ok(text.to_string());
         }
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(text)).map(str::to_string) {
+            verbose!("Reusing cached translation.");
+            return Ok(self.replace(cached));
+        }
 
         // 2. 先经过 encoder 编码
-        println!("Encoding text with English tokenizer...");
+        verbose!("Encoding text with English tokenizer...");
         let mut tokens = self
             .tokenizer
             .encode(text, /* add_special_tokens = */ true)
@@ -134,7 +202,7 @@ impl Translator {
         }
 
         // 4. 用 tokenizer_dec 解码生成后的序列（去掉开头的 decoder_start_token_id）
-        println!("Decoding generated tokens with Chinese tokenizer...");
+        verbose!("Decoding generated tokens with Chinese tokenizer...");
         let translation = self
             .tokenizer_dec
             .decode(&token_ids[1..], /* skip_special_tokens = */ true)
@@ -142,10 +210,213 @@ impl Translator {
 
         // 5. 注意要清空 KV 缓存，避免下次翻译时冲突
         self.model.reset_kv_cache();
+        if let Some(cache) = self.cache.as_mut() {
+            cache.insert(text, &translation);
+        }
+
+        verbose!("Translation completed successfully.");
+        Ok(self.replace(translation))
+    }
+
+    /// 逐行读取并翻译，每翻译完一行就立即写出并 flush，内存占用与输入大小无关。
+    ///
+    /// 切分方式见 [`translate_lines`]。每行完成后调用 `on_line(已完成行数)`，
+    /// 并报告一次总量未知的进度。返回处理的总行数。
+    pub fn translate_reader<R: BufRead, W: Write>(
+        &mut self,
+        reader: R,
+        writer: W,
+        mut on_line: impl FnMut(usize),
+    ) -> anyhow::Result<usize> {
+        translate_lines(reader, writer, |chunk| self.translate(chunk), |count| {
+            on_line(count);
+            progress::report("translate", None);
+        })
+    }
+
+    /// 与 [`Translator::translate_reader`] 按相同的方式切分和翻译，但返回每个片段的原文、译文与字符偏移，
+    /// 便于构建双语对照视图。空行和行首时间戳不产生片段。每行完成后调用 `on_line(已完成行数)`。
+    pub fn translate_aligned<R: BufRead>(
+        &mut self,
+        reader: R,
+        mut on_line: impl FnMut(usize),
+    ) -> anyhow::Result<Vec<AlignedChunk>> {
+        translate_aligned_lines(reader, |chunk| self.translate(chunk), |count| {
+            on_line(count);
+            progress::report("translate", None);
+        })
+    }
+
+    /// 依次翻译 `chunks` 中的源文本片段，每翻译完一个就产出其译文，便于界面逐步显示。
+    ///
+    /// 返回的迭代器是惰性的，取下一项时才翻译下一个片段；某个片段失败时产出 `Err`，调用方可以选择停止或继续。
+    /// 片段通常来自 [`line_chunks`]，与 [`Translator::translate_reader`] 的切分方式一致。
+    pub fn translate_stream<'a, I>(&'a mut self, chunks: I) -> impl Iterator<Item = anyhow::Result<String>> + 'a
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        I::IntoIter: 'a,
+    {
+        chunks.into_iter().map(move |chunk| self.translate(chunk.as_ref().trim()))
+    }
+
+    /// 依次翻译多段文本，复用同一个已加载的模型，返回顺序与输入一致。
+    ///
+    /// 每段开始翻译前调用 `on_progress(当前序号, 总数)`，序号从 1 开始，同时报告已完成的百分比。
+    pub fn translate_batch<S: AsRef<str>>(
+        &mut self,
+        texts: &[S],
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> anyhow::Result<Vec<String>> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                on_progress(index + 1, texts.len());
+                progress::report("translate", Some(index as f64 * 100.0 / texts.len() as f64));
+                self.translate(text.as_ref().trim())
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .inspect(|_| progress::report("translate", Some(100.0)))
+    }
+}
+
+/// [`Translator::translate_reader`] 的实现：逐行读取 `reader`，用 `translate` 翻译每个片段，
+/// 每行译完就写入 `writer` 并 flush，返回处理的总行数。
+///
+/// 空行原样保留，行首的 `[HH:MM:SS]` 时间戳不送入模型、翻译后重新加回，过长的行先按句子切分
+/// （见 [`line_chunks`]），各片段去掉首尾空白后分别翻译再拼接。每行完成后调用 `on_line(已完成行数)`。
+fn translate_lines<R: BufRead, W: Write>(
+    reader: R,
+    mut writer: W,
+    mut translate: impl FnMut(&str) -> anyhow::Result<String>,
+    mut on_line: impl FnMut(usize),
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    for line in reader.lines() {
+        let line = line?;
+        // 输入可能是带 BOM 的文件（例如之前用 --bom 写出的结果）
+        let line = if count == 0 { line.trim_start_matches('\u{feff}') } else { &line };
+        // `[HH:MM:SS] text` 这类行只翻译正文，时间戳原样保留
+        let (timestamp, chunks) = line_chunks(line);
+        let translated = chunks.into_iter().map(|chunk| translate(chunk.trim())).collect::<anyhow::Result<Vec<_>>>()?.join("");
+        match timestamp {
+            Some(timestamp) => writeln!(writer, "{} {}", timestamp, translated)?,
+            None => writeln!(writer, "{}", translated)?,
+        }
+        writer.flush()?;
+        count += 1;
+        on_line(count);
+    }
+    Ok(count)
+}
+
+/// [`Translator::translate_aligned`] 的实现：片段去掉首尾空白后交给 `translate`（与 [`translate_lines`] 一致，
+/// 相同的句子命中同一条缓存），`source`、`start`/`end` 仍对应未去空白的原始片段
+fn translate_aligned_lines<R: BufRead>(
+    mut reader: R,
+    mut translate: impl FnMut(&str) -> anyhow::Result<String>,
+    mut on_line: impl FnMut(usize),
+) -> anyhow::Result<Vec<AlignedChunk>> {
+    let mut chunks = Vec::new();
+    let mut raw = String::new();
+    // 当前行第一个字符在整个输入中的字符偏移
+    let mut line_start = 0;
+    let mut count = 0;
+    loop {
+        raw.clear();
+        if reader.read_line(&mut raw)? == 0 {
+            break;
+        }
+        let mut line = raw.as_str();
+        if count == 0 {
+            line = line.trim_start_matches('\u{feff}');
+        }
+        let line_chars = line.chars().count();
+        let line = line.trim_end_matches(['\n', '\r']);
+        let (_, text) = split_timestamp_prefix(line);
+        if !text.trim().is_empty() {
+            for chunk in split_sentences(text, MAX_CHUNK_CHARS) {
+                // 片段都是 `line` 的子串，按地址差求出在行内的字节位置
+                let byte_offset = chunk.as_ptr() as usize - line.as_ptr() as usize;
+                let start = line_start + line[..byte_offset].chars().count();
+                chunks.push(AlignedChunk {
+                    start,
+                    end: start + chunk.chars().count(),
+                    source: chunk.to_string(),
+                    translation: translate(chunk.trim())?,
+                });
+            }
+        }
+        line_start += line_chars;
+        count += 1;
+        on_line(count);
+    }
+    Ok(chunks)
+}
 
-        println!("Translation completed successfully.");
-        Ok(translation)
+/// `translate --stream` 输出一行：先写行首时间戳，再把 `translations`（通常来自 [`Translator::translate_stream`]）
+/// 产出的每个片段写入 `writer` 并立即 flush，最后换行。某个片段翻译失败时返回其错误，已写出的片段保留
+pub fn write_streamed_line<W: Write>(
+    mut writer: W,
+    timestamp: Option<&str>,
+    translations: impl Iterator<Item = anyhow::Result<String>>,
+) -> anyhow::Result<()> {
+    if let Some(timestamp) = timestamp {
+        write!(writer, "{} ", timestamp)?;
     }
+    for translated in translations {
+        write!(writer, "{}", translated?)?;
+        writer.flush()?;
+    }
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// 检查分词器是否与模型配套：
+///
+/// - 词表不能大于模型的词表，否则分词器来自另一个模型；
+/// - `</s>` 与 `<pad>` 的编号要与模型配置一致；
+/// - 用该语言的探测文本 `probe` 编码时不能出现 `<unk>`，否则多半是英文和中文分词器文件放反了。
+fn check_tokenizer(tokenizer: &Tokenizer, path: &str, language: &str, probe: &str, config: &marian::Config) -> anyhow::Result<()> {
+    let vocab_size = tokenizer.get_vocab_size(true);
+    if vocab_size > config.vocab_size {
+        bail!(
+            "{} tokenizer {} has {} tokens but the model's vocabulary has {}; it belongs to a different model",
+            language, path, vocab_size, config.vocab_size
+        );
+    }
+    for (token, expected) in [("</s>", config.eos_token_id), ("<pad>", config.pad_token_id)] {
+        let id = tokenizer.token_to_id(token);
+        if id != Some(expected) {
+            bail!(
+                "{} tokenizer {} maps {} to {:?} but the model expects {}; it belongs to a different model",
+                language, path, token, id, expected
+            );
+        }
+    }
+    let unk = tokenizer.token_to_id("<unk>");
+    let encoding = tokenizer.encode(probe, false).map_err(E::msg)?;
+    let unknown = encoding.get_ids().iter().filter(|&&id| Some(id) == unk).count();
+    if unknown > 0 {
+        bail!(
+            "{} tokenizer {} cannot tokenize {} text ({} of {} tokens unknown); are the en and zh tokenizer files swapped?",
+            language, path, language, unknown, encoding.get_ids().len()
+        );
+    }
+    Ok(())
+}
+
+/// 读取 safetensors 模型文件，返回 `(张量数, 参数总数, 词表大小)`。
+///
+/// 词表大小取自共享词嵌入 `model.shared.weight` 的第一维，找不到该张量时为 `None`。
+/// 文件被截断或不是 safetensors 格式时返回错误。
+pub fn inspect_safetensors(path: &str) -> anyhow::Result<(usize, usize, Option<usize>)> {
+    let tensors = candle_core::safetensors::load(path, &Device::Cpu)?;
+    let params = tensors.values().map(|t| t.elem_count()).sum();
+    let vocab_size = tensors.get("model.shared.weight").and_then(|t| t.dims().first().copied());
+    Ok((tensors.len(), params, vocab_size))
 }
 
 /// 判断文本是否主要由英文字母构成
@@ -157,4 +428,189 @@ fn is_english(text: &str) -> bool {
         return false;
     }
     en_chars as f32 / total_chars as f32 > 0.5
-}
\ No newline at end of file
+}
+/// 将一行文本按句末标点切分，使每段不超过 `max_chars` 个字符（单句本身过长时保持原样）
+fn split_sentences(line: &str, max_chars: usize) -> Vec<&str> {
+    if line.chars().count() <= max_chars {
+        return vec![line];
+    }
+
+    // 先找出所有句子的结束位置（句末标点之后）
+    let mut ends = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?' | ';') && chars.peek().is_none_or(|(_, next)| next.is_whitespace()) {
+            ends.push(index + c.len_utf8());
+        }
+    }
+    ends.push(line.len());
+
+    // 再把相邻句子合并成不超过上限的片段
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut last_end = 0;
+    for end in ends {
+        if end <= last_end {
+            continue;
+        }
+        if line[start..end].chars().count() > max_chars && last_end > start {
+            chunks.push(line[start..last_end].trim());
+            start = last_end;
+        }
+        last_end = end;
+    }
+    if start < line.len() {
+        chunks.push(line[start..].trim());
+    }
+    chunks.retain(|c| !c.is_empty());
+    chunks
+}
+
+/// 按 [`Translator::translate_reader`] 的方式切分一行：拆出行首时间戳，正文过长时按句子切分。
+/// 返回 `(时间戳, 送入模型的片段)`，空行没有片段。
+pub fn line_chunks(line: &str) -> (Option<&str>, Vec<&str>) {
+    let (timestamp, text) = split_timestamp_prefix(line);
+    if text.trim().is_empty() {
+        return (timestamp, Vec::new());
+    }
+    (timestamp, split_sentences(text, MAX_CHUNK_CHARS))
+}
+
+/// 拆出行首形如 `[HH:MM:SS]`、`[00:01.500 --> 00:03.000]` 的时间戳，返回 `(时间戳, 正文)`；行首的 BOM 不计入正文
+fn split_timestamp_prefix(line: &str) -> (Option<&str>, &str) {
+    let line = line.trim_start_matches('\u{feff}');
+    let trimmed = line.trim_start();
+    if let Some(end) = trimmed.strip_prefix('[').and_then(|rest| rest.find(']')) {
+        let inner = &trimmed[1..end + 1];
+        let is_timestamp = inner.contains(':')
+            && inner.chars().all(|c| c.is_ascii_digit() || matches!(c, ':' | '.' | ',' | ' ' | '-' | '>'));
+        if is_timestamp {
+            return (Some(&trimmed[..end + 2]), trimmed[end + 2..].trim_start());
+        }
+    }
+    (None, line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_timestamp_prefixes() {
+        assert_eq!(split_timestamp_prefix("[00:01:02] Hello there."), (Some("[00:01:02]"), "Hello there."));
+        assert_eq!(split_timestamp_prefix("  [00:01.500 --> 00:03.000]  Hi"), (Some("[00:01.500 --> 00:03.000]"), "Hi"));
+        assert_eq!(split_timestamp_prefix("[note] Hello"), (None, "[note] Hello"));
+        assert_eq!(split_timestamp_prefix("Hello [00:01:02]"), (None, "Hello [00:01:02]"));
+    }
+
+    #[test]
+    fn ignores_a_leading_bom() {
+        assert_eq!(line_chunks("\u{feff}[00:00:01] Hello."), (Some("[00:00:01]"), vec!["Hello."]));
+        assert_eq!(line_chunks("\u{feff}Hello."), (None, vec!["Hello."]));
+    }
+
+    #[test]
+    fn blank_lines_have_no_chunks() {
+        assert_eq!(line_chunks(""), (None, vec![]));
+        assert_eq!(line_chunks("   "), (None, vec![]));
+        assert_eq!(line_chunks("[00:00:05]   "), (Some("[00:00:05]"), vec![]));
+    }
+
+    #[test]
+    fn splits_long_lines_at_sentence_ends() {
+        let line = (1..=40).map(|n| format!("This is sentence number {}.", n)).collect::<Vec<_>>().join(" ");
+        assert!(line.chars().count() > MAX_CHUNK_CHARS);
+        let (timestamp, chunks) = line_chunks(&line);
+        assert_eq!(timestamp, None);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.chars().count() <= MAX_CHUNK_CHARS && c.ends_with('.')));
+        assert_eq!(chunks.join(" "), line);
+    }
+
+    #[test]
+    fn keeps_short_lines_and_overlong_sentences_whole() {
+        assert_eq!(split_sentences("One. Two.", MAX_CHUNK_CHARS), ["One. Two."]);
+        let sentence = "word ".repeat(100);
+        assert_eq!(split_sentences(sentence.trim(), MAX_CHUNK_CHARS), [sentence.trim()]);
+        // 小数点后没有空白，不算句末
+        assert_eq!(split_sentences("Pi is 3.14. Done.", 12), ["Pi is 3.14.", "Done."]);
+    }
+
+    #[test]
+    fn translate_lines_matches_one_shot_translation() {
+        // 中等大小的转写稿：普通句子、空行和带时间戳的行；替身“翻译”为转成大写，整篇一次性转换的结果即为期望输出
+        let mut content = String::new();
+        for i in 0..500 {
+            match i % 7 {
+                0 => content.push('\n'),
+                3 => content.push_str(&format!("[00:{:02}:{:02}] Line {} has a timestamp. And a second sentence!\n", i / 60, i % 60, i)),
+                _ => content.push_str(&format!("Line {} says hello to the translator.\n", i)),
+            }
+        }
+        let input = format!("\u{feff}{}", content);
+
+        let mut output = Vec::new();
+        let mut lines_done = 0;
+        let count = translate_lines(input.as_bytes(), &mut output, |chunk| Ok(chunk.to_uppercase()), |line| lines_done = line).unwrap();
+
+        assert_eq!(count, 500);
+        assert_eq!(lines_done, 500);
+        assert_eq!(String::from_utf8(output).unwrap(), content.to_uppercase());
+    }
+
+    #[test]
+    fn aligned_chunks_translate_trimmed_text_at_untrimmed_offsets() {
+        let input = "  Hello world.  \n\n[00:00:01] Second line.\n";
+        let mut sources = Vec::new();
+        let chunks = translate_aligned_lines(input.as_bytes(), |chunk| {
+            sources.push(chunk.to_string());
+            Ok(chunk.to_uppercase())
+        }, |_| {})
+        .unwrap();
+
+        assert_eq!(sources, ["Hello world.", "Second line."]);
+        let chars: Vec<char> = input.chars().collect();
+        for chunk in &chunks {
+            assert_eq!(chars[chunk.start..chunk.end].iter().collect::<String>(), chunk.source);
+            assert_eq!(chunk.translation, chunk.source.trim().to_uppercase());
+        }
+        assert_eq!(chunks[0].source, "  Hello world.  ");
+    }
+
+    /// 记录每次 flush 时已写出内容的 writer
+    #[derive(Default)]
+    struct FlushRecorder {
+        written: Vec<u8>,
+        flushes: Vec<String>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes.push(String::from_utf8(self.written.clone()).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn streamed_line_flushes_each_chunk() {
+        let mut writer = FlushRecorder::default();
+        let translations = ["第一句。", "第二句。"].into_iter().map(|t| Ok(t.to_string()));
+        write_streamed_line(&mut writer, Some("[00:00:01]"), translations).unwrap();
+        assert_eq!(writer.flushes[..2], ["[00:00:01] 第一句。", "[00:00:01] 第一句。第二句。"]);
+        assert_eq!(String::from_utf8(writer.written).unwrap(), "[00:00:01] 第一句。第二句。\n");
+    }
+
+    #[test]
+    fn streamed_line_keeps_chunks_before_a_failure() {
+        let mut writer = FlushRecorder::default();
+        let translations = vec![Ok("第一句。".to_string()), Err(anyhow::anyhow!("model error")), Ok("第三句。".to_string())];
+        let error = write_streamed_line(&mut writer, None, translations.into_iter()).unwrap_err();
+        assert_eq!(error.to_string(), "model error");
+        assert_eq!(writer.flushes, ["第一句。"]);
+    }
+}
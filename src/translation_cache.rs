@@ -0,0 +1,110 @@
+use crate::output::{write_output, WriteOptions};
+use anyhow::Context;
+use clap::Args;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// 翻译缓存的命令行设置，所有子命令共用
+#[derive(Args, Debug, Clone, Default, Serialize)]
+pub struct CacheConfig {
+    /// 关闭翻译缓存，每段文本都重新送入模型
+    #[arg(long = "no-translation-cache", global = true, help = "Translate every chunk with the model, even when the same text was already translated")]
+    pub disabled: bool,
+    /// 缓存文件，跨多次运行复用译文；未指定时缓存只在本次运行的内存中
+    #[arg(long = "translation-cache", global = true, value_name = "FILE", value_parser = crate::paths::expand_path, env = "AUDIO_TRANS_VOX_TRANSLATION_CACHE", conflicts_with = "disabled", help = "Also keep translations in this JSON file and reuse them across runs")]
+    pub file: Option<String>,
+}
+
+/// 以源文本为键的翻译缓存，同一段文本只送入模型一次。
+///
+/// 指定了缓存文件时，启动时载入其中对应模型的条目，丢弃时写回。文件按模型分节，
+/// 形如 `{"en-zh": {"Hello": "你好"}}`，其他模型的条目原样保留。
+pub struct TranslationCache {
+    model: String,
+    entries: HashMap<String, String>,
+    file: Option<PathBuf>,
+    /// 文件中其他模型的条目，写回时一并保留
+    others: Map<String, Value>,
+    /// 是否有新条目需要写回
+    dirty: bool,
+}
+
+impl TranslationCache {
+    /// 按设置创建 `model`（例如 `en-zh`）的缓存，`--no-translation-cache` 时返回 `None`。
+    ///
+    /// 缓存文件不存在时从空缓存开始；文件损坏时给出警告并忽略其内容，写回时会被覆盖。
+    pub fn open(config: &CacheConfig, model: &str) -> Option<Self> {
+        if config.disabled {
+            return None;
+        }
+        let mut cache = Self {
+            model: model.to_string(),
+            entries: HashMap::new(),
+            file: config.file.as_ref().map(PathBuf::from),
+            others: Map::new(),
+            dirty: false,
+        };
+        if let Some(path) = cache.file.clone().filter(|path| path.exists()) {
+            match load_sections(&path) {
+                Ok(mut sections) => {
+                    if let Some(Value::Object(entries)) = sections.remove(model) {
+                        cache.entries = entries
+                            .into_iter()
+                            .filter_map(|(source, translation)| Some((source, translation.as_str()?.to_string())))
+                            .collect();
+                    }
+                    cache.others = sections;
+                }
+                Err(e) => eprintln!("Warning: ignoring translation cache {}: {:#}", path.display(), e),
+            }
+        }
+        Some(cache)
+    }
+
+    pub fn get(&self, source: &str) -> Option<&str> {
+        self.entries.get(source).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, source: &str, translation: &str) {
+        self.entries.insert(source.to_string(), translation.to_string());
+        self.dirty = true;
+    }
+
+    /// 有新条目且指定了缓存文件时写回文件
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        let Some(path) = self.file.as_ref().filter(|_| self.dirty) else {
+            return Ok(());
+        };
+        let mut sections = self.others.clone();
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort();
+        sections.insert(
+            self.model.clone(),
+            Value::Object(entries.into_iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect()),
+        );
+        let json = serde_json::to_string_pretty(&Value::Object(sections))?;
+        write_output(path, &json, &WriteOptions::default())
+            .with_context(|| format!("failed to write translation cache {}", path.display()))?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for TranslationCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+}
+
+/// 读取缓存文件中按模型分节的条目
+fn load_sections(path: &Path) -> anyhow::Result<Map<String, Value>> {
+    let text = std::fs::read_to_string(path)?;
+    match serde_json::from_str(&text)? {
+        Value::Object(sections) => Ok(sections),
+        _ => anyhow::bail!("expected a JSON object"),
+    }
+}
@@ -0,0 +1,116 @@
+use crate::audio_transcribe::Segment;
+use crate::segment_hook::SegmentSender;
+use crate::transcript::{self, Cleanup};
+use crate::translate::Translator;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// 转写与翻译之间的队列最多容纳的段落数，翻译跟不上时解码在此等待
+pub const QUEUE_CAPACITY: usize = 32;
+
+/// `--overlap-translation`：边转写边翻译。
+///
+/// Whisper 每解码出一段就经有界队列送到后台线程翻译，两个阶段同时进行；队列满时解码等待翻译（背压），
+/// 段落不会无限堆积。译文以（清理后的）原文为键记录下来，转写结束后调用方仍按最终的段落顺序组装输出，
+/// 已翻译过的段落直接取用，因此输出与不重叠时完全一致。
+pub struct TranslationWorker {
+    sender: SyncSender<Option<Segment>>,
+    worker: JoinHandle<(Translator, HashMap<String, String>)>,
+}
+
+impl TranslationWorker {
+    /// 把 `translator` 移到后台线程；段落先按 `cleanup` 清理，与最终送去翻译的文本保持一致
+    pub fn spawn(translator: Translator, cleanup: Vec<Cleanup>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Option<Segment>>(QUEUE_CAPACITY);
+        let worker = thread::spawn(move || {
+            let mut translator = translator;
+            let translations = translate_queue(&receiver, &cleanup, |source| translator.translate(source));
+            (translator, translations)
+        });
+        Self { sender, worker }
+    }
+
+    pub fn sender(&self) -> SegmentSender {
+        SegmentSender::bounded(self.sender.clone())
+    }
+
+    /// 通知后台线程不再有段落，等它翻译完已入队的段落，交还翻译器和已完成的译文
+    pub fn finish(self) -> (Translator, HashMap<String, String>) {
+        let _ = self.sender.send(None);
+        self.worker.join().expect("translation worker panicked")
+    }
+}
+
+/// 后台线程的主循环：逐个翻译队列中的段落直到收到 `None`，返回（清理后的）原文到译文的映射。
+///
+/// 空段落和已翻译过的原文不再翻译；失败的段落不记录，留到转写结束后再翻译一次，由那里报告错误。
+fn translate_queue<F>(receiver: &Receiver<Option<Segment>>, cleanup: &[Cleanup], mut translate: F) -> HashMap<String, String>
+where
+    F: FnMut(&str) -> anyhow::Result<String>,
+{
+    let mut translations = HashMap::new();
+    while let Ok(Some(segment)) = receiver.recv() {
+        let mut segments = [segment];
+        transcript::clean_segments(&mut segments, cleanup);
+        let source = segments[0].text.trim();
+        if source.is_empty() || translations.contains_key(source) {
+            continue;
+        }
+        if let Ok(translation) = translate(source) {
+            translations.insert(source.to_string(), translation);
+        }
+    }
+    translations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn segment(text: &str) -> Segment {
+        Segment { start_ms: 0, end_ms: 1000, text: text.to_string(), words: Vec::new(), speaker: None, language: None, confidence: None }
+    }
+
+    #[test]
+    fn translates_each_cleaned_source_once() {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let queue = SegmentSender::bounded(sender.clone());
+        for text in [" hello  world ", "hello world", "   ", "fail", "bye"] {
+            queue.send(segment(text));
+        }
+        sender.send(None).unwrap();
+
+        let mut calls = Vec::new();
+        let translations = translate_queue(&receiver, &[Cleanup::Spaces], |source| {
+            calls.push(source.to_string());
+            match source {
+                "fail" => anyhow::bail!("model error"),
+                _ => Ok(source.to_uppercase()),
+            }
+        });
+
+        assert_eq!(calls, ["hello world", "fail", "bye"]);
+        assert_eq!(translations.len(), 2);
+        assert_eq!(translations["hello world"], "HELLO WORLD");
+        assert_eq!(translations["bye"], "BYE");
+    }
+
+    #[test]
+    fn bounded_sender_waits_for_the_consumer() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let queue = SegmentSender::bounded(sender);
+        let producer = thread::spawn(move || {
+            queue.send(segment("first"));
+            queue.send(segment("second"));
+        });
+
+        // 队列只有一个空位，第二段要等第一段被取走后才能送入
+        thread::sleep(Duration::from_millis(100));
+        assert!(!producer.is_finished());
+        assert_eq!(receiver.recv().unwrap().unwrap().text, "first");
+        producer.join().unwrap();
+        assert_eq!(receiver.recv().unwrap().unwrap().text, "second");
+    }
+}
@@ -0,0 +1,43 @@
+use std::ops::Range;
+
+/// 分析帧长（毫秒）
+const FRAME_MS: usize = 30;
+/// 间隔短于该时长（毫秒）的相邻语音区域合并为一段，避免在句中停顿处切开
+const MIN_SILENCE_MS: usize = 1000;
+/// 每个语音区域前后额外保留的时长（毫秒），防止截掉轻声的开头和结尾
+const PADDING_MS: usize = 250;
+
+/// 基于能量的简单语音活动检测，返回包含语音的采样区间（按时间排序、互不重叠）。
+///
+/// 以 30ms 为一帧计算均方根电平，高于 `threshold_dbfs` 的帧视为语音；
+/// 相距不足 1 秒的区域会被合并，每个区域前后各扩展 250ms。
+pub fn speech_regions(samples: &[f32], sample_rate: u32, threshold_dbfs: f32) -> Vec<Range<usize>> {
+    let frame = (sample_rate as usize * FRAME_MS / 1000).max(1);
+    let min_gap = sample_rate as usize * MIN_SILENCE_MS / 1000;
+    let padding = sample_rate as usize * PADDING_MS / 1000;
+
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for (index, chunk) in samples.chunks(frame).enumerate() {
+        let rms = (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt();
+        if rms <= 0.0 || 20.0 * rms.log10() < threshold_dbfs {
+            continue;
+        }
+        let start = index * frame;
+        let end = start + chunk.len();
+        match regions.last_mut() {
+            Some(last) if start - last.end < min_gap => last.end = end,
+            _ => regions.push(start..end),
+        }
+    }
+
+    // 扩展边界后可能重新相邻或重叠，再合并一次
+    let mut padded: Vec<Range<usize>> = Vec::with_capacity(regions.len());
+    for region in regions {
+        let region = region.start.saturating_sub(padding)..(region.end + padding).min(samples.len());
+        match padded.last_mut() {
+            Some(last) if region.start <= last.end => last.end = region.end,
+            _ => padded.push(region),
+        }
+    }
+    padded
+}
@@ -0,0 +1,229 @@
+use anyhow::{bail, Context};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Take};
+use std::path::Path;
+
+/// `WAVE_FORMAT_PCM`
+const FORMAT_PCM: u16 = 1;
+/// `WAVE_FORMAT_IEEE_FLOAT`
+const FORMAT_IEEE_FLOAT: u16 = 3;
+/// `WAVE_FORMAT_EXTENSIBLE`，实际格式由 SubFormat GUID 的前两个字节给出
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// 64-bit 浮点 WAV 的读取器。
+///
+/// hound 只支持 32-bit 浮点采样：64-bit 的 `WAVE_FORMAT_IEEE_FLOAT` 文件在打开时就会报错，
+/// `WAVE_FORMAT_EXTENSIBLE` 的则在读取采样时出错。这里直接解析 RIFF 块，逐个读取 `f64` 采样并转换为 `f32`。
+pub struct Float64WavReader {
+    channels: u16,
+    sample_rate: u32,
+    data: Take<BufReader<File>>,
+}
+
+impl Float64WavReader {
+    /// 打开 WAV 文件；不是 64-bit 浮点格式时返回 `Ok(None)`，调用方应改用 hound 读取
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("failed to open WAV file {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let mut riff = [0u8; 12];
+        if reader.read_exact(&mut riff).is_err() || &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Ok(None);
+        }
+
+        let mut format = None;
+        loop {
+            let mut header = [0u8; 8];
+            if reader.read_exact(&mut header).is_err() {
+                return Ok(None);
+            }
+            let len = u32::from_le_bytes(header[4..8].try_into().unwrap());
+            match &header[0..4] {
+                b"fmt " => {
+                    let mut chunk = vec![0u8; len as usize];
+                    reader.read_exact(&mut chunk).context("truncated fmt chunk")?;
+                    if len % 2 == 1 {
+                        reader.read_exact(&mut [0u8; 1])?;
+                    }
+                    format = parse_format(&chunk);
+                    if format.is_none() {
+                        return Ok(None);
+                    }
+                }
+                b"data" => {
+                    let Some((channels, sample_rate)) = format else {
+                        bail!("data chunk appears before the fmt chunk");
+                    };
+                    return Ok(Some(Self { channels, sample_rate, data: reader.take(len as u64) }));
+                }
+                _ => skip_chunk(&mut reader, len)?,
+            }
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// 按文件中的顺序（多声道时交错）逐个返回采样，末尾不足 8 字节的残缺采样被忽略
+    pub fn into_samples(mut self) -> impl Iterator<Item = io::Result<f32>> {
+        std::iter::from_fn(move || {
+            let mut bytes = [0u8; 8];
+            match self.data.read_exact(&mut bytes) {
+                Ok(()) => Some(Ok(f64::from_le_bytes(bytes) as f32)),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+}
+
+/// 检查 WAV 文件的编码：ADPCM、A-law/μ-law、MP3 等压缩编码的文件 hound 无法读取，只会给出含糊的错误，
+/// 这里读取 fmt 块中的格式标签，不是 PCM 或 IEEE 浮点时返回说明编码并建议转换的错误。
+///
+/// 文件不是 RIFF/WAVE 或没有 fmt 块时返回 `Ok(())`，交由后续的读取报告具体错误。
+pub fn ensure_supported_codec(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("failed to open WAV file {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+
+    let mut riff = [0u8; 12];
+    if reader.read_exact(&mut riff).is_err() || &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Ok(());
+    }
+    loop {
+        let mut header = [0u8; 8];
+        if reader.read_exact(&mut header).is_err() {
+            return Ok(());
+        }
+        let len = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if &header[0..4] != b"fmt " {
+            skip_chunk(&mut reader, len)?;
+            continue;
+        }
+        let mut chunk = vec![0u8; len as usize];
+        if len < 2 || reader.read_exact(&mut chunk).is_err() {
+            return Ok(());
+        }
+        let mut tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+        if tag == FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+            tag = u16::from_le_bytes([chunk[24], chunk[25]]);
+        }
+        if tag == FORMAT_PCM || tag == FORMAT_IEEE_FLOAT {
+            return Ok(());
+        }
+        bail!(
+            "unsupported WAV codec in {}: {} (format tag 0x{:04X}); only PCM and float WAV files can be read, convert it first, e.g. `ffmpeg -i {} -c:a pcm_s16le converted.wav`",
+            path.display(),
+            codec_name(tag),
+            tag,
+            path.display()
+        );
+    }
+}
+
+/// 常见 WAV 格式标签对应的编码名称
+fn codec_name(tag: u16) -> &'static str {
+    match tag {
+        0x0002 => "Microsoft ADPCM",
+        0x0006 => "A-law",
+        0x0007 => "μ-law",
+        0x0011 => "IMA ADPCM",
+        0x0031 => "GSM 6.10",
+        0x0050 => "MPEG audio",
+        0x0055 => "MP3",
+        0x0161..=0x0163 => "Windows Media Audio",
+        0x2000 => "AC-3",
+        _ => "a compressed codec",
+    }
+}
+
+/// 跳过 LIST 等不需要的块（块长度为奇数时有一个填充字节）
+fn skip_chunk(reader: &mut BufReader<File>, len: u32) -> io::Result<()> {
+    let skip = len as u64 + (len % 2) as u64;
+    io::copy(&mut reader.take(skip), &mut io::sink())?;
+    Ok(())
+}
+
+/// 解析 fmt 块，是 64-bit 浮点格式时返回 `(通道数, 采样率)`
+fn parse_format(chunk: &[u8]) -> Option<(u16, u32)> {
+    if chunk.len() < 16 {
+        return None;
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes([chunk[offset], chunk[offset + 1]]);
+    let mut tag = u16_at(0);
+    let channels = u16_at(2);
+    let sample_rate = u32::from_le_bytes(chunk[4..8].try_into().unwrap());
+    let bits = u16_at(14);
+    if tag == FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+        tag = u16_at(24);
+    }
+    (tag == FORMAT_IEEE_FLOAT && bits == 64 && channels > 0).then_some((channels, sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_wav(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    /// 64-bit 浮点 WAV：fmt 块之后有一个奇数长度的 LIST 块，用来覆盖填充字节的跳过
+    fn float64_wav(channels: u16, sample_rate: u32, samples: &[f64]) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(FORMAT_IEEE_FLOAT.to_le_bytes());
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(sample_rate.to_le_bytes());
+        fmt.extend((sample_rate * channels as u32 * 8).to_le_bytes());
+        fmt.extend((channels * 8).to_le_bytes());
+        fmt.extend(64u16.to_le_bytes());
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+        let mut body = b"WAVE".to_vec();
+        for (id, chunk) in [(b"fmt ", &fmt[..]), (b"LIST", &b"INFOx"[..]), (b"data", &data[..])] {
+            body.extend(id);
+            body.extend((chunk.len() as u32).to_le_bytes());
+            body.extend(chunk);
+            if chunk.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((body.len() as u32).to_le_bytes());
+        wav.extend(body);
+        wav
+    }
+
+    #[test]
+    fn reads_float64_samples_and_rate() {
+        let samples = [0.0, 0.5, -0.25, 1.0, -1.0, 0.125];
+        let path = temp_wav("float64.wav", &float64_wav(2, 44100, &samples));
+        let reader = Float64WavReader::open(&path).unwrap().expect("64-bit float WAV not recognized");
+        assert_eq!(reader.channels(), 2);
+        assert_eq!(reader.sample_rate(), 44100);
+        let read: Vec<f32> = reader.into_samples().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, samples.map(|s| s as f32));
+    }
+
+    #[test]
+    fn leaves_other_formats_to_hound() {
+        let path = std::env::temp_dir().join(format!("audio_trans_vox_{}_pcm16.wav", std::process::id()));
+        let spec = hound::WavSpec { channels: 1, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        writer.write_sample(1000i16).unwrap();
+        writer.finalize().unwrap();
+        let reader = Float64WavReader::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(reader.is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{bail, Context};
+use crate::wav64::{self, Float64WavReader};
+use std::path::Path;
+
+/// 解码后的 WAV 音频，所有通道的采样已归一化到 [-1.0, 1.0]，多声道时交错排列
+pub struct DecodedWav {
+    pub samples: Vec<f32>,
+    pub channels: usize,
+    pub sample_rate: u32,
+    /// 采样格式的描述，例如 "16-bit PCM"、"32-bit float"
+    pub format: String,
+}
+
+impl DecodedWav {
+    /// 帧数（每帧包含所有通道各一个采样）
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// 音频时长秒数
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 / self.sample_rate as f64
+    }
+}
+
+/// 解码整个 WAV 文件，支持 8/16/24/32-bit 整数以及 32/64-bit 浮点采样
+pub fn decode(path: impl AsRef<Path>) -> anyhow::Result<DecodedWav> {
+    let path = path.as_ref();
+    wav64::ensure_supported_codec(path)?;
+    // hound 不支持 64-bit 浮点 WAV，单独读取
+    if let Some(reader) = Float64WavReader::open(path)? {
+        let channels = reader.channels().max(1) as usize;
+        let sample_rate = reader.sample_rate();
+        let samples: Vec<f32> = reader.into_samples().collect::<Result<_, _>>()?;
+        return Ok(DecodedWav { samples, channels, sample_rate, format: "64-bit float".to_string() });
+    }
+    let reader = hound::WavReader::open(path).with_context(|| format!("failed to open WAV file {}", path.display()))?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let (samples, format): (Vec<f32>, _) = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let bits = spec.bits_per_sample;
+            if !matches!(bits, 8 | 16 | 24 | 32) {
+                bail!("不支持的整数位深：{}", bits);
+            }
+            let full_scale = (1i64 << (bits - 1)) as f32;
+            let samples = reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / full_scale))
+                .collect::<Result<_, _>>()?;
+            (samples, format!("{}-bit PCM", bits))
+        }
+        hound::SampleFormat::Float => (reader.into_samples::<f32>().collect::<Result<_, _>>()?, "32-bit float".to_string()),
+    };
+    Ok(DecodedWav { samples, channels, sample_rate: spec.sample_rate, format })
+}
+
+/// 解码 WAV 文件，把所有通道的采样归一化到 [-1.0, 1.0] 后按桶统计峰值。
+///
+/// 返回 `(每个桶的峰值, 音频时长秒数)`，桶的数量为 `buckets`（音频过短时会更少）。
+pub fn bucket_peaks(path: impl AsRef<Path>, buckets: usize) -> anyhow::Result<(Vec<f32>, f64)> {
+    let wav = decode(path)?;
+    Ok((peaks_of(&wav.samples, wav.frames(), wav.channels, buckets), wav.duration()))
+}
+
+/// 多声道时每个桶覆盖完整的帧，取所有通道中的最大绝对值
+fn peaks_of(samples: &[f32], frames: usize, channels: usize, buckets: usize) -> Vec<f32> {
+    let frames_per_bucket = frames.div_ceil(buckets.max(1)).max(1);
+    samples
+        .chunks(frames_per_bucket * channels)
+        .map(|chunk| chunk.iter().fold(0.0f32, |peak, s| peak.max(s.abs())))
+        .collect()
+}
+
+/// 将峰值绘制为以中线对称的 ASCII 包络，共 `height` 行（取偶数，至少 2 行）
+pub fn render(peaks: &[f32], height: usize) -> String {
+    let half = (height / 2).max(1);
+    let mut result = String::new();
+    for row in 0..half * 2 {
+        // 上半部分从顶部向中线，下半部分从中线向底部
+        let level = if row < half { half - row } else { row - half + 1 };
+        let line: String = peaks
+            .iter()
+            .map(|&peak| {
+                let bars = (peak.min(1.0) * half as f32).ceil() as usize;
+                if bars >= level { '#' } else if level == 1 { '-' } else { ' ' }
+            })
+            .collect();
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    result
+}